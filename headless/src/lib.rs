@@ -0,0 +1,5 @@
+mod pyramid;
+mod renderer;
+
+pub use pyramid::*;
+pub use renderer::*;
@@ -0,0 +1,441 @@
+use super::{Region, Renderer};
+use image::{
+    imageops::{resize, FilterType},
+    ImageError, Rgba, RgbaImage,
+};
+use model::{
+    attributes,
+    layout::{SPRITE_REACH, TILE_SIZE},
+    minimap::{minimap_rgba, tile_minimap_color},
+    Attribute, Item, Position, Tile, World,
+};
+use rayon::prelude::*;
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+pub const PYRAMID_TILE_SIZE: u32 = 256;
+// a tile of the highest zoom covers a sector of 8x8 map tiles at full size
+pub const SECTOR_SIZE: u16 = PYRAMID_TILE_SIZE as u16 / TILE_SIZE;
+// at zoom 0 a single tile covers the whole 65536x65536 map
+pub const MAX_ZOOM: u8 = 13;
+pub const DEFAULT_MIN_ZOOM: u8 = 5;
+// below this zoom map tiles shrink under 4 pixels and minimap colours replace sprites
+const MIN_SPRITE_ZOOM: u8 = MAX_ZOOM - 3;
+const MANIFEST_FILE: &str = "hashes.txt";
+
+type Sector = (u8, u16, u16);
+type TileKey = (u8, u8, u32, u32);
+// sector x, y and its contents hash
+type SectorHash = (u16, u16, u64);
+
+#[derive(Debug)]
+pub enum Error {
+    Io,
+    Image,
+}
+
+impl From<io::Error> for Error {
+    fn from(_: io::Error) -> Self {
+        Error::Io
+    }
+}
+
+impl From<ImageError> for Error {
+    fn from(_: ImageError) -> Self {
+        Error::Image
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ExportSummary {
+    pub written: usize,
+    pub skipped: usize,
+    // tiles of the previous export without map content anymore
+    pub removed: usize,
+}
+
+// writes {floor}/{zoom}/{x}/{y}.png tiles, only tiles over sectors with map content exist
+pub struct Pyramid<'a> {
+    items: &'a HashMap<u16, Item>,
+    min_zoom: u8,
+    max_zoom: u8,
+}
+
+impl<'a> Pyramid<'a> {
+    pub fn new(items: &'a HashMap<u16, Item>) -> Self {
+        Self {
+            items,
+            min_zoom: DEFAULT_MIN_ZOOM,
+            max_zoom: MAX_ZOOM,
+        }
+    }
+
+    pub fn min_zoom(mut self, min_zoom: u8) -> Self {
+        self.min_zoom = min_zoom.min(MAX_ZOOM);
+        self
+    }
+
+    pub fn max_zoom(mut self, max_zoom: u8) -> Self {
+        self.max_zoom = max_zoom.min(MAX_ZOOM);
+        self
+    }
+
+    // tiles whose sectors hash the same as in the previous export's manifest are left untouched,
+    // the hashes cover the items drawn in them, so changed assets are drawn again
+    pub fn export(&self, world: &World, directory: &Path) -> Result<ExportSummary, Error> {
+        let item_hashes = item_hashes(world, self.items);
+        let (sector_tiles, sector_hashes) = sectors(world, &item_hashes);
+        let previous = read_manifest(directory);
+
+        let mut tiles = BTreeMap::<TileKey, Vec<SectorHash>>::new();
+        for zoom in self.min_zoom..=self.max_zoom {
+            let shift = MAX_ZOOM - zoom;
+            for ((z, x, y), hash) in sector_hashes.iter() {
+                tiles
+                    .entry((*z, zoom, *x as u32 >> shift, *y as u32 >> shift))
+                    .or_default()
+                    .push((*x, *y, *hash));
+            }
+        }
+
+        let tiles: Vec<_> = tiles
+            .into_par_iter()
+            .map(|(key, mut sectors)| {
+                sectors.sort();
+                let mut hasher = Fnv::new();
+                for (x, y, hash) in sectors.iter() {
+                    hasher
+                        .write(&x.to_le_bytes())
+                        .write(&y.to_le_bytes())
+                        .write(&hash.to_le_bytes());
+                }
+                (key, sectors, hasher.finish())
+            })
+            .collect();
+
+        let written = tiles
+            .par_iter()
+            .map(|(key, sectors, hash)| {
+                let path = tile_path(directory, key);
+                if previous.get(key) == Some(hash) && path.is_file() {
+                    return Ok(false);
+                }
+
+                let image = if key.1 >= MIN_SPRITE_ZOOM {
+                    self.render_sprites(world, key, sectors, &sector_tiles)
+                } else {
+                    self.render_minimap(world, key, sectors, &sector_tiles)
+                };
+
+                if let Some(parent) = path.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                image.save(&path)?;
+                Ok(true)
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        write_manifest(directory, &tiles)?;
+
+        let exported: BTreeSet<_> = tiles.iter().map(|(key, _, _)| key).collect();
+        let mut removed = 0;
+        for key in previous.keys().filter(|key| !exported.contains(key)) {
+            match fs::remove_file(tile_path(directory, key)) {
+                Ok(()) => removed += 1,
+                Err(error) if error.kind() == io::ErrorKind::NotFound => {}
+                Err(error) => return Err(error.into()),
+            }
+        }
+
+        let written = written.into_iter().filter(|written| *written).count();
+        Ok(ExportSummary {
+            written,
+            skipped: tiles.len() - written,
+            removed,
+        })
+    }
+
+    fn render_sprites(
+        &self,
+        world: &World,
+        key: &TileKey,
+        sectors: &[SectorHash],
+        sector_tiles: &HashMap<Sector, Vec<&Position>>,
+    ) -> RgbaImage {
+        let (z, zoom, x, y) = *key;
+        let span = tile_span(zoom);
+        let region = Region {
+            x: (x * span) as u16,
+            y: (y * span) as u16,
+            z,
+            width: span as u16,
+            height: span as u16,
+        };
+
+        // sprites of the sectors right and below can reach into the tile
+        let positions: BTreeSet<_> = sectors
+            .iter()
+            .flat_map(|(x, y, _)| {
+                [(0, 0), (1, 0), (0, 1), (1, 1)].map(|(dx, dy)| {
+                    sector_tiles.get(&(z, x.saturating_add(dx), y.saturating_add(dy)))
+                })
+            })
+            .flatten()
+            .flatten()
+            .copied()
+            .collect();
+
+        let image = Renderer::new(self.items).render_tiles(world, &region, positions);
+        if span == SECTOR_SIZE as u32 {
+            image
+        } else {
+            resize(
+                &image,
+                PYRAMID_TILE_SIZE,
+                PYRAMID_TILE_SIZE,
+                FilterType::Triangle,
+            )
+        }
+    }
+
+    fn render_minimap(
+        &self,
+        world: &World,
+        key: &TileKey,
+        sectors: &[SectorHash],
+        sector_tiles: &HashMap<Sector, Vec<&Position>>,
+    ) -> RgbaImage {
+        let (z, zoom, x, y) = *key;
+        let span = tile_span(zoom);
+        let color = |item_id: u16| self.items.get(&item_id).and_then(|item| item.minimap_color);
+        let mut image = RgbaImage::new(PYRAMID_TILE_SIZE, PYRAMID_TILE_SIZE);
+
+        // a map tile covers a few pixels, or several of them share one
+        let pixel = |tile: u32| (tile as u64 * PYRAMID_TILE_SIZE as u64 / span as u64) as u32;
+        for (sector_x, sector_y, _) in sectors {
+            for position in sector_tiles
+                .get(&(z, *sector_x, *sector_y))
+                .into_iter()
+                .flatten()
+            {
+                let Some(color) = tile_minimap_color(&world.tiles()[*position], &color) else {
+                    continue;
+                };
+                let tile_x = position.x as u32 - x * span;
+                let tile_y = position.y as u32 - y * span;
+                for pixel_y in pixel(tile_y)..pixel(tile_y + 1).max(pixel(tile_y) + 1) {
+                    for pixel_x in pixel(tile_x)..pixel(tile_x + 1).max(pixel(tile_x) + 1) {
+                        image.put_pixel(pixel_x, pixel_y, Rgba(minimap_rgba(color)));
+                    }
+                }
+            }
+        }
+
+        image
+    }
+}
+
+// map tiles covered by one side of a pyramid tile
+fn tile_span(zoom: u8) -> u32 {
+    (SECTOR_SIZE as u32) << (MAX_ZOOM - zoom)
+}
+
+fn tile_path(directory: &Path, (z, zoom, x, y): &TileKey) -> PathBuf {
+    directory
+        .join(z.to_string())
+        .join(zoom.to_string())
+        .join(x.to_string())
+        .join(format!("{}.png", y))
+}
+
+// tiles are indexed by their own sector, but hashed into every sector their sprites can reach
+fn sectors<'a>(
+    world: &'a World,
+    item_hashes: &HashMap<u16, u64>,
+) -> (HashMap<Sector, Vec<&'a Position>>, HashMap<Sector, u64>) {
+    let mut sector_tiles = HashMap::<Sector, Vec<&Position>>::new();
+    let mut contents = HashMap::<Sector, Vec<(&Position, u64)>>::new();
+
+    for (position, tile) in world.tiles() {
+        let sector = |x: u16, y: u16| (position.z, x / SECTOR_SIZE, y / SECTOR_SIZE);
+        let (x, y) = (position.x, position.y);
+        let (reach_x, reach_y) = (
+            x.saturating_sub(SPRITE_REACH),
+            y.saturating_sub(SPRITE_REACH),
+        );
+        let tile_hash = tile_hash(tile, item_hashes);
+
+        sector_tiles.entry(sector(x, y)).or_default().push(position);
+
+        let reached: BTreeSet<_> = [
+            sector(x, y),
+            sector(reach_x, y),
+            sector(x, reach_y),
+            sector(reach_x, reach_y),
+        ]
+        .into();
+        for sector in reached {
+            contents
+                .entry(sector)
+                .or_default()
+                .push((position, tile_hash));
+        }
+    }
+
+    let sector_hashes = contents
+        .into_par_iter()
+        .map(|(sector, mut tiles)| {
+            tiles.sort();
+            let mut hasher = Fnv::new();
+            for (position, hash) in tiles {
+                hasher
+                    .write(&position.x.to_le_bytes())
+                    .write(&position.y.to_le_bytes())
+                    .write(&[position.z])
+                    .write(&hash.to_le_bytes());
+            }
+            (sector, hasher.finish())
+        })
+        .collect();
+
+    (sector_tiles, sector_hashes)
+}
+
+// only what changes the rendered sprites is hashed
+fn tile_hash(tile: &Tile, item_hashes: &HashMap<u16, u64>) -> u64 {
+    let mut hasher = Fnv::new();
+    for entity in tile.entities.iter() {
+        let item_id = match entity.attributes.get("item") {
+            Some(Attribute::Item(attributes::Item(item_id))) => *item_id,
+            _ => 0,
+        };
+        let count = match entity.attributes.get("count") {
+            Some(Attribute::Count(attributes::Count(count))) => *count,
+            _ => 0,
+        };
+        let fluid = match entity.attributes.get("fluid") {
+            Some(Attribute::Fluid(attributes::Fluid(fluid))) => *fluid,
+            _ => 0,
+        };
+        let item_hash = item_hashes.get(&item_id).copied().unwrap_or_default();
+        hasher
+            .write(&item_id.to_le_bytes())
+            .write(&item_hash.to_le_bytes())
+            .write(&[count, fluid]);
+    }
+    hasher.finish()
+}
+
+// items of the map with what they look like, composing the textures of the ones that weren't yet
+fn item_hashes(world: &World, items: &HashMap<u16, Item>) -> HashMap<u16, u64> {
+    let item_ids: BTreeSet<_> = world
+        .tiles()
+        .values()
+        .flat_map(|tile| tile.entities.iter())
+        .filter_map(|entity| match entity.attributes.get("item") {
+            Some(Attribute::Item(attributes::Item(item_id))) => Some(*item_id),
+            _ => None,
+        })
+        .collect();
+
+    item_ids
+        .into_par_iter()
+        .filter_map(|item_id| Some((item_id, item_hash(items.get(&item_id)?))))
+        .collect()
+}
+
+fn item_hash(item: &Item) -> u64 {
+    let mut hasher = Fnv::new();
+    hasher
+        .write(&item.client_id.to_le_bytes())
+        .write(&[
+            item.stack_order as u8,
+            item.stackable as u8,
+            item.splash as u8,
+            item.fluid_container as u8,
+            item.minimap_color.is_some() as u8,
+            item.minimap_color.unwrap_or_default(),
+        ])
+        .write(&item.draw_offset.x.to_le_bytes())
+        .write(&item.draw_offset.y.to_le_bytes())
+        .write(&item.height_offset.x.to_le_bytes())
+        .write(&item.height_offset.y.to_le_bytes());
+
+    let textures = item.textures.composed();
+    for frame in textures.frames.iter() {
+        for pattern_z in frame.patterns_z.iter() {
+            for pattern_y in pattern_z.patterns_y.iter() {
+                for pattern_x in pattern_y.patterns_x.iter() {
+                    for layer in pattern_x.layers.iter() {
+                        hasher
+                            .write(&layer.texture.width.to_le_bytes())
+                            .write(&layer.texture.height.to_le_bytes())
+                            .write(&layer.texture.rgba_bytes);
+                    }
+                }
+            }
+        }
+    }
+    hasher.finish()
+}
+
+// 64 bit FNV-1a, unlike the std hashers it gives the same hashes with every Rust release,
+// so the manifest stays valid after a toolchain update
+struct Fnv(u64);
+
+impl Fnv {
+    fn new() -> Self {
+        Fnv(0xcbf2_9ce4_8422_2325)
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> &mut Self {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+        self
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+// one "floor zoom x y hash" line per tile, a missing or broken manifest re-exports everything
+fn read_manifest(directory: &Path) -> HashMap<TileKey, u64> {
+    let Ok(manifest) = fs::read_to_string(directory.join(MANIFEST_FILE)) else {
+        return HashMap::new();
+    };
+
+    manifest
+        .lines()
+        .filter_map(|line| {
+            let mut values = line.split_whitespace();
+            Some((
+                (
+                    values.next()?.parse().ok()?,
+                    values.next()?.parse().ok()?,
+                    values.next()?.parse().ok()?,
+                    values.next()?.parse().ok()?,
+                ),
+                values.next()?.parse().ok()?,
+            ))
+        })
+        .collect()
+}
+
+fn write_manifest(
+    directory: &Path,
+    tiles: &[(TileKey, Vec<SectorHash>, u64)],
+) -> Result<(), Error> {
+    let manifest: String = tiles
+        .iter()
+        .map(|((z, zoom, x, y), _, hash)| format!("{} {} {} {} {}\n", z, zoom, x, y, hash))
+        .collect();
+    fs::create_dir_all(directory)?;
+    fs::write(directory.join(MANIFEST_FILE), manifest)?;
+    Ok(())
+}
@@ -0,0 +1,170 @@
+use image::{
+    buffer::ConvertBuffer,
+    imageops::{overlay, resize, FilterType},
+    ImageBuffer, Rgba, RgbaImage,
+};
+use model::{
+    layout::{SPRITE_REACH, TILE_SIZE},
+    scene::{Floors, Render, Scene, View, ViewMode},
+    Item, Outfit, Position, World,
+};
+use std::{collections::HashMap, convert::Infallible};
+
+// part of a floor in tiles
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Region {
+    pub x: u16,
+    pub y: u16,
+    pub z: u8,
+    pub width: u16,
+    pub height: u16,
+}
+
+// composites the scene on the CPU, the same one the WebGL and Canvas2D renderers draw
+pub struct Renderer<'a> {
+    items: &'a HashMap<u16, Item>,
+    outfits: Option<&'a HashMap<u16, Outfit>>,
+    frame: usize,
+}
+
+impl<'a> Renderer<'a> {
+    pub fn new(items: &'a HashMap<u16, Item>) -> Self {
+        Self {
+            items,
+            outfits: None,
+            frame: 0,
+        }
+    }
+
+    // animated items show this frame, it wraps around for items with fewer of them
+    pub fn frame(mut self, frame: usize) -> Self {
+        self.frame = frame;
+        self
+    }
+
+    // creatures of spawns are only drawn with outfits to dress them
+    pub fn outfits(mut self, outfits: &'a HashMap<u16, Outfit>) -> Self {
+        self.outfits = Some(outfits);
+        self
+    }
+
+    pub fn render_region(&self, world: &World, region: &Region) -> RgbaImage {
+        self.render_tiles(world, region, world.tiles().keys())
+    }
+
+    // positions outside of the region are skipped, so callers can pass a rough set of them
+    pub fn render_tiles<'p>(
+        &self,
+        world: &World,
+        region: &Region,
+        positions: impl IntoIterator<Item = &'p Position>,
+    ) -> RgbaImage {
+        // tiles right and below the region can reach into it
+        let positions = positions.into_iter().filter(|position| {
+            position.z == region.z
+                && position.x >= region.x
+                && position.y >= region.y
+                && position.x - region.x < region.width.saturating_add(SPRITE_REACH)
+                && position.y - region.y < region.height.saturating_add(SPRITE_REACH)
+        });
+
+        let no_outfits = HashMap::new();
+        let outfits = self.outfits.unwrap_or(&no_outfits);
+        let scene = Scene::with_tiles(world, positions, self.items, outfits);
+        match Render::render(self, &scene, &View::from(region)) {
+            Ok(image) => image,
+            Err(never) => match never {},
+        }
+    }
+}
+
+impl From<&Region> for View {
+    fn from(region: &Region) -> Self {
+        let width = region.width as u32 * TILE_SIZE as u32;
+        let height = region.height as u32 * TILE_SIZE as u32;
+        let x = region.x as f32 * TILE_SIZE as f32;
+        let y = region.y as f32 * TILE_SIZE as f32;
+        let (scale_x, scale_y) = (2. / width as f32, 2. / height as f32);
+
+        View {
+            transformation: [
+                scale_x,
+                0.,
+                0.,
+                0.,
+                -scale_y,
+                0.,
+                -x * scale_x - 1.,
+                y * scale_y + 1.,
+                1.,
+            ],
+            width,
+            height,
+            floors: Floors {
+                current_level: region.z,
+                view_mode: ViewMode::CurrentFloor,
+            },
+        }
+    }
+}
+
+// the visible part of the map is drawn at full size and scaled to the view when it's zoomed
+impl Render for Renderer<'_> {
+    type Output = RgbaImage;
+    type Error = Infallible;
+
+    fn render(&self, scene: &Scene, view: &View) -> Result<RgbaImage, Infallible> {
+        let Some(bounds) = view.bounds() else {
+            return Ok(RgbaImage::new(view.width, view.height));
+        };
+        let tile_size = TILE_SIZE as f32;
+        let min_x = (bounds.min_x * tile_size).round() as i64;
+        let min_y = (bounds.min_y * tile_size).round() as i64;
+        let width = ((bounds.max_x - bounds.min_x) * tile_size).round() as u32;
+        let height = ((bounds.max_y - bounds.min_y) * tile_size).round() as u32;
+        let mut image = RgbaImage::new(width, height);
+
+        // lower floors first, sprites of other floors are shifted like in the editor
+        for (z, alpha) in view.floors.visible() {
+            let floor_offset = view.floors.offset(z);
+            let pixel_offset = (floor_offset * tile_size) as i64;
+            for (_, sprites) in scene.visible_tiles(z, Some(bounds), floor_offset) {
+                for sprite in sprites {
+                    let x = sprite.x as i64 + pixel_offset - min_x;
+                    let y = sprite.y as i64 + pixel_offset - min_y;
+                    scene.with_texture(self.items, &sprite.key, self.frame, |texture| {
+                        let Some(sprite_image) = ImageBuffer::<Rgba<u8>, &[u8]>::from_raw(
+                            texture.width as u32,
+                            texture.height as u32,
+                            &texture.rgba_bytes,
+                        ) else {
+                            return;
+                        };
+
+                        // only faded floors need their own copy of the sprite
+                        if alpha < 1. {
+                            let mut faded: RgbaImage = sprite_image.convert();
+                            for pixel in faded.pixels_mut() {
+                                pixel[3] = (pixel[3] as f32 * alpha) as u8;
+                            }
+                            overlay(&mut image, &faded, x, y);
+                        } else {
+                            overlay(&mut image, &sprite_image, x, y);
+                        }
+                    });
+                }
+            }
+        }
+
+        if (width, height) == (view.width, view.height) {
+            Ok(image)
+        } else {
+            Ok(resize(
+                &image,
+                view.width,
+                view.height,
+                FilterType::Triangle,
+            ))
+        }
+    }
+}
@@ -0,0 +1,136 @@
+use model::{
+    attributes, Attribute, Entity, Item, LazyTextures, Offset, Position, SharedSprites, Sprite,
+    SpriteLayout, StackOrder, Tile, World,
+};
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::{Arc, RwLock},
+};
+
+// sprites of the fixture sheet, 32x32 each from left to right
+const GROUND: u32 = 1;
+const WALL: u32 = 2;
+const BLUE_TREE: [u32; 4] = [3, 4, 5, 6];
+const BLINKER: [u32; 2] = [7, 8];
+const DIAMOND: u32 = 9;
+const ORANGE_TREE: [u32; 4] = [10, 11, 12, 13];
+
+pub fn fixture(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("fixtures")
+        .join(name)
+}
+
+pub fn sprites() -> SharedSprites {
+    let sheet = image::open(fixture("sprites.png")).unwrap().into_rgba8();
+    let size = Sprite::SIZE as u32;
+    let sprites = (0..sheet.width() / size)
+        .map(|index| {
+            let sprite = image::imageops::crop_imm(&sheet, index * size, 0, size, size);
+            let sprite = Sprite {
+                id: index + 1,
+                width: Sprite::SIZE,
+                height: Sprite::SIZE,
+                rgba_bytes: sprite.to_image().into_raw(),
+            };
+            (sprite.id, sprite)
+        })
+        .collect();
+    Arc::new(RwLock::new(sprites))
+}
+
+pub fn item(
+    server_id: u16,
+    (width, height, frames): (u8, u8, u8),
+    sprite_ids: &[u32],
+    sprites: &SharedSprites,
+) -> Item {
+    let sprite_layout = SpriteLayout {
+        width,
+        height,
+        layers: 1,
+        patterns_x: 1,
+        patterns_y: 1,
+        patterns_z: 1,
+        frames,
+        frame_durations: Vec::new(),
+        sprite_ids: sprite_ids.to_vec(),
+    };
+    Item {
+        server_id,
+        client_id: server_id,
+        name: None,
+        minimap_color: None,
+        light: None,
+        ground: false,
+        stackable: false,
+        splash: false,
+        fluid_container: false,
+        stack_order: StackOrder::Common,
+        draw_offset: Offset::default(),
+        height_offset: Offset::default(),
+        textures: LazyTextures::new(sprite_layout.clone(), false, sprites.clone()),
+        sprite_layout,
+    }
+}
+
+pub fn items() -> HashMap<u16, Item> {
+    let sprites = sprites();
+    let items = [
+        Item {
+            ground: true,
+            stack_order: StackOrder::Ground,
+            ..item(100, (1, 1, 1), &[GROUND], &sprites)
+        },
+        // lifts the items above it
+        Item {
+            stack_order: StackOrder::Bottom,
+            height_offset: Offset { x: 8, y: 8 },
+            ..item(101, (1, 1, 1), &[WALL], &sprites)
+        },
+        item(102, (2, 2, 1), &BLUE_TREE, &sprites),
+        item(103, (1, 1, 2), &BLINKER, &sprites),
+        Item {
+            stack_order: StackOrder::Top,
+            draw_offset: Offset { x: 8, y: 8 },
+            ..item(104, (1, 1, 1), &[DIAMOND], &sprites)
+        },
+        item(105, (2, 2, 1), &ORANGE_TREE, &sprites),
+    ];
+    items
+        .into_iter()
+        .map(|item| (item.server_id, item))
+        .collect()
+}
+
+pub fn world() -> World {
+    let mut world = World::new(64, 64);
+    for x in 26..=37 {
+        for y in 26..=37 {
+            world.add_tile(Position(x, y, 7), Tile::default());
+            place(&mut world, (x, y), 100);
+        }
+    }
+    // the blinker stands on the wall, the diamond is drawn over both
+    place(&mut world, (29, 29), 104);
+    place(&mut world, (29, 29), 103);
+    place(&mut world, (29, 29), 101);
+    // below the chunk edge, reaching over it into (30, 31)
+    place(&mut world, (30, 32), 105);
+    // above the chunk edge, drawn over the orange tree like everywhere else on the floor
+    place(&mut world, (31, 31), 102);
+    // right of the region, reaching into it
+    place(&mut world, (36, 33), 102);
+    world
+}
+
+pub fn place(world: &mut World, (x, y): (u16, u16), item_id: u16) {
+    let mut entity = Entity::new();
+    entity.attributes.insert(
+        "item".to_string(),
+        Attribute::Item(attributes::Item(item_id)),
+    );
+    world.add_entity(Position(x, y, 7), entity, None);
+}
@@ -0,0 +1,48 @@
+mod common;
+
+use common::{fixture, items, world};
+use image::RgbaImage;
+use skyless_editor_headless::{Region, Renderer};
+
+// crosses the chunk corner at (32, 32) so sprites reach from one chunk into another
+const REGION: Region = Region {
+    x: 28,
+    y: 28,
+    z: 7,
+    width: 8,
+    height: 8,
+};
+
+// references are written again with UPDATE_GOLDEN=1
+fn assert_golden(image: &RgbaImage, name: &str) {
+    let path = fixture(name);
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        image.save(&path).unwrap();
+        return;
+    }
+
+    let reference = image::open(&path).unwrap().into_rgba8();
+    assert_eq!(image.dimensions(), reference.dimensions(), "{name}");
+    let different = image
+        .pixels()
+        .zip(reference.pixels())
+        .filter(|(pixel, reference)| pixel != reference)
+        .count();
+    assert_eq!(different, 0, "{different} pixels differ from {name}");
+}
+
+#[test]
+fn region_matches_reference() {
+    let items = items();
+    let image = Renderer::new(&items).render_region(&world(), &REGION);
+    assert_golden(&image, "region.png");
+}
+
+#[test]
+fn animated_frame_matches_reference() {
+    let items = items();
+    let image = Renderer::new(&items)
+        .frame(1)
+        .render_region(&world(), &REGION);
+    assert_golden(&image, "region_frame_1.png");
+}
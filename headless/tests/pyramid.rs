@@ -0,0 +1,91 @@
+mod common;
+
+use common::{item, items, sprites, world};
+use model::World;
+use skyless_editor_headless::{Pyramid, MAX_ZOOM};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+const DIAMOND: u32 = 9;
+
+fn directory(name: &str) -> PathBuf {
+    let directory = std::env::temp_dir().join(format!("pyramid-{}-{name}", std::process::id()));
+    fs::remove_dir_all(&directory).ok();
+    directory
+}
+
+#[test]
+fn unchanged_tiles_are_skipped() {
+    let directory = directory("unchanged");
+    let (items, world) = (items(), world());
+    let pyramid = Pyramid::new(&items).min_zoom(MAX_ZOOM);
+
+    let first = pyramid.export(&world, &directory).unwrap();
+    let second = pyramid.export(&world, &directory).unwrap();
+    fs::remove_dir_all(&directory).ok();
+
+    assert!(first.written > 0);
+    assert_eq!((second.written, second.skipped), (0, first.written));
+}
+
+#[test]
+fn changed_items_are_drawn_again() {
+    let directory = directory("changed");
+    let (mut items, world) = (items(), world());
+    let first = Pyramid::new(&items)
+        .min_zoom(MAX_ZOOM)
+        .export(&world, &directory)
+        .unwrap();
+
+    // the blue tree only stands in some of the tiles
+    items.insert(102, item(102, (1, 1, 1), &[DIAMOND], &sprites()));
+    let second = Pyramid::new(&items)
+        .min_zoom(MAX_ZOOM)
+        .export(&world, &directory)
+        .unwrap();
+    fs::remove_dir_all(&directory).ok();
+
+    assert!(second.written > 0);
+    assert!(second.written < first.written);
+}
+
+#[test]
+fn tiles_without_content_are_removed() {
+    let directory = directory("removed");
+    let (items, world) = (items(), world());
+    let first = Pyramid::new(&items)
+        .min_zoom(MAX_ZOOM)
+        .export(&world, &directory)
+        .unwrap();
+
+    let mut left = World::new(64, 64);
+    for (position, tile) in world.tiles().iter().filter(|(position, _)| position.x < 30) {
+        left.add_tile(position.clone(), tile.clone());
+    }
+    let second = Pyramid::new(&items)
+        .min_zoom(MAX_ZOOM)
+        .export(&left, &directory)
+        .unwrap();
+    let pngs = count_pngs(&directory);
+    fs::remove_dir_all(&directory).ok();
+
+    assert!(second.removed > 0);
+    assert_eq!(
+        second.written + second.skipped + second.removed,
+        first.written
+    );
+    assert_eq!(pngs, second.written + second.skipped);
+}
+
+fn count_pngs(directory: &Path) -> usize {
+    fs::read_dir(directory)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .map(|path| match path.is_dir() {
+            true => count_pngs(&path),
+            false => path.extension().is_some_and(|extension| extension == "png") as usize,
+        })
+        .sum()
+}
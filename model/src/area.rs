@@ -2,6 +2,7 @@ use super::Position;
 use rkyv::{Archive, Deserialize, Serialize};
 
 #[derive(Clone, Archive, Deserialize, Serialize)]
+#[archive(check_bytes)]
 pub struct Area {
     name: String,
     parent: Option<String>,
@@ -1,16 +1,19 @@
-use super::attributes::{Container, Count, Fluid, Item};
+use super::attributes::{Container, Count, Fluid, House, Item, TileFlags};
 use rkyv::{Archive, Deserialize, Serialize};
 use std::{collections::HashMap, fmt::Debug};
 use strum::Display;
 use wasm_bindgen::prelude::wasm_bindgen;
 
 #[derive(Debug, Clone, Display, Archive, Deserialize, Serialize)]
+#[archive(check_bytes)]
 #[strum(serialize_all = "snake_case")]
 pub enum Attribute {
     Container(Container),
     Count(Count),
     Fluid(Fluid),
+    House(House),
     Item(Item),
+    TileFlags(TileFlags),
 }
 
 pub type AttributeType = Attribute;
@@ -3,5 +3,13 @@ use rkyv::{Archive, Deserialize, Serialize};
 use wasm_bindgen::prelude::wasm_bindgen;
 
 #[derive(Debug, Clone, Archive, Deserialize, Serialize)]
+#[archive(check_bytes)]
 #[archive(bound(serialize = "__S: rkyv::ser::ScratchSpace + rkyv::ser::Serializer"))]
-pub struct Container(#[omit_bounds] pub Vec<Entity>);
+#[archive_attr(check_bytes(
+    bound = "__C: rkyv::validation::ArchiveContext, <__C as rkyv::Fallible>::Error: std::error::Error"
+))]
+pub struct Container(
+    #[omit_bounds]
+    #[archive_attr(omit_bounds)]
+    pub Vec<Entity>,
+);
@@ -1,4 +1,5 @@
 use rkyv::{Archive, Deserialize, Serialize};
 
 #[derive(Debug, Clone, Archive, Deserialize, Serialize)]
+#[archive(check_bytes)]
 pub struct Fluid(pub u8);
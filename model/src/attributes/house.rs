@@ -0,0 +1,5 @@
+use rkyv::{Archive, Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Archive, Deserialize, Serialize)]
+#[archive(check_bytes)]
+pub struct House(pub u32);
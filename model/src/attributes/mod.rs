@@ -1,9 +1,13 @@
 mod container;
 mod count;
 mod fluid;
+mod house;
 mod item;
+mod tile_flags;
 
 pub use container::Container;
 pub use count::Count;
 pub use fluid::Fluid;
+pub use house::House;
 pub use item::Item;
+pub use tile_flags::TileFlags;
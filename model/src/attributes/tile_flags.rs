@@ -0,0 +1,16 @@
+use rkyv::{Archive, Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Archive, Deserialize, Serialize)]
+#[archive(check_bytes)]
+pub struct TileFlags(pub u32);
+
+impl TileFlags {
+    pub const PROTECTION_ZONE: u32 = 1;
+    pub const NO_PVP: u32 = 1 << 2;
+    pub const NO_LOGOUT: u32 = 1 << 3;
+    pub const PVP_ZONE: u32 = 1 << 4;
+
+    pub fn contains(&self, flag: u32) -> bool {
+        self.0 & flag != 0
+    }
+}
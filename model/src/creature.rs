@@ -0,0 +1,12 @@
+use super::{Direction, Look, Position};
+use rkyv::{Archive, Deserialize, Serialize};
+
+// look is missing when the server data doesn't describe the creature
+#[derive(Debug, Clone, Archive, Deserialize, Serialize)]
+#[archive(check_bytes)]
+pub struct Creature {
+    pub name: String,
+    pub position: Position,
+    pub direction: Direction,
+    pub look: Option<Look>,
+}
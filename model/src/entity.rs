@@ -4,6 +4,7 @@ use std::collections::HashMap;
 use wasm_bindgen::prelude::wasm_bindgen;
 
 #[derive(Debug, Default, Clone, Archive, Deserialize, Serialize)]
+#[archive(check_bytes)]
 pub struct Entity {
     pub attributes: AttributesType,
 }
@@ -1,14 +1,25 @@
-use super::{Offset, Texture, Textures};
-use rkyv::{Archive, Deserialize, Serialize};
+use super::{
+    attributes::{Count, Fluid},
+    Attribute, AttributesType, LazyTextures, Light, Offset, Position, SpriteLayout, StackOrder,
+    Texture, Textures, TexturesGetBuilder,
+};
+use rkyv::{with::Skip, Archive, Deserialize, Serialize};
 use wasm_bindgen::{prelude::wasm_bindgen, JsValue};
 
 #[wasm_bindgen]
 #[derive(Debug, Clone, Archive, Deserialize, Serialize)]
+#[archive(check_bytes)]
 pub struct Item {
-    pub id: u16,
+    #[wasm_bindgen(js_name = serverId)]
+    pub server_id: u16,
+    #[wasm_bindgen(js_name = clientId)]
+    pub client_id: u16,
     #[wasm_bindgen(getter_with_clone)]
     pub name: Option<String>,
-    // minimap:
+    #[wasm_bindgen(skip)]
+    pub minimap_color: Option<u8>,
+    #[wasm_bindgen(skip)]
+    pub light: Option<Light>,
     #[wasm_bindgen(skip)]
     pub ground: bool,
     #[wasm_bindgen(skip)]
@@ -18,26 +29,211 @@ pub struct Item {
     #[wasm_bindgen(skip)]
     pub fluid_container: bool,
     #[wasm_bindgen(skip)]
+    pub stack_order: StackOrder,
+    #[wasm_bindgen(skip)]
     pub draw_offset: Offset,
     #[wasm_bindgen(skip)]
     pub height_offset: Offset,
     #[wasm_bindgen(skip)]
-    pub textures: Textures,
+    pub sprite_layout: SpriteLayout,
+    // composed by the client from the sprites it receives once, when they're first drawn
+    #[wasm_bindgen(skip)]
+    #[with(Skip)]
+    pub textures: LazyTextures,
+}
+
+// server fluid types repeat 8 colours, e.g. life fluid is red + 8, the client orders its
+// fluid patterns differently: empty, blue, purple, brown, brown, red, green, brown, yellow, white
+const FLUID_PATTERNS: [usize; 8] = [0, 1, 5, 3, 6, 8, 9, 2];
+
+impl Item {
+    // picks the pattern the client would draw for this item on a given position, frame is left to the caller
+    pub fn pattern(&self, position: &Position, attributes: &AttributesType) -> TexturesGetBuilder {
+        let (patterns_x, patterns_y, patterns_z) = self.textures.composed().patterns_count();
+        if patterns_x == 0 || patterns_y == 0 || patterns_z == 0 {
+            return TexturesGetBuilder::new();
+        }
+
+        let index = if self.stackable {
+            match attributes.get("count") {
+                Some(Attribute::Count(Count(count))) => Some(Self::count_pattern(*count)),
+                _ => Some(0),
+            }
+        } else if self.splash || self.fluid_container {
+            match attributes.get("fluid") {
+                Some(Attribute::Fluid(Fluid(fluid))) => {
+                    Some(FLUID_PATTERNS[*fluid as usize % FLUID_PATTERNS.len()])
+                }
+                _ => Some(0),
+            }
+        } else {
+            None
+        };
+
+        match index {
+            Some(index) => TexturesGetBuilder::new()
+                .pattern_x(index % patterns_x)
+                .pattern_y(index / patterns_x % patterns_y),
+            None => TexturesGetBuilder::new()
+                .pattern_x(position.x as usize % patterns_x)
+                .pattern_y(position.y as usize % patterns_y)
+                .pattern_z(position.z as usize % patterns_z),
+        }
+    }
+
+    fn count_pattern(count: u8) -> usize {
+        match count {
+            0..=1 => 0,
+            2 => 1,
+            3 => 2,
+            4 => 3,
+            5..=9 => 4,
+            10..=24 => 5,
+            25..=49 => 6,
+            _ => 7,
+        }
+    }
 }
 
 #[wasm_bindgen]
 impl Item {
     #[wasm_bindgen(getter)]
     pub fn texture(&self) -> Texture {
-        self.textures.get_default().clone()
+        self.textures.composed().get_default().clone()
     }
 
     #[wasm_bindgen(getter, js_name = allTextures)]
     pub fn all_textures(&self) -> Vec<JsValue> {
         self.textures
+            .composed()
             .get_all()
             .into_iter()
             .map(JsValue::from)
             .collect()
     }
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::{attributes, Entity, SharedSprites, Sprite, Tile};
+    use std::collections::HashMap;
+
+    pub(crate) fn item(
+        server_id: u16,
+        sprite_layout: SpriteLayout,
+        sprites: &SharedSprites,
+    ) -> Item {
+        Item {
+            server_id,
+            client_id: server_id,
+            name: None,
+            minimap_color: None,
+            light: None,
+            ground: false,
+            stackable: false,
+            splash: false,
+            fluid_container: false,
+            stack_order: StackOrder::Common,
+            draw_offset: Offset::default(),
+            height_offset: Offset::default(),
+            sprite_layout: sprite_layout.clone(),
+            textures: LazyTextures::new(sprite_layout, false, sprites.clone()),
+        }
+    }
+
+    // a sprite of a single colour
+    pub(crate) fn sprite(id: u32, rgba: [u8; 4]) -> Sprite {
+        Sprite {
+            id,
+            width: Sprite::SIZE,
+            height: Sprite::SIZE,
+            rgba_bytes: rgba.repeat(Sprite::SIZE as usize * Sprite::SIZE as usize),
+        }
+    }
+
+    pub(crate) fn tile(item_ids: &[u16]) -> Tile {
+        Tile {
+            entities: item_ids
+                .iter()
+                .map(|item_id| Entity {
+                    attributes: [(
+                        "item".to_string(),
+                        Attribute::Item(attributes::Item(*item_id)),
+                    )]
+                    .into(),
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    // patterns without sprites, they are only counted
+    fn patterned(patterns_x: u8, patterns_y: u8, patterns_z: u8) -> Item {
+        let count = patterns_x as u32 * patterns_y as u32 * patterns_z as u32;
+        let sprite_layout = SpriteLayout {
+            width: 1,
+            height: 1,
+            layers: 1,
+            patterns_x,
+            patterns_y,
+            patterns_z,
+            frames: 1,
+            frame_durations: Vec::new(),
+            sprite_ids: (1..=count).collect(),
+        };
+        item(100, sprite_layout, &SharedSprites::default())
+    }
+
+    fn attributes(name: &str, attribute: Attribute) -> AttributesType {
+        HashMap::from([(name.to_string(), attribute)])
+    }
+
+    #[test]
+    fn counts_pick_the_client_stack_sizes() {
+        let patterns: Vec<_> = [0, 1, 2, 3, 4, 5, 9, 10, 24, 25, 49, 50, 100]
+            .into_iter()
+            .map(Item::count_pattern)
+            .collect();
+        assert_eq!(patterns, [0, 0, 1, 2, 3, 4, 4, 5, 5, 6, 6, 7, 7]);
+    }
+
+    #[test]
+    fn stackable_items_use_the_count_pattern() {
+        let mut item = patterned(4, 2, 1);
+        item.stackable = true;
+        let position = Position(3, 5, 7);
+
+        let pattern = item.pattern(&position, &attributes("count", Attribute::Count(Count(10))));
+        assert_eq!((pattern.pattern_x, pattern.pattern_y), (1, 1));
+        let pattern = item.pattern(&position, &HashMap::new());
+        assert_eq!((pattern.pattern_x, pattern.pattern_y), (0, 0));
+    }
+
+    #[test]
+    fn fluids_use_the_client_fluid_order() {
+        let mut item = patterned(4, 3, 1);
+        item.splash = true;
+        let position = Position(3, 5, 7);
+
+        for (fluid, index) in [(0, 0), (1, 1), (2, 5), (7, 2), (10, 5)] {
+            let fluid = attributes("fluid", Attribute::Fluid(Fluid(fluid)));
+            let pattern = item.pattern(&position, &fluid);
+            assert_eq!(pattern.pattern_x + pattern.pattern_y * 4, index);
+        }
+    }
+
+    #[test]
+    fn other_items_repeat_their_patterns_over_the_map() {
+        let item = patterned(2, 3, 2);
+        let pattern = item.pattern(&Position(5, 7, 9), &HashMap::new());
+        assert_eq!(
+            (pattern.pattern_x, pattern.pattern_y, pattern.pattern_z),
+            (1, 1, 1)
+        );
+
+        let item = patterned(0, 0, 0);
+        let pattern = item.pattern(&Position(5, 7, 9), &HashMap::new());
+        assert_eq!(pattern, TexturesGetBuilder::new());
+    }
+}
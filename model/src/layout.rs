@@ -0,0 +1,119 @@
+use super::{attributes, Attribute, Item, Position, TexturesGetBuilder, Tile};
+
+pub const TILE_SIZE: u16 = 32;
+// the client stops lifting items after this many pixels
+pub const MAX_ELEVATION: u16 = 24;
+// big sprites, draw offsets and elevation reach a few tiles up-left from their tile
+pub const SPRITE_REACH: u16 = 3;
+
+// an item of a tile as it's drawn, sprites are aligned to the bottom right corner of the tile
+#[derive(Debug, Clone, Copy)]
+pub struct TileSprite<'a> {
+    pub entity: usize,
+    pub item: &'a Item,
+    pub pattern: TexturesGetBuilder,
+    // pixels the sprite is moved up-left by its draw offset and the elevation below it
+    pub shift_x: u16,
+    pub shift_y: u16,
+}
+
+// items of a tile in the order they are drawn, the same for every renderer
+pub fn tile_sprites<'a>(
+    position: &Position,
+    tile: &Tile,
+    item: impl Fn(u16) -> Option<&'a Item>,
+) -> Vec<TileSprite<'a>> {
+    let mut items: Vec<_> = tile
+        .entities
+        .iter()
+        .enumerate()
+        .filter_map(|(index, entity)| match entity.attributes.get("item") {
+            Some(Attribute::Item(attributes::Item(item_id))) => {
+                item(*item_id).map(|item| (index, entity, item))
+            }
+            _ => None,
+        })
+        .collect();
+    // stable sort keeps the map order of common items, which is bottom to top
+    items.sort_by_key(|(_, _, item)| item.stack_order);
+
+    // items with height lift everything drawn after them on the same tile
+    let mut elevation: u16 = 0;
+    let mut sprites = Vec::new();
+
+    for (index, entity, item) in items {
+        sprites.push(TileSprite {
+            entity: index,
+            item,
+            pattern: item.pattern(position, &entity.attributes),
+            shift_x: item.draw_offset.x + elevation,
+            shift_y: item.draw_offset.y + elevation,
+        });
+        elevation = (elevation + item.height_offset.x).min(MAX_ELEVATION);
+    }
+
+    sprites
+}
+
+// creatures stand on top of everything that lifts items
+pub fn tile_elevation(sprites: &[TileSprite]) -> u16 {
+    sprites.iter().fold(0, |elevation, sprite| {
+        (elevation + sprite.item.height_offset.x).min(MAX_ELEVATION)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        item::tests::{item, tile},
+        Offset, SharedSprites, SpriteLayout, StackOrder,
+    };
+    use std::collections::HashMap;
+
+    fn items() -> HashMap<u16, Item> {
+        let sprites = SharedSprites::default();
+        [
+            (1, StackOrder::Ground, 8, Offset::default()),
+            (2, StackOrder::Common, 20, Offset::default()),
+            (3, StackOrder::Common, 0, Offset::default()),
+            (4, StackOrder::Top, 0, Offset { x: 4, y: 2 }),
+        ]
+        .into_iter()
+        .map(|(id, stack_order, height, draw_offset)| {
+            let mut item = item(id, SpriteLayout::default(), &sprites);
+            item.stack_order = stack_order;
+            item.height_offset = Offset {
+                x: height,
+                y: height,
+            };
+            item.draw_offset = draw_offset;
+            (id, item)
+        })
+        .collect()
+    }
+
+    #[test]
+    fn items_are_drawn_in_stack_order() {
+        let items = items();
+        let tile = tile(&[2, 4, 1, 99, 3]);
+        let sprites = tile_sprites(&Position(0, 0, 7), &tile, |id| items.get(&id));
+
+        let entities: Vec<_> = sprites.iter().map(|sprite| sprite.entity).collect();
+        assert_eq!(entities, [2, 0, 4, 1]);
+    }
+
+    #[test]
+    fn items_are_lifted_by_the_ones_below() {
+        let items = items();
+        let tile = tile(&[2, 4, 1, 3]);
+        let sprites = tile_sprites(&Position(0, 0, 7), &tile, |id| items.get(&id));
+
+        let shifts: Vec<_> = sprites
+            .iter()
+            .map(|sprite| (sprite.shift_x, sprite.shift_y))
+            .collect();
+        assert_eq!(shifts, [(0, 0), (8, 8), (24, 24), (28, 26)]);
+        assert_eq!(tile_elevation(&sprites), MAX_ELEVATION);
+    }
+}
@@ -5,24 +5,41 @@
 mod area;
 mod attribute;
 pub mod attributes;
+mod creature;
 mod entity;
 mod item;
+pub mod layout;
 mod light;
+mod look;
+pub mod minimap;
 mod offset;
+mod outfit;
 mod position;
+pub mod scene;
+mod spawn;
+mod sprite;
+mod stack_order;
 mod texture;
 mod textures;
 mod tile;
 mod world;
 
+// the archived types are stored in tauri's snapshot files, a change that doesn't change their
+// sizes, like reordering fields of the same type, has to bump the snapshot version
 pub use area::Area;
 pub use attribute::*;
-pub use entity::Entity;
-pub use item::Item;
+pub use creature::{ArchivedCreature, Creature};
+pub use entity::{ArchivedEntity, Entity};
+pub use item::{ArchivedItem, Item};
 pub use light::Light;
+pub use look::{Direction, Look};
 pub use offset::Offset;
-pub use position::Position;
+pub use outfit::*;
+pub use position::{ArchivedPosition, Position};
+pub use spawn::{ArchivedSpawn, Spawn};
+pub use sprite::*;
+pub use stack_order::StackOrder;
 pub use texture::Texture;
 pub use textures::*;
-pub use tile::Tile;
+pub use tile::{ArchivedTile, Tile};
 pub use world::World;
@@ -1,5 +1,17 @@
-#[derive(Debug)]
+use super::minimap::{minimap_color, minimap_rgba};
+use rkyv::{Archive, Deserialize, Serialize};
+
+// level is how many tiles the light reaches, color is an index in the minimap palette
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Archive, Deserialize, Serialize)]
+#[archive(check_bytes)]
 pub struct Light {
     pub level: u16,
     pub color: u16,
 }
+
+impl Light {
+    pub fn rgb(&self) -> [u8; 3] {
+        let [r, g, b, _] = minimap_color(self.color).map_or([255; 4], minimap_rgba);
+        [r, g, b]
+    }
+}
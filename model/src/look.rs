@@ -0,0 +1,77 @@
+use rkyv::{Archive, Deserialize, Serialize};
+use wasm_bindgen::prelude::wasm_bindgen;
+
+// how a creature is dressed, colours are indexes in the outfit palette and addons a bit set
+#[wasm_bindgen]
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    Hash,
+    PartialOrd,
+    Ord,
+    Archive,
+    Deserialize,
+    Serialize,
+)]
+#[archive(check_bytes)]
+pub struct Look {
+    #[wasm_bindgen(js_name = lookType)]
+    pub look_type: u16,
+    pub head: u8,
+    pub body: u8,
+    pub legs: u8,
+    pub feet: u8,
+    pub addons: u8,
+    pub mount: u16,
+}
+
+// pattern_x of outfits follows this order
+#[wasm_bindgen]
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    Hash,
+    PartialOrd,
+    Ord,
+    Archive,
+    Deserialize,
+    Serialize,
+)]
+#[archive(check_bytes)]
+pub enum Direction {
+    North = 0,
+    East = 1,
+    #[default]
+    South = 2,
+    West = 3,
+}
+
+#[wasm_bindgen]
+impl Look {
+    #[wasm_bindgen(constructor)]
+    pub fn new(look_type: u16) -> Self {
+        Self {
+            look_type,
+            ..Default::default()
+        }
+    }
+}
+
+impl From<u8> for Direction {
+    fn from(direction: u8) -> Self {
+        match direction {
+            0 => Direction::North,
+            1 => Direction::East,
+            3 => Direction::West,
+            _ => Direction::South,
+        }
+    }
+}
@@ -0,0 +1,147 @@
+use super::{attributes, Attribute, Position, Tile};
+use std::collections::BTreeMap;
+
+// the client's 6x6x6 colour cube, index = red * 36 + green * 6 + blue
+pub const MINIMAP_COLORS: u16 = 216;
+
+const MINIMAP_COLOR_STEP: u8 = 51;
+
+#[derive(Debug, Clone)]
+pub struct MinimapFloor {
+    pub z: u8,
+    // top left tile of the image
+    pub x: u16,
+    pub y: u16,
+    // a floor can span every coordinate, one more than a u16 holds
+    pub width: u32,
+    pub height: u32,
+    pub rgba: Vec<u8>,
+}
+
+// colors outside of the palette are ignored like the client does
+pub fn minimap_color(color: u16) -> Option<u8> {
+    (color < MINIMAP_COLORS).then_some(color as u8)
+}
+
+pub fn minimap_rgba(color: u8) -> [u8; 4] {
+    [
+        color / 36 % 6 * MINIMAP_COLOR_STEP,
+        color / 6 % 6 * MINIMAP_COLOR_STEP,
+        color % 6 * MINIMAP_COLOR_STEP,
+        255,
+    ]
+}
+
+// the top-most item with a color wins, tiles without one stay transparent
+pub fn tile_minimap_color(tile: &Tile, color: &impl Fn(u16) -> Option<u8>) -> Option<u8> {
+    tile.entities
+        .iter()
+        .rev()
+        .find_map(|entity| match entity.attributes.get("item") {
+            Some(Attribute::Item(attributes::Item(item_id))) => color(*item_id),
+            _ => None,
+        })
+}
+
+// every floor is as big as the area its colored tiles span
+pub fn minimap_floors<'a>(
+    tiles: impl Iterator<Item = (&'a Position, &'a Tile)>,
+    color: impl Fn(u16) -> Option<u8>,
+) -> BTreeMap<u8, MinimapFloor> {
+    let mut colors = BTreeMap::<u8, Vec<(u16, u16, u8)>>::new();
+    for (position, tile) in tiles {
+        if let Some(color) = tile_minimap_color(tile, &color) {
+            colors
+                .entry(position.z)
+                .or_default()
+                .push((position.x, position.y, color));
+        }
+    }
+
+    colors
+        .into_iter()
+        .map(|(z, colors)| {
+            let min_x = colors.iter().map(|(x, ..)| *x).min().unwrap_or(0);
+            let min_y = colors.iter().map(|(_, y, _)| *y).min().unwrap_or(0);
+            let max_x = colors.iter().map(|(x, ..)| *x).max().unwrap_or(0);
+            let max_y = colors.iter().map(|(_, y, _)| *y).max().unwrap_or(0);
+
+            let width = max_x as u32 - min_x as u32 + 1;
+            let height = max_y as u32 - min_y as u32 + 1;
+            let mut floor = MinimapFloor::new(z, min_x, min_y, width, height);
+            for (x, y, color) in colors.into_iter() {
+                floor.set(x, y, Some(color));
+            }
+            (z, floor)
+        })
+        .collect()
+}
+
+impl MinimapFloor {
+    pub fn new(z: u8, x: u16, y: u16, width: u32, height: u32) -> Self {
+        Self {
+            z,
+            x,
+            y,
+            width,
+            height,
+            rgba: vec![0; width as usize * height as usize * 4],
+        }
+    }
+
+    pub fn contains(&self, x: u16, y: u16) -> bool {
+        x >= self.x
+            && y >= self.y
+            && ((x - self.x) as u32) < self.width
+            && ((y - self.y) as u32) < self.height
+    }
+
+    // tiles outside of the floor are left out, it has to be rebuilt to grow
+    pub fn set(&mut self, x: u16, y: u16, color: Option<u8>) -> bool {
+        if !self.contains(x, y) {
+            return false;
+        }
+        let index = ((y - self.y) as usize * self.width as usize + (x - self.x) as usize) * 4;
+        let rgba = color.map_or([0; 4], minimap_rgba);
+        self.rgba[index..index + 4].copy_from_slice(&rgba);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{item::tests::tile, Position};
+
+    #[test]
+    fn top_most_colored_item_wins() {
+        let color = |item_id| (item_id > 1).then_some(item_id as u8);
+        assert_eq!(tile_minimap_color(&tile(&[2, 3, 1]), &color), Some(3));
+        assert_eq!(tile_minimap_color(&tile(&[1]), &color), None);
+        assert_eq!(minimap_color(MINIMAP_COLORS), None);
+        assert_eq!(minimap_rgba(215), [255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn floors_span_every_coordinate() {
+        let tiles = [
+            (Position(0, 10, 7), tile(&[1])),
+            (Position(u16::MAX, 10, 7), tile(&[1])),
+            (Position(5, 5, 6), tile(&[2])),
+        ];
+        let floors = minimap_floors(tiles.iter().map(|(p, t)| (p, t)), |_| Some(215));
+
+        let floor = &floors[&7];
+        assert_eq!(
+            (floor.x, floor.y, floor.width, floor.height),
+            (0, 10, 65536, 1)
+        );
+        assert!(floor.contains(u16::MAX, 10));
+        assert!(!floor.contains(0, 11));
+        assert_eq!(floor.rgba[floor.rgba.len() - 4..], [255, 255, 255, 255]);
+
+        let floor = &floors[&6];
+        assert_eq!((floor.x, floor.y, floor.width, floor.height), (5, 5, 1, 1));
+        assert!(!floors.contains_key(&0));
+    }
+}
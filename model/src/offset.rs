@@ -1,6 +1,7 @@
 use rkyv::{Archive, Deserialize, Serialize};
 
 #[derive(Debug, Clone, Default, Archive, Deserialize, Serialize)]
+#[archive(check_bytes)]
 pub struct Offset {
     pub x: u16,
     pub y: u16,
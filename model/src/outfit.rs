@@ -0,0 +1,308 @@
+use super::{
+    Direction, LazyTextures, Look, Offset, SpriteLayout, Texture, Textures, TexturesGetBuilder,
+};
+use rkyv::{with::Skip, Archive, Deserialize, Serialize};
+use std::collections::HashMap;
+
+// the client's outfit palette, 19 hues in 7 rows of saturation and intensity
+pub const OUTFIT_COLORS: u8 = 133;
+
+const OUTFIT_HUES: u8 = 19;
+const OUTFIT_ROWS: u8 = 7;
+
+// template mask colours of the four channels
+const HEAD_MASK: [u8; 3] = [255, 255, 0];
+const BODY_MASK: [u8; 3] = [255, 0, 0];
+const LEGS_MASK: [u8; 3] = [0, 255, 0];
+const FEET_MASK: [u8; 3] = [0, 0, 255];
+
+// patterns are directions (x), addons (y) and mounted (z), the second layer is the template mask
+#[derive(Debug, Clone, Archive, Deserialize, Serialize)]
+#[archive(check_bytes)]
+pub struct Outfit {
+    pub id: u16,
+    pub draw_offset: Offset,
+    pub sprite_layout: SpriteLayout,
+    // composed by the client from the sprites it receives once, when they're first drawn
+    #[with(Skip)]
+    pub textures: LazyTextures,
+}
+
+// the same hsi conversion as the client, colours outside of the palette are the first one
+pub fn outfit_rgb(color: u8) -> [u8; 3] {
+    let color = if color < OUTFIT_COLORS { color } else { 0 };
+    let (hue, row) = (color % OUTFIT_HUES, color / OUTFIT_HUES);
+
+    // the first column is a greyscale
+    if hue == 0 {
+        let grey = ((1. - row as f32 / OUTFIT_ROWS as f32) * 255.) as u8;
+        return [grey; 3];
+    }
+
+    let hue = hue as f32 / (OUTFIT_HUES - 1) as f32;
+    let (saturation, intensity) = match row {
+        0 => (0.25, 1.),
+        1 => (0.25, 0.75),
+        2 => (0.5, 0.75),
+        3 => (0.667, 0.75),
+        4 => (1., 1.),
+        5 => (1., 0.75),
+        _ => (1., 0.5),
+    };
+
+    let low = intensity * (1. - saturation);
+    let sextant = hue * 6.;
+    let (r, g, b) = if sextant < 1. {
+        (intensity, low + (intensity - low) * sextant, low)
+    } else if sextant < 2. {
+        (
+            intensity - (intensity - low) * (sextant - 1.),
+            intensity,
+            low,
+        )
+    } else if sextant < 3. {
+        (low, intensity, low + (intensity - low) * (sextant - 2.))
+    } else if sextant < 4. {
+        (
+            low,
+            intensity - (intensity - low) * (sextant - 3.),
+            intensity,
+        )
+    } else if sextant < 5. {
+        (low + (intensity - low) * (sextant - 4.), low, intensity)
+    } else {
+        (
+            intensity,
+            low,
+            intensity - (intensity - low) * (sextant - 5.),
+        )
+    };
+
+    [(r * 255.) as u8, (g * 255.) as u8, (b * 255.) as u8]
+}
+
+impl Outfit {
+    // the mount is drawn first and the outfit switches to its mounted pattern,
+    // textures are aligned to the bottom right corner like items on a tile
+    pub fn compose(
+        &self,
+        look: &Look,
+        direction: Direction,
+        frame: usize,
+        mount: Option<&Outfit>,
+    ) -> Texture {
+        let textures = self.textures.composed();
+        let (_, patterns_y, patterns_z) = textures.patterns_count();
+        let mounted = mount.is_some() && patterns_z > 1;
+        let pattern = TexturesGetBuilder::new()
+            .frame(frame % textures.frames_count().max(1))
+            .pattern_x(direction as usize)
+            .pattern_z(mounted as usize);
+
+        // the base outfit is always there, every other row of patterns is an addon
+        let addons: Vec<_> = (0..patterns_y)
+            .filter(|addon| *addon == 0 || look.addons & (1 << (addon - 1)) != 0)
+            .map(|addon| pattern.pattern_y(addon))
+            .collect();
+
+        let mount_pattern = mount.map(|mount| {
+            let mount_textures = mount.textures.composed();
+            let pattern = TexturesGetBuilder::new()
+                .frame(frame % mount_textures.frames_count().max(1))
+                .pattern_x(direction as usize);
+            (mount_textures, pattern)
+        });
+
+        let layers = mount_pattern
+            .iter()
+            .map(|(mount_textures, pattern)| mount_textures.get(*pattern))
+            .chain(addons.iter().map(|pattern| textures.get(*pattern)));
+        let width = layers
+            .clone()
+            .map(|texture| texture.width)
+            .max()
+            .unwrap_or(0);
+        let height = layers.map(|texture| texture.height).max().unwrap_or(0);
+
+        let mut rgba = vec![0; width as usize * height as usize * 4];
+        if let Some((mount_textures, pattern)) = &mount_pattern {
+            draw(&mut rgba, width, height, mount_textures.get(*pattern), None);
+        }
+        for pattern in addons {
+            let mask =
+                (textures.layers_count() > 1).then(|| (textures.get(pattern.layer(1)), look));
+            draw(&mut rgba, width, height, textures.get(pattern), mask);
+        }
+
+        Texture {
+            width,
+            height,
+            rgba_bytes: rgba,
+        }
+    }
+}
+
+// the draw offset of mounted creatures comes from their mount
+pub fn compose_look<'a>(
+    outfits: &'a HashMap<u16, Outfit>,
+    look: &Look,
+    direction: Direction,
+    frame: usize,
+) -> Option<(&'a Offset, Texture)> {
+    let outfit = outfits.get(&look.look_type)?;
+    let mount = outfits.get(&look.mount);
+    let texture = outfit.compose(look, direction, frame, mount);
+    Some((&mount.unwrap_or(outfit).draw_offset, texture))
+}
+
+fn mask_color(mask: [u8; 3], look: &Look) -> Option<[u8; 3]> {
+    match mask {
+        HEAD_MASK => Some(outfit_rgb(look.head)),
+        BODY_MASK => Some(outfit_rgb(look.body)),
+        LEGS_MASK => Some(outfit_rgb(look.legs)),
+        FEET_MASK => Some(outfit_rgb(look.feet)),
+        _ => None,
+    }
+}
+
+pub(crate) fn draw(
+    rgba: &mut [u8],
+    width: u16,
+    height: u16,
+    texture: &Texture,
+    mask: Option<(&Texture, &Look)>,
+) {
+    let offset_x = (width - texture.width) as usize;
+    let offset_y = (height - texture.height) as usize;
+
+    for (index, pixel) in texture.rgba_bytes.chunks_exact(4).enumerate() {
+        let alpha = pixel[3] as u32;
+        if alpha == 0 {
+            continue;
+        }
+
+        let mut color = [pixel[0], pixel[1], pixel[2]];
+        let channel = mask.and_then(|(mask, look)| {
+            let mask_pixel = mask.rgba_bytes.get(index * 4..index * 4 + 3)?;
+            mask_color(mask_pixel.try_into().ok()?, look)
+        });
+        if let Some(channel) = channel {
+            for (value, channel) in color.iter_mut().zip(channel) {
+                *value = (*value as u32 * channel as u32 / 255) as u8;
+            }
+        }
+
+        let x = offset_x + index % texture.width as usize;
+        let y = offset_y + index / texture.width as usize;
+        let target = &mut rgba[(y * width as usize + x) * 4..][..4];
+        let target_alpha = target[3] as u32 * (255 - alpha) / 255;
+        let out_alpha = alpha + target_alpha;
+        for (value, source) in target.iter_mut().zip(color) {
+            *value = ((source as u32 * alpha + *value as u32 * target_alpha) / out_alpha) as u8;
+        }
+        target[3] = out_alpha as u8;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{item::tests::sprite, SharedSprites};
+
+    const RED: [u8; 4] = [255, 0, 0, 255];
+    const GREEN: [u8; 4] = [0, 255, 0, 255];
+    const BLUE: [u8; 4] = [0, 0, 255, 255];
+
+    fn outfit(
+        id: u16,
+        size: u8,
+        (layers, patterns_y, patterns_z): (u8, u8, u8),
+        sprites: &[[u8; 4]],
+    ) -> Outfit {
+        let shared = SharedSprites::default();
+        let mut sprite_ids = Vec::new();
+        for (index, rgba) in sprites.iter().enumerate() {
+            let sprite_id = id as u32 * 100 + index as u32;
+            shared
+                .write()
+                .unwrap()
+                .insert(sprite_id, sprite(sprite_id, *rgba));
+            sprite_ids.extend(std::iter::repeat_n(
+                sprite_id,
+                size as usize * size as usize,
+            ));
+        }
+        let sprite_layout = SpriteLayout {
+            width: size,
+            height: size,
+            layers,
+            patterns_x: 1,
+            patterns_y,
+            patterns_z,
+            frames: 1,
+            frame_durations: Vec::new(),
+            sprite_ids,
+        };
+        Outfit {
+            id,
+            draw_offset: Offset::default(),
+            sprite_layout: sprite_layout.clone(),
+            textures: LazyTextures::new(sprite_layout, true, shared),
+        }
+    }
+
+    fn pixel(texture: &Texture, x: usize, y: usize) -> &[u8] {
+        let index = (y * texture.width as usize + x) * 4;
+        &texture.rgba_bytes[index..index + 4]
+    }
+
+    #[test]
+    fn template_mask_colours_the_channels() {
+        let [r, g, b] = BODY_MASK;
+        let outfit = outfit(1, 1, (2, 1, 1), &[[255; 4], [r, g, b, 255]]);
+        let look = Look {
+            body: 94,
+            ..Default::default()
+        };
+
+        let texture = outfit.compose(&look, Direction::South, 0, None);
+        let [r, g, b] = outfit_rgb(94);
+        assert_eq!(pixel(&texture, 0, 0), [r, g, b, 255]);
+        assert_eq!(pixel(&texture, 31, 31), [r, g, b, 255]);
+    }
+
+    #[test]
+    fn addons_are_drawn_only_when_set() {
+        let outfit = outfit(1, 1, (1, 2, 1), &[RED, BLUE]);
+        let compose = |addons| {
+            let look = Look {
+                addons,
+                ..Default::default()
+            };
+            outfit.compose(&look, Direction::South, 0, None)
+        };
+
+        assert_eq!(pixel(&compose(0), 0, 0), RED);
+        assert_eq!(pixel(&compose(1), 0, 0), BLUE);
+        assert_eq!(pixel(&compose(2), 0, 0), RED);
+    }
+
+    #[test]
+    fn mounts_are_drawn_below_the_mounted_outfit() {
+        let mount = outfit(2, 2, (1, 1, 1), &[GREEN]);
+        let outfit = outfit(1, 1, (1, 1, 2), &[RED, BLUE]);
+        let look = Look {
+            mount: 2,
+            ..Default::default()
+        };
+
+        let texture = outfit.compose(&look, Direction::South, 0, None);
+        assert_eq!((texture.width, texture.height), (32, 32));
+        assert_eq!(pixel(&texture, 0, 0), RED);
+
+        let texture = outfit.compose(&look, Direction::South, 0, Some(&mount));
+        assert_eq!((texture.width, texture.height), (64, 64));
+        assert_eq!(pixel(&texture, 0, 0), GREEN);
+        assert_eq!(pixel(&texture, 63, 63), BLUE);
+    }
+}
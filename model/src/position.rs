@@ -4,6 +4,7 @@ use wasm_bindgen::prelude::wasm_bindgen;
 
 #[wasm_bindgen]
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Archive, Deserialize, Serialize, PartialOrd, Ord)]
+#[archive(check_bytes)]
 #[archive_attr(derive(PartialEq, Eq, Hash))]
 pub struct Position {
     pub x: u16,
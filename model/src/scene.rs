@@ -0,0 +1,685 @@
+use super::{
+    compose_look,
+    layout::{tile_elevation, tile_sprites, SPRITE_REACH, TILE_SIZE},
+    Direction, Item, Look, Offset, Outfit, Position, Texture, TexturesGetBuilder, Tile, World,
+};
+use std::{
+    collections::{hash_map::Entry, BTreeMap, HashMap},
+    ops::RangeInclusive,
+};
+use wasm_bindgen::prelude::wasm_bindgen;
+
+pub const CHUNK_SIZE: u16 = 32;
+pub const CHUNK_MARGIN: f32 = SPRITE_REACH as f32;
+
+pub const FLOOR_LEVEL: u8 = 7;
+pub const MAX_LEVEL: u8 = 15;
+const UNDERGROUND_VISIBLE_FLOORS: u8 = 2;
+
+const FADING_FLOORS: u8 = 2;
+const FADING_STEP: f32 = 0.35;
+const HIGHER_FLOOR_ALPHA: f32 = 0.5;
+
+// phase duration in milliseconds of items without their own, dat files don't have any
+pub const FRAME_DURATION: u32 = 500;
+
+// the same scene drawn by different backends, WebGL2 and Canvas2D into a canvas
+// and the headless renderer into an image
+pub trait Render {
+    type Output;
+    type Error;
+
+    fn render(&self, scene: &Scene, view: &View) -> Result<Self::Output, Self::Error>;
+}
+
+// field order makes chunks of the same floor neighbours in a BTreeMap
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ChunkKey {
+    pub z: u8,
+    pub x: u16,
+    pub y: u16,
+}
+
+// visible part of the map in tiles
+#[derive(Debug, Clone, Copy)]
+pub struct ViewBounds {
+    pub min_x: f32,
+    pub min_y: f32,
+    pub max_x: f32,
+    pub max_y: f32,
+}
+
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ViewMode {
+    AllFloors,
+    CurrentFloor,
+    Fading,
+    TransparentHigherFloor,
+}
+
+// which floors are drawn around the current one and how transparent they are
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Floors {
+    pub current_level: u8,
+    pub view_mode: ViewMode,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct View {
+    // maps map pixels to clip space
+    pub transformation: [f32; 9],
+    // size of the target in pixels
+    pub width: u32,
+    pub height: u32,
+    pub floors: Floors,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum SpriteKey {
+    // client id and pattern of the first frame, server items sharing a client id share sprites
+    Item(u16, TexturesGetBuilder),
+    // composed creatures of spawns, only their standing frame
+    Creature(Look, Direction),
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SceneSprite {
+    // index in the tile entities, creatures have none
+    pub entity: Option<usize>,
+    pub key: SpriteKey,
+    // top left corner in map pixels, floors other than the current one are shifted by the view
+    pub x: i32,
+    pub y: i32,
+    pub width: u16,
+    pub height: u16,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SceneChunk {
+    // tiles and their sprites in the order they are drawn
+    pub tiles: BTreeMap<Position, Vec<SceneSprite>>,
+}
+
+// what is drawn where, built once from the world and shared by every backend
+#[derive(Debug, Clone, Default)]
+pub struct Scene {
+    chunks: BTreeMap<ChunkKey, SceneChunk>,
+    creatures: HashMap<Position, (Look, Direction)>,
+    creature_textures: HashMap<(Look, Direction), (Offset, Texture)>,
+    // any server id of a client id, to reach the textures of item sprites
+    client_items: HashMap<u16, u16>,
+}
+
+impl From<&Position> for ChunkKey {
+    fn from(position: &Position) -> Self {
+        ChunkKey {
+            z: position.z,
+            x: position.x / CHUNK_SIZE,
+            y: position.y / CHUNK_SIZE,
+        }
+    }
+}
+
+impl SceneChunk {
+    // tiles of a column of the floor, x is on the map
+    pub fn column(&self, x: u16) -> impl Iterator<Item = (&Position, &Vec<SceneSprite>)> {
+        let start = Position {
+            x,
+            y: 0,
+            z: 0,
+            stack_pos: None,
+        };
+        let end = Position {
+            x,
+            y: u16::MAX,
+            z: u8::MAX,
+            stack_pos: Some(u16::MAX),
+        };
+        self.tiles.range(start..=end)
+    }
+}
+
+// chunks of a floor are drawn one tile column at a time, going down through every chunk of the
+// column in turn, which is the order of the tiles on the whole floor, x then y, so sprites
+// reaching over the edge of a chunk cover their neighbours the same way as inside a chunk,
+// keys are sorted and yield (index of the chunk, x in the chunk)
+pub fn floor_order(keys: &[ChunkKey]) -> Vec<(usize, u16)> {
+    let mut order = Vec::new();
+    let mut start = 0;
+    for column in keys.chunk_by(|a, b| a.x == b.x) {
+        for x in 0..CHUNK_SIZE {
+            order.extend((start..start + column.len()).map(|index| (index, x)));
+        }
+        start += column.len();
+    }
+    order
+}
+
+impl ChunkKey {
+    pub fn floor(z: u8) -> RangeInclusive<ChunkKey> {
+        ChunkKey { z, x: 0, y: 0 }..=ChunkKey {
+            z,
+            x: u16::MAX,
+            y: u16::MAX,
+        }
+    }
+
+    // floor offset shifts the whole chunk the same way as its tiles
+    pub fn is_visible(&self, bounds: &ViewBounds, floor_offset: f32) -> bool {
+        let min_x = (self.x * CHUNK_SIZE) as f32 + floor_offset - CHUNK_MARGIN;
+        let min_y = (self.y * CHUNK_SIZE) as f32 + floor_offset - CHUNK_MARGIN;
+        let max_x = (self.x * CHUNK_SIZE) as f32 + CHUNK_SIZE as f32 + floor_offset;
+        let max_y = (self.y * CHUNK_SIZE) as f32 + CHUNK_SIZE as f32 + floor_offset;
+
+        min_x <= bounds.max_x
+            && max_x >= bounds.min_x
+            && min_y <= bounds.max_y
+            && max_y >= bounds.min_y
+    }
+}
+
+impl ViewBounds {
+    pub fn new(transformation: &[f32; 9], tile_size: f32) -> Option<Self> {
+        let (x_a, y_a) = unproject(transformation, -1., -1.)?;
+        let (x_b, y_b) = unproject(transformation, 1., 1.)?;
+
+        Some(ViewBounds {
+            min_x: x_a.min(x_b) / tile_size,
+            min_y: y_a.min(y_b) / tile_size,
+            max_x: x_a.max(x_b) / tile_size,
+            max_y: y_a.max(y_b) / tile_size,
+        })
+    }
+}
+
+// maps a clip space point back to map pixels, the transformation is affine so its inverse is simple
+pub fn unproject(transformation: &[f32; 9], x: f32, y: f32) -> Option<(f32, f32)> {
+    let [a, b, _, c, d, _, tx, ty, _] = *transformation;
+    let determinant = a * d - b * c;
+    if determinant == 0. {
+        return None;
+    }
+
+    Some((
+        (d * (x - tx) - c * (y - ty)) / determinant,
+        (a * (y - ty) - b * (x - tx)) / determinant,
+    ))
+}
+
+impl Default for Floors {
+    fn default() -> Self {
+        Floors {
+            current_level: FLOOR_LEVEL,
+            view_mode: ViewMode::AllFloors,
+        }
+    }
+}
+
+impl Floors {
+    // the lowest floor visible from the current one, underground only a few floors below are visible
+    pub fn lowest_visible_level(&self) -> u8 {
+        if self.current_level <= FLOOR_LEVEL {
+            FLOOR_LEVEL
+        } else {
+            (self.current_level + UNDERGROUND_VISIBLE_FLOORS).min(MAX_LEVEL)
+        }
+    }
+
+    pub fn alpha(&self, z: u8) -> Option<f32> {
+        let current_level = self.current_level;
+        match self.view_mode {
+            ViewMode::AllFloors => {
+                (z >= current_level && z <= self.lowest_visible_level()).then_some(1.)
+            }
+            ViewMode::CurrentFloor => (z == current_level).then_some(1.),
+            ViewMode::Fading => {
+                let distance = z.abs_diff(current_level);
+                (distance <= FADING_FLOORS).then_some(1. - distance as f32 * FADING_STEP)
+            }
+            ViewMode::TransparentHigherFloor => {
+                if z >= current_level && z <= self.lowest_visible_level() {
+                    Some(1.)
+                } else if z + 1 == current_level {
+                    Some(HIGHER_FLOOR_ALPHA)
+                } else {
+                    None
+                }
+            }
+        }
+    }
+
+    // floors below are shifted down-right and floors above up-left, in tiles
+    pub fn offset(&self, z: u8) -> f32 {
+        z as f32 - self.current_level as f32
+    }
+
+    // (z, alpha) of the drawn floors, lower floors first
+    pub fn visible(&self) -> impl Iterator<Item = (u8, f32)> {
+        let floors = *self;
+        (0..=MAX_LEVEL)
+            .rev()
+            .filter_map(move |z| Some((z, floors.alpha(z)?)))
+    }
+}
+
+impl View {
+    pub fn bounds(&self) -> Option<ViewBounds> {
+        ViewBounds::new(&self.transformation, TILE_SIZE as f32)
+    }
+
+    // screen pixels taken by a tile
+    pub fn tile_pixels(&self) -> f32 {
+        let [a, b, ..] = self.transformation;
+        (a.powi(2) + b.powi(2)).sqrt() * TILE_SIZE as f32 * self.width as f32 / 2.
+    }
+}
+
+impl Scene {
+    pub fn new(world: &World, items: &HashMap<u16, Item>, outfits: &HashMap<u16, Outfit>) -> Self {
+        Self::with_tiles(world, world.tiles().keys(), items, outfits)
+    }
+
+    // only the given tiles, for renderers that draw a small part of a big map
+    pub fn with_tiles<'p>(
+        world: &World,
+        positions: impl IntoIterator<Item = &'p Position>,
+        items: &HashMap<u16, Item>,
+        outfits: &HashMap<u16, Outfit>,
+    ) -> Self {
+        let mut scene = Scene {
+            client_items: items
+                .values()
+                .map(|item| (item.client_id, item.server_id))
+                .collect(),
+            // creatures of spawns with a known look
+            creatures: world
+                .spawns()
+                .iter()
+                .flat_map(|spawn| spawn.creatures.iter())
+                .filter_map(|creature| {
+                    Some((
+                        creature.position.clone(),
+                        (creature.look?, creature.direction),
+                    ))
+                })
+                .collect(),
+            ..Default::default()
+        };
+
+        for position in positions {
+            scene.update_tile(world, items, outfits, position);
+        }
+        scene
+    }
+
+    // the key of the chunk with the tile is returned, so backends can rebuild what they keep of it
+    pub fn update_tile(
+        &mut self,
+        world: &World,
+        items: &HashMap<u16, Item>,
+        outfits: &HashMap<u16, Outfit>,
+        position: &Position,
+    ) -> ChunkKey {
+        let key = ChunkKey::from(position);
+        let sprites = world
+            .tiles()
+            .get(position)
+            .map(|tile| self.tile_sprites(position, tile, items, outfits));
+
+        let chunk = self.chunks.entry(key).or_default();
+        match sprites {
+            Some(sprites) => chunk.tiles.insert(position.clone(), sprites),
+            None => chunk.tiles.remove(position),
+        };
+        key
+    }
+
+    // sprites are aligned to the bottom right corner of their tile
+    fn tile_sprites(
+        &mut self,
+        position: &Position,
+        tile: &Tile,
+        items: &HashMap<u16, Item>,
+        outfits: &HashMap<u16, Outfit>,
+    ) -> Vec<SceneSprite> {
+        let tile_x = (position.x as i32 + 1) * TILE_SIZE as i32;
+        let tile_y = (position.y as i32 + 1) * TILE_SIZE as i32;
+
+        let sprites = tile_sprites(position, tile, |item_id| items.get(&item_id));
+        let elevation = tile_elevation(&sprites);
+        let mut scene_sprites: Vec<_> = sprites
+            .iter()
+            .map(|sprite| {
+                let textures = sprite.item.textures.composed();
+                let texture = textures.get(sprite.pattern);
+                SceneSprite {
+                    entity: Some(sprite.entity),
+                    key: SpriteKey::Item(sprite.item.client_id, sprite.pattern.frame(0)),
+                    x: tile_x - sprite.shift_x as i32 - texture.width as i32,
+                    y: tile_y - sprite.shift_y as i32 - texture.height as i32,
+                    width: texture.width,
+                    height: texture.height,
+                }
+            })
+            .collect();
+
+        // creatures are drawn over the items of their tile, lifted by them like the top item
+        let Some((look, direction)) = self.creatures.get(position).copied() else {
+            return scene_sprites;
+        };
+        let (offset, texture) = match self.creature_textures.entry((look, direction)) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                let Some((offset, texture)) = compose_look(outfits, &look, direction, 0) else {
+                    return scene_sprites;
+                };
+                entry.insert((offset.clone(), texture))
+            }
+        };
+        scene_sprites.push(SceneSprite {
+            entity: None,
+            key: SpriteKey::Creature(look, direction),
+            x: tile_x - (offset.x + elevation) as i32 - texture.width as i32,
+            y: tile_y - (offset.y + elevation) as i32 - texture.height as i32,
+            width: texture.width,
+            height: texture.height,
+        });
+
+        scene_sprites
+    }
+
+    pub fn chunks(&self) -> &BTreeMap<ChunkKey, SceneChunk> {
+        &self.chunks
+    }
+
+    pub fn chunk(&self, key: &ChunkKey) -> Option<&SceneChunk> {
+        self.chunks.get(key)
+    }
+
+    // every chunk of the floor when the view has no bounds
+    pub fn visible_chunks(
+        &self,
+        z: u8,
+        bounds: Option<ViewBounds>,
+        floor_offset: f32,
+    ) -> impl Iterator<Item = (&ChunkKey, &SceneChunk)> {
+        self.chunks
+            .range(ChunkKey::floor(z))
+            .filter(move |(key, _)| {
+                bounds.is_none_or(|bounds| key.is_visible(&bounds, floor_offset))
+            })
+    }
+
+    // tiles of the visible chunks in the order of the whole floor
+    pub fn visible_tiles(
+        &self,
+        z: u8,
+        bounds: Option<ViewBounds>,
+        floor_offset: f32,
+    ) -> impl Iterator<Item = (&Position, &Vec<SceneSprite>)> {
+        let chunks: Vec<_> = self.visible_chunks(z, bounds, floor_offset).collect();
+        let keys: Vec<_> = chunks.iter().map(|(key, _)| **key).collect();
+        floor_order(&keys).into_iter().flat_map(move |(index, x)| {
+            let (key, chunk) = chunks[index];
+            chunk.column(key.x * CHUNK_SIZE + x)
+        })
+    }
+
+    pub fn tile(&self, position: &Position) -> Option<&[SceneSprite]> {
+        self.chunks
+            .get(&ChunkKey::from(position))?
+            .tiles
+            .get(position)
+            .map(Vec::as_slice)
+    }
+
+    pub fn creature_textures(&self) -> impl Iterator<Item = (Look, Direction, &Texture)> {
+        self.creature_textures
+            .iter()
+            .map(|((look, direction), (_, texture))| (*look, *direction, texture))
+    }
+
+    // one item of every client id that is drawn, items off the map are never composed
+    pub fn client_items<'a>(
+        &'a self,
+        items: &'a HashMap<u16, Item>,
+    ) -> impl Iterator<Item = &'a Item> + 'a {
+        self.client_items
+            .values()
+            .filter_map(|server_id| items.get(server_id))
+    }
+
+    pub fn frames_count(&self, items: &HashMap<u16, Item>, key: &SpriteKey) -> usize {
+        match key {
+            SpriteKey::Item(client_id, _) => self
+                .client_items
+                .get(client_id)
+                .and_then(|server_id| items.get(server_id))
+                .map_or(1, |item| item.textures.composed().frames_count()),
+            SpriteKey::Creature(..) => 1,
+        }
+    }
+
+    // the frame wraps around for sprites with fewer of them, item textures can be composed on
+    // the way so they're only lent to the closure
+    pub fn with_texture<R>(
+        &self,
+        items: &HashMap<u16, Item>,
+        key: &SpriteKey,
+        frame: usize,
+        f: impl FnOnce(&Texture) -> R,
+    ) -> Option<R> {
+        match key {
+            SpriteKey::Item(client_id, pattern) => {
+                let item = items.get(self.client_items.get(client_id)?)?;
+                let textures = item.textures.composed();
+                let frames = textures.frames_count();
+                (frames > 0).then(|| f(textures.get(pattern.frame(frame % frames))))
+            }
+            SpriteKey::Creature(look, direction) => self
+                .creature_textures
+                .get(&(*look, *direction))
+                .map(|(_, texture)| f(texture)),
+        }
+    }
+
+    // x and y are in map tiles as seen on the screen, floors other than the current one are shifted,
+    // the entity is the top-most item under the point and the position is on the current floor
+    // when nothing is drawn there
+    pub fn pick(
+        &self,
+        items: &HashMap<u16, Item>,
+        floors: &Floors,
+        x: f32,
+        y: f32,
+    ) -> (Position, Option<usize>) {
+        // higher floors are drawn over lower ones
+        for z in 0..=MAX_LEVEL {
+            if floors.alpha(z).is_none() {
+                continue;
+            }
+            let floor_offset = floors.offset(z);
+            let (floor_x, floor_y) = (x - floor_offset, y - floor_offset);
+            if floor_x < 0. || floor_y < 0. {
+                continue;
+            }
+
+            // sprites reach up-left from their tile, tiles drawn later are checked first
+            let mut candidates = Vec::new();
+            for dx in 0..=CHUNK_MARGIN as u16 {
+                for dy in 0..=CHUNK_MARGIN as u16 {
+                    let (Some(x), Some(y)) = (
+                        (floor_x as u16).checked_add(dx),
+                        (floor_y as u16).checked_add(dy),
+                    ) else {
+                        continue;
+                    };
+                    let position = Position(x, y, z);
+                    if let Some(sprites) = self.tile(&position) {
+                        candidates.push((position, sprites));
+                    }
+                }
+            }
+            candidates.sort_by(|(a, _), (b, _)| b.cmp(a));
+
+            let pixel_x = (floor_x * TILE_SIZE as f32).floor() as i32;
+            let pixel_y = (floor_y * TILE_SIZE as f32).floor() as i32;
+            for (position, sprites) in candidates {
+                let hit = sprites.iter().rev().find(|sprite| {
+                    let (local_x, local_y) = (pixel_x - sprite.x, pixel_y - sprite.y);
+                    if sprite.entity.is_none()
+                        || local_x < 0
+                        || local_y < 0
+                        || local_x >= sprite.width as i32
+                        || local_y >= sprite.height as i32
+                    {
+                        return false;
+                    }
+                    // transparent pixels let the point through to what is below
+                    let alpha =
+                        (local_y as usize * sprite.width as usize + local_x as usize) * 4 + 3;
+                    self.with_texture(items, &sprite.key, 0, |texture| {
+                        texture
+                            .rgba_bytes
+                            .get(alpha)
+                            .is_some_and(|alpha| *alpha > 0)
+                    })
+                    .unwrap_or(false)
+                });
+
+                if let Some(sprite) = hit {
+                    return (position, sprite.entity);
+                }
+            }
+        }
+
+        // nothing is drawn there, so it's the tile of the current floor
+        let position = Position(x.max(0.) as u16, y.max(0.) as u16, floors.current_level);
+        (position, None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        item::tests::{item, sprite, tile},
+        SharedSprites, SpriteLayout, StackOrder,
+    };
+
+    // items of a single sprite repeated over their size in tiles, the item id is the sprite id
+    fn items() -> HashMap<u16, Item> {
+        let sprites = SharedSprites::default();
+        [
+            (1, StackOrder::Ground, 1, 255),
+            (2, StackOrder::Common, 1, 255),
+            (3, StackOrder::Common, 1, 0),
+            (4, StackOrder::Common, 2, 255),
+        ]
+        .into_iter()
+        .map(|(id, stack_order, size, alpha)| {
+            sprites
+                .write()
+                .unwrap()
+                .insert(id as u32, sprite(id as u32, [id as u8, 0, 0, alpha]));
+            let sprite_layout = SpriteLayout {
+                width: size,
+                height: size,
+                layers: 1,
+                patterns_x: 1,
+                patterns_y: 1,
+                patterns_z: 1,
+                frames: 1,
+                frame_durations: Vec::new(),
+                sprite_ids: vec![id as u32; size as usize * size as usize],
+            };
+            let mut item = item(id, sprite_layout, &sprites);
+            item.stack_order = stack_order;
+            (id, item)
+        })
+        .collect()
+    }
+
+    fn scene(items: &HashMap<u16, Item>, tiles: &[(Position, &[u16])]) -> Scene {
+        let mut world = World::new(64, 64);
+        for (position, item_ids) in tiles {
+            world.add_tile(position.clone(), tile(item_ids));
+        }
+        Scene::new(&world, items, &HashMap::new())
+    }
+
+    #[test]
+    fn floor_order_goes_down_columns_through_chunks() {
+        let keys = [
+            ChunkKey { z: 7, x: 0, y: 0 },
+            ChunkKey { z: 7, x: 0, y: 1 },
+            ChunkKey { z: 7, x: 1, y: 0 },
+        ];
+        let order = floor_order(&keys);
+        assert_eq!(order.len(), 3 * CHUNK_SIZE as usize);
+        assert_eq!(order[..4], [(0, 0), (1, 0), (0, 1), (1, 1)]);
+        assert_eq!(order[2 * CHUNK_SIZE as usize], (2, 0));
+        assert_eq!(order.last(), Some(&(2, CHUNK_SIZE - 1)));
+    }
+
+    #[test]
+    fn top_most_opaque_item_is_picked() {
+        let items = items();
+        let floors = Floors::default();
+        let position = Position(10, 10, 7);
+
+        assert_eq!(
+            scene(&items, &[(position.clone(), &[2, 1])]).pick(&items, &floors, 10.5, 10.5),
+            (position.clone(), Some(0))
+        );
+
+        // the transparent item lets the point through to the ground below it
+        assert_eq!(
+            scene(&items, &[(position.clone(), &[3, 1])]).pick(&items, &floors, 10.5, 10.5),
+            (position, Some(1))
+        );
+    }
+
+    #[test]
+    fn tiles_drawn_later_are_picked_first() {
+        let items = items();
+        let floors = Floors::default();
+        let tiles: [(Position, &[u16]); 2] =
+            [(Position(10, 10, 7), &[1]), (Position(11, 11, 7), &[4])];
+
+        assert_eq!(
+            scene(&items, &tiles).pick(&items, &floors, 10.5, 10.5),
+            (Position(11, 11, 7), Some(0))
+        );
+        assert_eq!(
+            scene(&items, &tiles[..1]).pick(&items, &floors, 10.5, 10.5),
+            (Position(10, 10, 7), Some(0))
+        );
+    }
+
+    #[test]
+    fn higher_floors_are_picked_first() {
+        let items = items();
+        let floors = Floors {
+            current_level: 6,
+            view_mode: ViewMode::AllFloors,
+        };
+        let tiles: [(Position, &[u16]); 2] =
+            [(Position(10, 10, 7), &[1]), (Position(11, 11, 6), &[2])];
+
+        // the lower floor is shifted down-right, under the tile of the current one
+        assert_eq!(
+            scene(&items, &tiles).pick(&items, &floors, 11.5, 11.5),
+            (Position(11, 11, 6), Some(0))
+        );
+        assert_eq!(
+            scene(&items, &tiles[..1]).pick(&items, &floors, 11.5, 11.5),
+            (Position(10, 10, 7), Some(0))
+        );
+        assert_eq!(
+            scene(&items, &tiles[..1]).pick(&items, &floors, 40.5, 40.5),
+            (Position(40, 40, 6), None)
+        );
+    }
+}
@@ -0,0 +1,10 @@
+use super::{Creature, Position};
+use rkyv::{Archive, Deserialize, Serialize};
+
+#[derive(Debug, Clone, Archive, Deserialize, Serialize)]
+#[archive(check_bytes)]
+pub struct Spawn {
+    pub position: Position,
+    pub radius: u16,
+    pub creatures: Vec<Creature>,
+}
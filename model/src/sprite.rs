@@ -1,24 +1,346 @@
-use serde::{Deserialize, Serialize};
+use super::{
+    outfit::draw, scene::FRAME_DURATION, Item, Texture, TextureFrame, TextureLayer,
+    TexturePatternX, TexturePatternY, TexturePatternZ, Textures,
+};
+use rkyv::{Archive, Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{Arc, OnceLock, PoisonError, RwLock},
+};
 
-#[derive(Clone, Deserialize, Serialize)]
+#[derive(Debug, Clone, Archive, Deserialize, Serialize)]
+#[archive(check_bytes)]
 pub struct Sprite {
-    id: u16,
-    pixels: Vec<u8>,
+    pub id: u32,
+    pub width: u16,
+    pub height: u16,
+    pub rgba_bytes: Vec<u8>,
 }
 
 impl Sprite {
+    // spr sprites and the smallest sprite sheet sprites
     pub const SIZE: u16 = 32;
-    pub const BYTES: u16 = Self::SIZE * Self::SIZE * 4;
 
-    pub fn new(id: u16, pixels: Vec<u8>) -> Self {
-        Self { id, pixels }
+    // asked for but not in the project, it's drawn as nothing
+    pub fn missing(id: u32) -> Self {
+        Sprite {
+            id,
+            width: 0,
+            height: 0,
+            rgba_bytes: Vec::new(),
+        }
     }
 
-    pub fn id(&self) -> u16 {
-        self.id
+    fn is_missing(&self) -> bool {
+        self.width == 0 || self.height == 0
+    }
+}
+
+// sprite ids in the order of dat and appearances files: frames, patterns z, y and x, layers,
+// then height and width counted from the bottom right corner
+#[derive(Debug, Clone, Default, Archive, Deserialize, Serialize)]
+#[archive(check_bytes)]
+pub struct SpriteLayout {
+    pub width: u8,
+    pub height: u8,
+    pub layers: u8,
+    pub patterns_x: u8,
+    pub patterns_y: u8,
+    pub patterns_z: u8,
+    pub frames: u8,
+    // milliseconds of every frame, empty when the file doesn't have them
+    pub frame_durations: Vec<u32>,
+    pub sprite_ids: Vec<u32>,
+}
+
+impl SpriteLayout {
+    pub fn frame_duration(&self, frame: usize) -> u32 {
+        frame_duration(&self.frame_durations, frame)
+    }
+
+    // the frame shown at a time in milliseconds, the animation loops
+    pub fn frame_at(&self, time: u64) -> usize {
+        frame_at(&self.frame_durations, self.frames as usize, time)
+    }
+
+    // outfits keep their template mask as a separate layer, every other texture is drawn as one image
+    pub fn compose(&self, sprites: &HashMap<u32, Sprite>, split_layers: bool) -> Textures {
+        // sprite sheet sprites can be bigger, an appearance only uses one size
+        let (sprite_width, sprite_height) = self
+            .sprite_ids
+            .iter()
+            .filter_map(|sprite_id| sprites.get(sprite_id))
+            .find(|sprite| !sprite.is_missing())
+            .map_or((Sprite::SIZE, Sprite::SIZE), |sprite| {
+                (sprite.width, sprite.height)
+            });
+        let width = self.width as u16 * sprite_width;
+        let height = self.height as u16 * sprite_height;
+
+        let mut sprite_ids = self.sprite_ids.iter();
+        let mut frames = Vec::new();
+        for _ in 0..self.frames {
+            let mut patterns_z = Vec::new();
+            for _ in 0..self.patterns_z {
+                let mut patterns_y = Vec::new();
+                for _ in 0..self.patterns_y {
+                    let mut patterns_x = Vec::new();
+                    for _ in 0..self.patterns_x {
+                        let mut layers = Vec::new();
+                        for _ in 0..self.layers {
+                            let mut rgba = vec![0; width as usize * height as usize * 4];
+                            for y in 0..self.height as u16 {
+                                for x in 0..self.width as u16 {
+                                    let sprite = sprite_ids
+                                        .next()
+                                        .and_then(|sprite_id| sprites.get(sprite_id))
+                                        .filter(|sprite| !sprite.is_missing());
+                                    if let Some(sprite) = sprite {
+                                        copy(
+                                            &mut rgba,
+                                            width,
+                                            (
+                                                width - (x + 1) * sprite_width,
+                                                height - (y + 1) * sprite_height,
+                                            ),
+                                            sprite,
+                                        );
+                                    }
+                                }
+                            }
+                            layers.push(Texture {
+                                width,
+                                height,
+                                rgba_bytes: rgba,
+                            });
+                        }
+
+                        patterns_x.push(TexturePatternX {
+                            layers: texture_layers(layers, split_layers),
+                        });
+                    }
+                    patterns_y.push(TexturePatternY { patterns_x });
+                }
+                patterns_z.push(TexturePatternZ { patterns_y });
+            }
+            frames.push(TextureFrame { patterns_z });
+        }
+        Textures { frames }
+    }
+
+    // every sprite arrived, including the ones the project turned out not to have
+    pub fn is_complete(&self, sprites: &HashMap<u32, Sprite>) -> bool {
+        self.sprite_ids
+            .iter()
+            .all(|sprite_id| sprites.contains_key(sprite_id))
+    }
+}
+
+// sprites are added while the project is open, as the editor asks for them
+pub type SharedSprites = Arc<RwLock<HashMap<u32, Sprite>>>;
+
+// composed from the sprites when they're used, clones share the composed textures
+// missing sprites are transparent, the textures are kept once every sprite arrived
+#[derive(Clone, Default)]
+pub struct LazyTextures {
+    sprite_layout: SpriteLayout,
+    split_layers: bool,
+    sprites: SharedSprites,
+    textures: Arc<OnceLock<Arc<Textures>>>,
+}
+
+impl LazyTextures {
+    pub fn new(sprite_layout: SpriteLayout, split_layers: bool, sprites: SharedSprites) -> Self {
+        Self {
+            sprite_layout,
+            split_layers,
+            sprites,
+            textures: Default::default(),
+        }
+    }
+
+    pub fn is_composed(&self) -> bool {
+        self.textures.get().is_some()
+    }
+
+    pub fn composed(&self) -> Arc<Textures> {
+        if let Some(textures) = self.textures.get() {
+            return textures.clone();
+        }
+        let sprites = self.sprites.read().unwrap_or_else(PoisonError::into_inner);
+        let textures = Arc::new(self.sprite_layout.compose(&sprites, self.split_layers));
+        if self.sprite_layout.is_complete(&sprites) {
+            return self.textures.get_or_init(|| textures).clone();
+        }
+        textures
+    }
+}
+
+// the sprites are left out, they're shared by every item
+impl fmt::Debug for LazyTextures {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LazyTextures")
+            .field("sprite_layout", &self.sprite_layout)
+            .field("textures", &self.textures.get())
+            .finish()
+    }
+}
+
+// server items sharing a client id share their textures
+pub fn share_item_textures<'a>(items: impl Iterator<Item = &'a mut Item>, sprites: &SharedSprites) {
+    let mut textures: HashMap<u16, LazyTextures> = HashMap::new();
+    for item in items {
+        item.textures = textures
+            .entry(item.client_id)
+            .or_insert_with(|| {
+                LazyTextures::new(item.sprite_layout.clone(), false, sprites.clone())
+            })
+            .clone();
+    }
+}
+
+pub fn frame_duration(frame_durations: &[u32], frame: usize) -> u32 {
+    frame_durations
+        .get(frame)
+        .copied()
+        .unwrap_or(FRAME_DURATION)
+        .max(1)
+}
+
+pub fn frame_at(frame_durations: &[u32], frames: usize, time: u64) -> usize {
+    let cycle: u64 = (0..frames)
+        .map(|frame| frame_duration(frame_durations, frame) as u64)
+        .sum();
+    if cycle == 0 {
+        return 0;
+    }
+    let mut time = time % cycle;
+    for frame in 0..frames {
+        let duration = frame_duration(frame_durations, frame) as u64;
+        if time < duration {
+            return frame;
+        }
+        time -= duration;
+    }
+    0
+}
+
+// sprites of a texture don't overlap, bigger sprites are cut at the texture's edge
+fn copy(rgba: &mut [u8], width: u16, (x, y): (u16, u16), sprite: &Sprite) {
+    let row_width = (sprite.width.min(width - x) as usize) * 4;
+    for (row, pixels) in sprite
+        .rgba_bytes
+        .chunks_exact(sprite.width as usize * 4)
+        .enumerate()
+    {
+        let start = ((y as usize + row) * width as usize + x as usize) * 4;
+        let Some(target) = rgba.get_mut(start..start + row_width) else {
+            break;
+        };
+        target.copy_from_slice(&pixels[..row_width]);
+    }
+}
+
+fn texture_layers(layers: Vec<Texture>, split: bool) -> Vec<TextureLayer> {
+    if split {
+        return layers
+            .into_iter()
+            .map(|texture| TextureLayer { texture })
+            .collect();
+    }
+
+    let mut layers = layers.into_iter();
+    let Some(mut texture) = layers.next() else {
+        return Vec::new();
+    };
+    for layer in layers {
+        draw(
+            &mut texture.rgba_bytes,
+            texture.width,
+            texture.height,
+            &layer,
+            None,
+        );
+    }
+    vec![TextureLayer { texture }]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TexturesGetBuilder;
+
+    fn layout(sprite_ids: Vec<u32>) -> SpriteLayout {
+        SpriteLayout {
+            width: 1,
+            height: 1,
+            layers: 1,
+            patterns_x: 1,
+            patterns_y: 1,
+            patterns_z: 1,
+            frames: sprite_ids.len() as u8,
+            frame_durations: Vec::new(),
+            sprite_ids,
+        }
+    }
+
+    fn red(id: u32) -> Sprite {
+        Sprite {
+            id,
+            width: Sprite::SIZE,
+            height: Sprite::SIZE,
+            rgba_bytes: [0xFF, 0, 0, 0xFF].repeat(Sprite::SIZE as usize * Sprite::SIZE as usize),
+        }
+    }
+
+    #[test]
+    fn textures_are_kept_once_every_sprite_arrived() {
+        let sprites = SharedSprites::default();
+        sprites.write().unwrap().insert(1, red(1));
+        let textures = LazyTextures::new(layout(vec![1, 2]), false, sprites.clone());
+
+        let composed = textures.composed();
+        assert!(!textures.is_composed());
+        assert_eq!(composed.frames_count(), 2);
+        assert_eq!(
+            composed.get(TexturesGetBuilder::new().frame(1)).rgba_bytes[3],
+            0
+        );
+
+        sprites.write().unwrap().insert(2, red(2));
+        let composed = textures.composed();
+        assert!(textures.is_composed());
+        assert!(textures.clone().is_composed());
+        assert_eq!(
+            composed.get(TexturesGetBuilder::new().frame(1)).rgba_bytes[3],
+            0xFF
+        );
+    }
+
+    #[test]
+    fn missing_sprites_are_transparent_and_complete() {
+        let sprites = SharedSprites::default();
+        sprites.write().unwrap().insert(1, Sprite::missing(1));
+        sprites.write().unwrap().insert(2, red(2));
+        let textures = LazyTextures::new(layout(vec![1, 2]), false, sprites);
+
+        let composed = textures.composed();
+        assert!(textures.is_composed());
+        let first = composed.get_default();
+        assert_eq!((first.width, first.height), (Sprite::SIZE, Sprite::SIZE));
+        assert!(first.rgba_bytes.iter().all(|byte| *byte == 0));
     }
 
-    pub fn pixels(&self) -> &[u8] {
-        &self.pixels
+    #[test]
+    fn frames_loop_with_their_durations() {
+        let durations = [100, 300];
+        assert_eq!(frame_at(&durations, 2, 0), 0);
+        assert_eq!(frame_at(&durations, 2, 99), 0);
+        assert_eq!(frame_at(&durations, 2, 100), 1);
+        assert_eq!(frame_at(&durations, 2, 399), 1);
+        assert_eq!(frame_at(&durations, 2, 400), 0);
+        assert_eq!(frame_duration(&[], 3), FRAME_DURATION);
+        assert_eq!(frame_at(&[], 0, 1234), 0);
     }
 }
@@ -0,0 +1,26 @@
+use rkyv::{Archive, Deserialize, Serialize};
+
+// variants are in the order the client draws them on a tile
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Archive,
+    Deserialize,
+    Serialize,
+)]
+#[archive(check_bytes)]
+pub enum StackOrder {
+    Ground,
+    Border,
+    Bottom,
+    #[default]
+    Common,
+    Top,
+}
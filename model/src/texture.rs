@@ -1,88 +1,47 @@
+use js_sys::Uint8Array;
 use rkyv::{Archive, Deserialize, Serialize};
 use wasm_bindgen::{prelude::wasm_bindgen, JsValue};
 
 #[wasm_bindgen]
 #[derive(Debug, Clone, Archive, Deserialize, Serialize)]
+#[archive(check_bytes)]
 pub struct Texture {
     pub width: u16,
     pub height: u16,
-    #[wasm_bindgen(getter_with_clone)]
-    pub image: String,
     #[wasm_bindgen(skip)]
     pub rgba_bytes: Vec<u8>,
 }
 
 impl Texture {
-    fn pixels(&self) -> Vec<Pixel> {
-        self.rgba_bytes
-            .chunks(4)
-            .map(|pixel| TryInto::<[u8; 4]>::try_into(pixel).unwrap().into())
-            .collect::<Vec<Pixel>>()
-    }
-
+    // edge pixels are repeated around the texture so that sampling near its border doesn't bleed
     pub fn rgba_bytes(&self, padding: usize) -> Vec<u8> {
-        let mut pixels_grid: Vec<_> = self
-            .pixels()
-            .chunks(self.width as usize)
-            .map(|row| row.to_vec())
-            .collect();
-
-        let width = self.width + (padding * 2) as u16;
-        let height = self.height + (padding * 2) as u16;
-
-        let first_row = pixels_grid.first().unwrap().clone();
-        let last_row = pixels_grid.last().unwrap().clone();
-
-        pixels_grid.splice(0..0, vec![first_row; padding]);
-        pixels_grid.append(&mut vec![last_row; padding]);
-
-        for row in 0..pixels_grid.len() {
-            let first_pixel_in_row = pixels_grid[row].first().unwrap().clone();
-            let last_pixel_in_row = pixels_grid[row].last().unwrap().clone();
-
-            pixels_grid[row].splice(0..0, vec![first_pixel_in_row; padding]);
-            pixels_grid[row].append(&mut vec![last_pixel_in_row; padding]);
+        let (width, height) = (self.width as usize, self.height as usize);
+        if width == 0 || height == 0 {
+            return Vec::new();
         }
 
-        let rgba_bytes = pixels_grid
-            .into_iter()
-            .flatten()
-            .map(|pixel| Into::<[u8; 4]>::into(pixel).to_vec())
-            .flatten()
-            .collect();
-
-        rgba_bytes
-    }
-}
-
-#[derive(Debug, Clone, Archive, Deserialize, Serialize)]
-pub struct Pixel {
-    pub red: u8,
-    pub green: u8,
-    pub blue: u8,
-    pub alpha: u8,
-}
-
-impl From<Pixel> for [u8; 4] {
-    fn from(
-        Pixel {
-            red,
-            green,
-            blue,
-            alpha,
-        }: Pixel,
-    ) -> Self {
-        [red, green, blue, alpha]
+        let mut rgba = Vec::with_capacity((width + padding * 2) * (height + padding * 2) * 4);
+        for y in 0..height + padding * 2 {
+            let start = y.saturating_sub(padding).min(height - 1) * width * 4;
+            let row = &self.rgba_bytes[start..start + width * 4];
+            for _ in 0..padding {
+                rgba.extend_from_slice(&row[..4]);
+            }
+            rgba.extend_from_slice(row);
+            for _ in 0..padding {
+                rgba.extend_from_slice(&row[row.len() - 4..]);
+            }
+        }
+        rgba
     }
 }
 
-impl Into<Pixel> for [u8; 4] {
-    fn into(self) -> Pixel {
-        Pixel {
-            red: self[0],
-            green: self[1],
-            blue: self[2],
-            alpha: self[3],
-        }
+#[wasm_bindgen]
+impl Texture {
+    // previews are made on demand, e.g. new ImageData(new Uint8ClampedArray(texture.rgba.buffer), texture.width)
+    // the pixels are copied once, straight into a JS array
+    #[wasm_bindgen(getter)]
+    pub fn rgba(&self) -> Uint8Array {
+        Uint8Array::from(self.rgba_bytes.as_slice())
     }
 }
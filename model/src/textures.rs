@@ -1,11 +1,19 @@
 use super::Texture;
 use rkyv::{Archive, Deserialize, Serialize};
 
-#[derive(Debug, Clone, Archive, Deserialize, Serialize)]
+#[derive(Debug, Clone, Default, Archive, Deserialize, Serialize)]
+#[archive(check_bytes)]
 pub struct Textures {
     pub frames: Vec<TextureFrame>,
 }
 
+// what textures without the asked for frame, pattern or layer give
+static EMPTY: Texture = Texture {
+    width: 0,
+    height: 0,
+    rgba_bytes: Vec::new(),
+};
+
 impl Textures {
     pub fn get_default(&self) -> &Texture {
         self.get(TexturesGetBuilder::default())
@@ -21,12 +29,59 @@ impl Textures {
             layer,
         }: TexturesGetBuilder,
     ) -> &Texture {
-        let f = &self.frames[frame];
-        let z = &f.patterns_z[pattern_z % f.patterns_z.len()];
-        let y = &z.patterns_y[pattern_y % z.patterns_y.len()];
-        let x = &y.patterns_x[pattern_x % y.patterns_x.len()];
-        let l = &x.layers[layer];
-        &l.texture
+        // frames and patterns wrap around, an empty texture is given when there are none
+        let texture = || {
+            let f = wrapped(&self.frames, frame)?;
+            let z = wrapped(&f.patterns_z, pattern_z)?;
+            let y = wrapped(&z.patterns_y, pattern_y)?;
+            let x = wrapped(&y.patterns_x, pattern_x)?;
+            let l = x.layers.get(layer)?;
+            Some(&l.texture)
+        };
+        texture().unwrap_or(&EMPTY)
+    }
+
+    pub fn frames_count(&self) -> usize {
+        self.frames.len()
+    }
+
+    // (patterns_x, patterns_y, patterns_z), every frame has the same patterns, zeros without sprites
+    pub fn patterns_count(&self) -> (usize, usize, usize) {
+        let z = self
+            .frames
+            .first()
+            .map_or(&[][..], |frame| &frame.patterns_z);
+        let y = z.first().map_or(&[][..], |z| &z.patterns_y);
+        let x = y.first().map_or(&[][..], |y| &y.patterns_x);
+        (x.len(), y.len(), z.len())
+    }
+
+    pub fn layers_count(&self) -> usize {
+        self.frames
+            .first()
+            .and_then(|frame| frame.patterns_z.first())
+            .and_then(|z| z.patterns_y.first())
+            .and_then(|y| y.patterns_x.first())
+            .map_or(0, |x| x.layers.len())
+    }
+
+    // every pattern of the first frame
+    pub fn patterns(&self) -> Vec<TexturesGetBuilder> {
+        let (patterns_x, patterns_y, patterns_z) = self.patterns_count();
+        let mut patterns = Vec::new();
+        for pattern_z in 0..patterns_z {
+            for pattern_y in 0..patterns_y {
+                for pattern_x in 0..patterns_x {
+                    patterns.push(
+                        TexturesGetBuilder::new()
+                            .pattern_x(pattern_x)
+                            .pattern_y(pattern_y)
+                            .pattern_z(pattern_z),
+                    );
+                }
+            }
+        }
+        patterns
     }
 
     pub fn get_all(&self) -> Vec<Texture> {
@@ -46,7 +101,11 @@ impl Textures {
     }
 }
 
-#[derive(Default)]
+fn wrapped<T>(values: &[T], index: usize) -> Option<&T> {
+    values.get(index % values.len().max(1))
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct TexturesGetBuilder {
     pub frame: usize,
     pub pattern_x: usize,
@@ -87,26 +146,78 @@ impl TexturesGetBuilder {
 }
 
 #[derive(Debug, Clone, Archive, Deserialize, Serialize)]
+#[archive(check_bytes)]
 pub struct TextureFrame {
     pub patterns_z: Vec<TexturePatternZ>,
 }
 
 #[derive(Debug, Clone, Archive, Deserialize, Serialize)]
+#[archive(check_bytes)]
 pub struct TexturePatternZ {
     pub patterns_y: Vec<TexturePatternY>,
 }
 
 #[derive(Debug, Clone, Archive, Deserialize, Serialize)]
+#[archive(check_bytes)]
 pub struct TexturePatternY {
     pub patterns_x: Vec<TexturePatternX>,
 }
 
 #[derive(Debug, Clone, Archive, Deserialize, Serialize)]
+#[archive(check_bytes)]
 pub struct TexturePatternX {
     pub layers: Vec<TextureLayer>,
 }
 
 #[derive(Debug, Clone, Archive, Deserialize, Serialize)]
+#[archive(check_bytes)]
 pub struct TextureLayer {
     pub texture: Texture,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn textures(frames: usize, patterns_x: usize) -> Textures {
+        let texture = |width| TextureLayer {
+            texture: Texture {
+                width,
+                height: 1,
+                rgba_bytes: vec![0; width as usize * 4],
+            },
+        };
+        Textures {
+            frames: (0..frames)
+                .map(|frame| TextureFrame {
+                    patterns_z: vec![TexturePatternZ {
+                        patterns_y: vec![TexturePatternY {
+                            patterns_x: (0..patterns_x)
+                                .map(|x| TexturePatternX {
+                                    layers: vec![texture((frame * patterns_x + x + 1) as u16)],
+                                })
+                                .collect(),
+                        }],
+                    }],
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn frames_and_patterns_wrap() {
+        let textures = textures(2, 3);
+        let get = |builder| textures.get(builder).width;
+        assert_eq!(get(TexturesGetBuilder::new().frame(1).pattern_x(2)), 6);
+        assert_eq!(get(TexturesGetBuilder::new().frame(3).pattern_x(4)), 5);
+        assert_eq!(get(TexturesGetBuilder::new().pattern_y(7).pattern_z(9)), 1);
+    }
+
+    #[test]
+    fn missing_textures_are_empty() {
+        assert_eq!(Textures::default().get_default().width, 0);
+        assert_eq!(textures(1, 0).get_default().width, 0);
+        let layer = TexturesGetBuilder::new().layer(1);
+        assert!(textures(1, 1).get(layer).rgba_bytes.is_empty());
+    }
+}
@@ -3,6 +3,7 @@ use rkyv::{Archive, Deserialize, Serialize};
 use wasm_bindgen::prelude::wasm_bindgen;
 
 #[derive(Debug, Default, Clone, Archive, Deserialize, Serialize)]
+#[archive(check_bytes)]
 pub struct Tile {
     pub attributes: AttributesType,
     pub entities: Vec<Entity>,
@@ -1,14 +1,16 @@
-use super::{Area, Entity, Position, Tile};
+use super::{Area, Entity, Position, Spawn, Tile};
 use rkyv::{Archive, Deserialize, Serialize};
 use std::collections::HashMap;
 use wasm_bindgen::prelude::wasm_bindgen;
 
 #[derive(Clone, Default, Archive, Deserialize, Serialize)]
+#[archive(check_bytes)]
 pub struct World {
     width: u32,
     height: u32,
     areas: Vec<Area>,
     tiles: HashMap<Position, Tile>,
+    spawns: Vec<Spawn>,
 }
 
 impl World {
@@ -18,6 +20,7 @@ impl World {
             height,
             areas: vec![Area::root()],
             tiles: HashMap::new(),
+            spawns: Vec::new(),
         }
     }
 
@@ -69,6 +72,14 @@ impl World {
         &self.tiles
     }
 
+    pub fn add_spawn(&mut self, spawn: Spawn) {
+        self.spawns.push(spawn);
+    }
+
+    pub fn spawns(&self) -> &[Spawn] {
+        &self.spawns
+    }
+
     pub fn into_tiles(self) -> HashMap<Position, Tile> {
         self.tiles
     }
@@ -0,0 +1,30 @@
+use super::CanaryProject;
+use crate::{
+    detect::{find_file, Detect, SEARCH_DEPTH},
+    parse::catalog,
+};
+use std::{ffi::OsStr, path::Path};
+
+impl Detect for CanaryProject {
+    fn detect(directory: &Path) -> Option<Self> {
+        let assets_path = [directory.to_path_buf(), directory.join("assets")]
+            .into_iter()
+            .find(|path| path.join(catalog::FILE_NAME).is_file())?;
+        let otbm_path = find_file(
+            directory,
+            &|path| path.extension() == Some(OsStr::new("otbm")),
+            SEARCH_DEPTH,
+        )?;
+        let otb_path = find_file(
+            directory,
+            &|path| path.file_name() == Some(OsStr::new("items.otb")),
+            SEARCH_DEPTH,
+        );
+
+        Some(CanaryProject {
+            assets_path,
+            otb_path,
+            otbm_path,
+        })
+    }
+}
@@ -0,0 +1,233 @@
+use super::CanaryProject;
+use crate::{
+    load::{self, Error, Load},
+    parse::{self, appearances, catalog, creature_lua, otb, otbm, spawns_xml, Parse},
+    snapshot::{self, Snapshot},
+    transport::Transport,
+};
+use async_trait::async_trait;
+use model::{minimap, Item, Look, Outfit, Position, SpriteLayout, Tile};
+use rayon::prelude::*;
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    path::PathBuf,
+    sync::Arc,
+};
+
+#[async_trait]
+impl Load for CanaryProject {
+    async fn load(
+        &self,
+        transport: Arc<impl Transport + Send + Sync + 'static>,
+    ) -> Result<(), Error> {
+        snapshot::load(
+            &self.otbm_path.with_extension(snapshot::EXTENSION),
+            &self.sources(),
+            |sources| self.snapshot(sources),
+            transport.as_ref(),
+        )
+        .await
+    }
+}
+
+impl CanaryProject {
+    // every file the snapshot is made from, in a stable order, the spawn file is one of the
+    // xml files next to the map
+    pub fn sources(&self) -> Vec<PathBuf> {
+        let catalog_path = self.assets_path.join(catalog::FILE_NAME);
+        let catalog: Option<catalog::Document> = File::open(&catalog_path)
+            .ok()
+            .and_then(|file| file.parse().ok());
+
+        let mut sources = vec![catalog_path];
+        if let Some(catalog) = catalog.as_ref() {
+            sources.extend(catalog.entries.iter().filter_map(|entry| match entry {
+                catalog::Entry::Appearances { file } | catalog::Entry::Sprite { file, .. } => {
+                    Some(self.assets_path.join(file))
+                }
+                _ => None,
+            }));
+        }
+        sources.extend(self.otb_path.clone());
+        sources.push(self.otbm_path.clone());
+        if let Some(map_path) = self.otbm_path.parent() {
+            let mut map_files: Vec<_> = fs::read_dir(map_path)
+                .into_iter()
+                .flatten()
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|path| path.extension().is_some_and(|extension| extension == "xml"))
+                .collect();
+            map_files.sort();
+            sources.extend(map_files);
+        }
+        let mut creature_paths = self.creature_paths();
+        creature_paths.sort();
+        sources.extend(creature_paths);
+        sources.retain(|path| path.is_file());
+        sources
+    }
+
+    pub fn snapshot(&self, sources: &[PathBuf]) -> Result<Snapshot, Error> {
+        println!("Loading catalog");
+        let catalog: catalog::Document =
+            File::open(self.assets_path.join(catalog::FILE_NAME))?.parse()?;
+        println!("Loading appearances");
+        let appearances_file = catalog.appearances_file().ok_or(parse::Error::Malformed)?;
+        let appearances: appearances::Document =
+            File::open(self.assets_path.join(appearances_file))?.parse()?;
+        let otb: Option<otb::Document> = match self.otb_path.as_ref() {
+            Some(otb_path) => {
+                println!("Loading OTB");
+                Some(File::open(otb_path)?.parse()?)
+            }
+            None => None,
+        };
+        println!("Loading OTBM");
+        let otbm: otbm::Document = otbm::Reader {
+            path: &self.otbm_path,
+            items: &appearances,
+            otb: otb.as_ref(),
+        }
+        .parse()?;
+        let spawns = self.load_spawns_xml(otbm.map.spawn_file.as_ref())?;
+
+        // without items.otb server ids are the same as client ids
+        let server_ids = match otb.as_ref() {
+            Some(otb) => otb.server_ids_by_client_id(),
+            None => appearances
+                .items
+                .keys()
+                .map(|client_id| (*client_id, vec![*client_id]))
+                .collect(),
+        };
+
+        let mut snapshot = Snapshot::new(sources)?;
+
+        snapshot.outfits = appearances
+            .outfits
+            .values()
+            .map(|outfit| Outfit {
+                id: outfit.id,
+                draw_offset: outfit.draw_offset.clone(),
+                sprite_layout: Self::sprite_layout(&outfit.textures),
+                textures: Default::default(),
+            })
+            .collect();
+
+        if let Some(spawns) = spawns {
+            let looks = self.load_creature_looks();
+            snapshot.spawns = spawns
+                .spawns
+                .iter()
+                .map(|spawn| spawn.to_model(&looks))
+                .collect();
+        }
+
+        load::report_unmapped_items(appearances.items.keys().copied(), &server_ids);
+        snapshot.items = appearances
+            .items
+            .values()
+            .collect::<Vec<_>>()
+            .into_par_iter()
+            .flat_map_iter(|item| {
+                // the client composes the textures once for every server item sharing them
+                let client_item = Item {
+                    server_id: item.id,
+                    client_id: item.id,
+                    name: item.name.clone(),
+                    minimap_color: item.minimap_color.and_then(minimap::minimap_color),
+                    light: item.light,
+                    ground: item.ground,
+                    stackable: item.stackable,
+                    splash: item.splash,
+                    fluid_container: item.fluid_container,
+                    stack_order: item.stack_order,
+                    draw_offset: item.draw_offset.clone(),
+                    height_offset: item.height_offset.clone(),
+                    sprite_layout: Self::sprite_layout(&item.textures),
+                    textures: Default::default(),
+                };
+                let server_ids = server_ids.get(&item.id).map_or(&[][..], Vec::as_slice);
+                load::server_items(&client_item, server_ids, otb.as_ref())
+            })
+            .collect();
+
+        snapshot.tiles = otbm
+            .map
+            .tiles
+            .into_par_iter()
+            .map(<(Position, Tile)>::from)
+            .collect();
+
+        Ok(snapshot)
+    }
+
+    // the spawn file is named by the map and lives next to it
+    fn load_spawns_xml(
+        &self,
+        spawn_file: Option<&String>,
+    ) -> Result<Option<spawns_xml::Document>, parse::Error> {
+        let spawns_path = spawn_file
+            .zip(self.otbm_path.parent())
+            .map(|(spawn_file, map_dir)| map_dir.join(spawn_file))
+            .filter(|spawns_path| spawns_path.is_file());
+        match spawns_path {
+            Some(spawns_path) => {
+                println!("Loading spawns");
+                Ok(Some(File::open(spawns_path)?.parse()?))
+            }
+            None => Ok(None),
+        }
+    }
+
+    // monster and npc scripts live in the data folder above the map
+    fn creature_paths(&self) -> Vec<PathBuf> {
+        let Some(data_path) = self
+            .otbm_path
+            .ancestors()
+            .find(|path| path.join("monster").is_dir() || path.join("npc").is_dir())
+        else {
+            return Vec::new();
+        };
+
+        load::files_with_extension(&data_path.join("monster"), "lua")
+            .into_iter()
+            .chain(load::files_with_extension(&data_path.join("npc"), "lua"))
+            .collect()
+    }
+
+    // creatures that can't be read are left without a look
+    fn load_creature_looks(&self) -> HashMap<String, Look> {
+        let mut looks = HashMap::new();
+        println!("Loading creatures");
+
+        for creature_path in self.creature_paths() {
+            let creature: Option<creature_lua::Document> = File::open(creature_path)
+                .ok()
+                .and_then(|file| file.parse().ok());
+            if let Some((name, Some(look))) =
+                creature.map(|creature| (creature.name, creature.look))
+            {
+                looks.insert(name.to_lowercase(), look);
+            }
+        }
+
+        looks
+    }
+
+    // every sprite of an appearance is a whole texture
+    fn sprite_layout(textures: &appearances::Textures) -> SpriteLayout {
+        SpriteLayout {
+            width: 1,
+            height: 1,
+            layers: textures.layers,
+            patterns_x: textures.patterns_x,
+            patterns_y: textures.patterns_y,
+            patterns_z: textures.patterns_z,
+            frames: textures.frames,
+            frame_durations: textures.frame_durations.clone(),
+            sprite_ids: textures.sprites.clone(),
+        }
+    }
+}
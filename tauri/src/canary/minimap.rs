@@ -0,0 +1,51 @@
+use super::CanaryProject;
+use crate::{
+    load::Error,
+    minimap::Minimap,
+    parse::{self, appearances, catalog, otb, otbm, Parse},
+};
+use model::{
+    minimap::{minimap_color, minimap_floors, MinimapFloor},
+    Position, Tile,
+};
+use std::{collections::BTreeMap, fs::File};
+
+impl Minimap for CanaryProject {
+    fn minimap(&self) -> Result<BTreeMap<u8, MinimapFloor>, Error> {
+        let catalog: catalog::Document =
+            File::open(self.assets_path.join(catalog::FILE_NAME))?.parse()?;
+        let appearances_file = catalog.appearances_file().ok_or(parse::Error::Malformed)?;
+        let appearances: appearances::Document =
+            File::open(self.assets_path.join(appearances_file))?.parse()?;
+        let otb: Option<otb::Document> = match self.otb_path.as_ref() {
+            Some(otb_path) => Some(File::open(otb_path)?.parse()?),
+            None => None,
+        };
+        let otbm: otbm::Document = otbm::Reader {
+            path: &self.otbm_path,
+            items: &appearances,
+            otb: otb.as_ref(),
+        }
+        .parse()?;
+
+        let tiles: Vec<(Position, Tile)> = otbm.map.tiles.into_iter().map(Into::into).collect();
+
+        // without items.otb server ids are the same as client ids
+        Ok(minimap_floors(
+            tiles.iter().map(|(position, tile)| (position, tile)),
+            |server_id| {
+                let otb_item = otb.as_ref().and_then(|otb| otb.item(server_id));
+                let client_id = otb_item.map_or(server_id, |otb_item| otb_item.client_id);
+                otb_item
+                    .and_then(|otb_item| otb_item.minimap_color)
+                    .or_else(|| {
+                        appearances
+                            .items
+                            .get(&client_id)
+                            .and_then(|item| item.minimap_color)
+                    })
+                    .and_then(minimap_color)
+            },
+        ))
+    }
+}
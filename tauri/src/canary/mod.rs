@@ -0,0 +1,26 @@
+use crate::project::Project;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CanaryProject {
+    pub assets_path: PathBuf,
+    pub otb_path: Option<PathBuf>,
+    pub otbm_path: PathBuf,
+}
+
+impl From<CanaryProject> for Project {
+    fn from(value: CanaryProject) -> Self {
+        Project::CanaryProject(value)
+    }
+}
+
+mod detector;
+mod loader;
+mod minimap;
+mod sprites;
+
+pub use detector::*;
+pub use loader::*;
+pub use sprites::*;
@@ -0,0 +1,96 @@
+use super::CanaryProject;
+use crate::{
+    load::Error,
+    parse::{catalog, sprite_sheet, Parse},
+    sprites::{SpriteSource, Sprites},
+};
+use lru::LruCache;
+use model::Sprite;
+use std::{
+    fs::File,
+    num::NonZeroUsize,
+    path::PathBuf,
+    sync::{Arc, Mutex, OnceLock, PoisonError},
+};
+
+// 36 MiB of decompressed sheets
+const CACHED_SHEETS: usize = 64;
+
+// none when the sheet can't be read
+type Sheet = Arc<OnceLock<Option<sprite_sheet::Document>>>;
+
+// sheets are decompressed the first time one of their sprites is read, the most recently read
+// ones are kept
+pub struct SpriteSheets {
+    assets_path: PathBuf,
+    // file, sprite type, first and last sprite id, sorted by the first sprite id
+    sheets: Vec<(String, sprite_sheet::SpriteType, u32, u32)>,
+    cache: Mutex<LruCache<usize, Sheet>>,
+}
+
+impl Sprites for CanaryProject {
+    fn sprite_source(&self) -> Result<Arc<dyn SpriteSource>, Error> {
+        let catalog: catalog::Document =
+            File::open(self.assets_path.join(catalog::FILE_NAME))?.parse()?;
+        let mut sheets: Vec<_> = catalog
+            .sprite_sheets()
+            .filter_map(|entry| match entry {
+                catalog::Entry::Sprite {
+                    file,
+                    sprite_type,
+                    first_sprite_id,
+                    last_sprite_id,
+                } => Some((
+                    file.clone(),
+                    sprite_sheet::SpriteType::try_from(*sprite_type).ok()?,
+                    *first_sprite_id,
+                    *last_sprite_id,
+                )),
+                _ => None,
+            })
+            .collect();
+        sheets.sort_by_key(|(_, _, first_sprite_id, _)| *first_sprite_id);
+
+        Ok(Arc::new(SpriteSheets {
+            assets_path: self.assets_path.clone(),
+            sheets,
+            cache: Mutex::new(LruCache::new(NonZeroUsize::new(CACHED_SHEETS).unwrap())),
+        }))
+    }
+}
+
+impl SpriteSheets {
+    // sprites of the same sheet wait for it to be decompressed once
+    fn sheet(&self, index: usize) -> Sheet {
+        self.cache
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get_or_insert(index, Default::default)
+            .clone()
+    }
+}
+
+impl SpriteSource for SpriteSheets {
+    fn sprite(&self, id: u32) -> Option<Sprite> {
+        let index = self
+            .sheets
+            .partition_point(|(_, _, first_sprite_id, _)| *first_sprite_id <= id)
+            .checked_sub(1)?;
+        let (file, sprite_type, first_sprite_id, last_sprite_id) = &self.sheets[index];
+        if id > *last_sprite_id {
+            return None;
+        }
+
+        let sheet = self.sheet(index);
+        let sheet = sheet
+            .get_or_init(|| File::open(self.assets_path.join(file)).ok()?.parse().ok())
+            .as_ref()?;
+        let sprite = sheet.sprite(*sprite_type, id - first_sprite_id)?;
+        Some(Sprite {
+            id,
+            width: sprite.width() as u16,
+            height: sprite.height() as u16,
+            rgba_bytes: sprite.into_raw(),
+        })
+    }
+}
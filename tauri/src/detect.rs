@@ -1,6 +1,39 @@
 use crate::load::Load;
-use std::path::Path;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+// how many directories below the picked one project files are looked for
+pub const SEARCH_DEPTH: usize = 3;
 
 pub trait Detect: Load + Sized {
     fn detect(directory: &Path) -> Option<Self>;
 }
+
+// files of a directory come before its subdirectories, both in name order
+pub fn find_file(
+    directory: &Path,
+    predicate: &impl Fn(&Path) -> bool,
+    depth: usize,
+) -> Option<PathBuf> {
+    let mut entries: Vec<_> = fs::read_dir(directory)
+        .ok()?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .collect();
+    entries.sort();
+
+    entries
+        .iter()
+        .find(|path| path.is_file() && predicate(path))
+        .cloned()
+        .or_else(|| {
+            if depth == 0 {
+                return None;
+            }
+            entries
+                .iter()
+                .filter(|path| path.is_dir())
+                .find_map(|path| find_file(path, predicate, depth - 1))
+        })
+}
@@ -0,0 +1,187 @@
+use crate::load::Error;
+use image::{
+    codecs::gif::{GifEncoder, Repeat},
+    imageops::overlay,
+    Delay, Frame, RgbaImage,
+};
+use model::{frame_duration, Texture, Textures, TexturesGetBuilder};
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt::Display,
+    fs::File,
+    io::BufWriter,
+    path::{Path, PathBuf},
+};
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub enum Format {
+    Gif,
+    Apng,
+    SpriteSheet,
+}
+
+// where every pattern ended up in the sprite sheet, written next to it
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpriteSheetCell {
+    pub frame: usize,
+    pub pattern_x: usize,
+    pub pattern_y: usize,
+    pub pattern_z: usize,
+    pub layer: usize,
+    pub x: u32,
+    pub y: u32,
+    pub width: u16,
+    pub height: u16,
+}
+
+// file names start with the name, sprite sheets also get a json file with their labels
+// frame durations are in milliseconds, missing ones fall back to the default duration
+pub fn export(
+    name: &str,
+    textures: &Textures,
+    frame_durations: &[u32],
+    format: Format,
+    directory: &Path,
+) -> Result<Vec<PathBuf>, Error> {
+    if textures.layers_count() == 0 {
+        return Err(Error::Export(format!("{name} has no sprites")));
+    }
+
+    match format {
+        Format::Gif => {
+            let path = directory.join(format!("{name}.gif"));
+            gif(textures, frame_durations, &path)?;
+            Ok(vec![path])
+        }
+        Format::Apng => {
+            let path = directory.join(format!("{name}.png"));
+            apng(textures, frame_durations, &path)?;
+            Ok(vec![path])
+        }
+        Format::SpriteSheet => {
+            let path = directory.join(format!("{name}.png"));
+            let labels_path = directory.join(format!("{name}.json"));
+            let (image, cells) = sprite_sheet(textures);
+            image.save(&path).map_err(export_error)?;
+            let labels = File::create(&labels_path).map_err(export_error)?;
+            serde_json::to_writer_pretty(labels, &cells).map_err(export_error)?;
+            Ok(vec![path, labels_path])
+        }
+    }
+}
+
+fn export_error(error: impl Display) -> Error {
+    Error::Export(error.to_string())
+}
+
+// animations play the first pattern, like the item palette shows it
+fn animation_frames(textures: &Textures) -> impl Iterator<Item = &Texture> {
+    (0..textures.frames_count()).map(|frame| textures.get(TexturesGetBuilder::new().frame(frame)))
+}
+
+fn image(texture: &Texture) -> Result<RgbaImage, Error> {
+    RgbaImage::from_raw(
+        texture.width.into(),
+        texture.height.into(),
+        texture.rgba_bytes.clone(),
+    )
+    .ok_or_else(|| Error::Export("texture size doesn't match its pixels".to_string()))
+}
+
+fn gif(textures: &Textures, frame_durations: &[u32], path: &Path) -> Result<(), Error> {
+    let file = File::create(path).map_err(export_error)?;
+    let mut encoder = GifEncoder::new(BufWriter::new(file));
+    encoder.set_repeat(Repeat::Infinite).map_err(export_error)?;
+    let frames = animation_frames(textures)
+        .enumerate()
+        .map(|(frame, texture)| {
+            let delay = Delay::from_numer_denom_ms(frame_duration(frame_durations, frame), 1);
+            Ok(Frame::from_parts(image(texture)?, 0, 0, delay))
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+    encoder.encode_frames(frames).map_err(export_error)?;
+    Ok(())
+}
+
+// the image crate can't write animated pngs, the png crate it uses can
+fn apng(textures: &Textures, frame_durations: &[u32], path: &Path) -> Result<(), Error> {
+    let default = textures.get_default();
+    let file = File::create(path).map_err(export_error)?;
+    let mut encoder = png::Encoder::new(
+        BufWriter::new(file),
+        default.width.into(),
+        default.height.into(),
+    );
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder
+        .set_animated(textures.frames_count() as u32, 0)
+        .map_err(export_error)?;
+
+    let mut writer = encoder.write_header().map_err(export_error)?;
+    for (frame, texture) in animation_frames(textures).enumerate() {
+        // the delay is a fraction of a second stored in u16s
+        let duration = frame_duration(frame_durations, frame).min(u16::MAX.into());
+        writer
+            .set_frame_delay(duration as u16, 1000)
+            .and_then(|_| writer.write_image_data(&texture.rgba_bytes))
+            .map_err(export_error)?;
+    }
+    writer.finish().map_err(export_error)?;
+    Ok(())
+}
+
+// a row per frame and a column per pattern and layer, cells are as big as the biggest texture
+pub fn sprite_sheet(textures: &Textures) -> (RgbaImage, Vec<SpriteSheetCell>) {
+    let patterns = textures.patterns();
+    let layers = textures.layers_count();
+    let cells: Vec<_> = (0..textures.frames_count())
+        .flat_map(|frame| {
+            patterns.iter().flat_map(move |pattern| {
+                (0..layers).map(move |layer| (pattern.frame(frame).layer(layer), frame))
+            })
+        })
+        .collect();
+
+    let (cell_width, cell_height) = cells
+        .iter()
+        .map(|(pattern, _)| textures.get(*pattern))
+        .fold((0, 0), |(width, height), texture| {
+            (
+                width.max(texture.width as u32),
+                height.max(texture.height as u32),
+            )
+        });
+    let columns = (patterns.len() * layers) as u32;
+    let mut sheet = RgbaImage::new(
+        cell_width * columns,
+        cell_height * textures.frames_count() as u32,
+    );
+
+    let cells = cells
+        .into_iter()
+        .enumerate()
+        .map(|(index, (pattern, frame))| {
+            let texture = textures.get(pattern);
+            let x = index as u32 % columns * cell_width;
+            let y = frame as u32 * cell_height;
+            if let Ok(texture_image) = image(texture) {
+                overlay(&mut sheet, &texture_image, x.into(), y.into());
+            }
+            SpriteSheetCell {
+                frame,
+                pattern_x: pattern.pattern_x,
+                pattern_y: pattern.pattern_y,
+                pattern_z: pattern.pattern_z,
+                layer: pattern.layer,
+                x,
+                y,
+                width: texture.width,
+                height: texture.height,
+            }
+        })
+        .collect();
+
+    (sheet, cells)
+}
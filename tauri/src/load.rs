@@ -1,13 +1,28 @@
-use crate::{parse, transport::Transport};
+use crate::{
+    parse::{self, otb},
+    transport::{self, Transport},
+};
 use async_trait::async_trait;
+use model::{minimap, Item};
 use serde::Serialize;
-use std::{io, sync::Arc};
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 use tauri::State;
 
 #[derive(Serialize)]
 pub enum Error {
     Something, // TODO:
     Parse(parse::Error),
+    // the project kind can't do this yet
+    Unsupported,
+    // what stopped an export, the message of the error it came from
+    Export(String),
+    // the editor couldn't be sent what was loaded
+    Transport,
 }
 
 impl From<parse::Error> for Error {
@@ -16,6 +31,12 @@ impl From<parse::Error> for Error {
     }
 }
 
+impl From<transport::Error> for Error {
+    fn from(_: transport::Error) -> Self {
+        Error::Transport
+    }
+}
+
 // FIXME: why do we need that? it should be implicit...
 impl From<io::Error> for Error {
     fn from(value: io::Error) -> Self {
@@ -30,3 +51,62 @@ pub trait Load {
         transport: Arc<impl Transport + Send + Sync + 'static>,
     ) -> Result<(), Error>;
 }
+
+// every file with the extension in the directory and its subdirectories
+pub fn files_with_extension(directory: &Path, extension: &str) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(directory) else {
+        return Vec::new();
+    };
+
+    entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .flat_map(|path| {
+            if path.is_dir() {
+                files_with_extension(&path, extension)
+            } else if path.extension().is_some_and(|ext| ext == extension) {
+                vec![path]
+            } else {
+                Vec::new()
+            }
+        })
+        .collect()
+}
+
+// the client item once for every server id sharing its client id, items.otb overrides what the client says
+pub fn server_items(item: &Item, server_ids: &[u16], otb: Option<&otb::Document>) -> Vec<Item> {
+    server_ids
+        .iter()
+        .map(|server_id| {
+            let otb_item = otb.and_then(|otb| otb.item(*server_id));
+            Item {
+                server_id: *server_id,
+                minimap_color: match otb_item.and_then(|otb_item| otb_item.minimap_color) {
+                    Some(minimap_color) => minimap::minimap_color(minimap_color),
+                    None => item.minimap_color,
+                },
+                light: otb_item.and_then(|otb_item| otb_item.light).or(item.light),
+                stack_order: otb_item.map_or(item.stack_order, model::StackOrder::from),
+                ..item.clone()
+            }
+        })
+        .collect()
+}
+
+// client items without an items.otb entry can't be placed on a map, they're left out
+pub fn report_unmapped_items(
+    client_ids: impl Iterator<Item = u16>,
+    server_ids: &HashMap<u16, Vec<u16>>,
+) {
+    let mut unmapped: Vec<_> = client_ids
+        .filter(|client_id| !server_ids.contains_key(client_id))
+        .collect();
+    if unmapped.is_empty() {
+        return;
+    }
+    unmapped.sort();
+    println!(
+        "Leaving out {} client items without a server id: {:?}",
+        unmapped.len(),
+        unmapped
+    );
+}
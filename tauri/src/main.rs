@@ -2,14 +2,18 @@
 #![allow(dead_code)]
 #![allow(unused)]
 
-use crate::parse::{dat, otb, otbm, spr, Parse};
+use crate::parse::{dat, otb, otbm, spr, Encode, Parse};
 use crate::transport::websocket::WebSocket;
 use crate::{
+    canary::CanaryProject,
     detect::Detect,
+    export::Format,
     load::{Error, Load},
+    minimap::Minimap,
     project::Project,
     skyless::SkylessProject,
-    tfs::TfsProject,
+    sprites::Sprites,
+    tfs::{ExportCache, ServerItem, SpriteHashMismatch, TfsProject},
 };
 use futures::StreamExt;
 use model::World;
@@ -20,14 +24,19 @@ use std::{
     path::PathBuf,
     sync::Arc,
 };
-use tauri::{AppHandle, Manager};
+use tauri::{AppHandle, Manager, State};
 use tokio::net::TcpListener;
 
 pub mod detect;
+pub mod export;
 pub mod load;
+pub mod minimap;
 pub mod parse;
+pub mod snapshot;
+pub mod sprites;
 pub mod transport;
 
+pub mod canary;
 pub mod project;
 pub mod skyless;
 pub mod tfs;
@@ -39,23 +48,127 @@ fn get_websocket_url(app: AppHandle) -> SocketAddr {
 
 #[tauri::command]
 fn detect(directory: PathBuf) -> Option<Project> {
-    SkylessProject::detect(&directory)
+    CanaryProject::detect(&directory)
         .map(From::from)
+        .or_else(|| SkylessProject::detect(&directory).map(From::from))
         .or_else(|| TfsProject::detect(&directory).map(From::from))
 }
 
 #[tauri::command]
-async fn load(app: AppHandle, project: TfsProject) -> Result<(), Error> {
-    let transport = app.state::<Arc<WebSocket>>().inner();
-    project.load(transport.clone()).await?; // FIXME: it can panic, use try_state instead
+async fn load(app: AppHandle, project: Project) -> Result<(), Error> {
+    let transport = app.state::<Arc<WebSocket>>().inner(); // FIXME: it can panic, use try_state instead
+
+    // set first, the editor asks for sprites as soon as it has the map
+    transport.set_sprite_source(project.sprite_source()?);
+    project.load(transport.clone()).await?;
+    Ok(())
+}
+
+#[tauri::command]
+async fn verify_sprite_hashes(
+    cache: State<'_, ExportCache>,
+    project: TfsProject,
+) -> Result<Vec<SpriteHashMismatch>, Error> {
+    let documents = cache.documents(&project)?;
+    Ok(TfsProject::verify_sprite_hashes(
+        &documents.otb,
+        &documents.dat,
+        &documents.spr,
+    ))
+}
+
+// items.otb is written again with the hashes of the current sprites
+#[tauri::command]
+async fn regenerate_sprite_hashes(
+    cache: State<'_, ExportCache>,
+    project: TfsProject,
+) -> Result<(), Error> {
+    let documents = cache.documents(&project)?;
+    let mut otb = documents.otb.clone();
+    TfsProject::regenerate_sprite_hashes(&mut otb, &documents.dat, &documents.spr);
+    File::create(&project.otb_path)?.encode(&otb)?;
     Ok(())
 }
 
+#[tauri::command]
+async fn server_items(project: TfsProject) -> Result<Vec<ServerItem>, Error> {
+    let otb: otb::Document = File::open(&project.otb_path)?.parse()?;
+    let items_xml = project.load_items_xml()?;
+    let mut server_items: Vec<_> = TfsProject::server_items(&otb, items_xml.as_ref())
+        .into_values()
+        .collect();
+    server_items.sort_by_key(|server_item| server_item.server_id);
+    Ok(server_items)
+}
+
+#[tauri::command]
+async fn export_minimap(project: Project, directory: PathBuf) -> Result<Vec<PathBuf>, Error> {
+    let floors = project.minimap()?;
+    minimap::export(&floors, &directory)
+}
+
+#[tauri::command]
+async fn export_item(
+    cache: State<'_, ExportCache>,
+    project: TfsProject,
+    server_id: u16,
+    format: Format,
+    directory: PathBuf,
+) -> Result<Vec<PathBuf>, Error> {
+    let textures = project.item_textures(&cache, |otb_item| otb_item.server_id == server_id)?;
+    let (_, frame_durations, textures) = textures
+        .first()
+        .ok_or_else(|| Error::Export(format!("no item has the server id {server_id}")))?;
+    export::export(
+        &server_id.to_string(),
+        textures,
+        frame_durations,
+        format,
+        &directory,
+    )
+}
+
+// every item of an items.otb group, files are named after the server id
+#[tauri::command]
+async fn export_item_group(
+    cache: State<'_, ExportCache>,
+    project: TfsProject,
+    group: otb::ItemGroup,
+    format: Format,
+    directory: PathBuf,
+) -> Result<Vec<PathBuf>, Error> {
+    let textures = project.item_textures(&cache, |otb_item| otb_item.group == group)?;
+    let paths = textures
+        .iter()
+        .map(|(server_id, frame_durations, textures)| {
+            export::export(
+                &server_id.to_string(),
+                textures,
+                frame_durations,
+                format,
+                &directory,
+            )
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(paths.into_iter().flatten().collect())
+}
+
 fn main() {
     tauri::Builder::default()
         .plugin(tauri_plugin_dialog::init())
         .plugin(WebSocket::init())
-        .invoke_handler(tauri::generate_handler![get_websocket_url, detect, load,])
+        .manage(ExportCache::default())
+        .invoke_handler(tauri::generate_handler![
+            get_websocket_url,
+            detect,
+            load,
+            verify_sprite_hashes,
+            regenerate_sprite_hashes,
+            server_items,
+            export_minimap,
+            export_item,
+            export_item_group,
+        ])
         .run(tauri::generate_context!())
         .expect("Error while running Tauri application");
 }
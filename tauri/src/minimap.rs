@@ -0,0 +1,39 @@
+use crate::{load::Error, parse, project::Project};
+use image::RgbaImage;
+use model::minimap::MinimapFloor;
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+pub trait Minimap {
+    fn minimap(&self) -> Result<BTreeMap<u8, MinimapFloor>, Error>;
+}
+
+// TODO: use some macro to auto-impl this
+impl Minimap for Project {
+    fn minimap(&self) -> Result<BTreeMap<u8, MinimapFloor>, Error> {
+        match self {
+            Project::CanaryProject(project) => project.minimap(),
+            Project::SkylessProject(project) => project.minimap(),
+            Project::TfsProject(project) => project.minimap(),
+        }
+    }
+}
+
+// file names keep the floor and its top left tile, images don't have to start at 0,0
+pub fn export(
+    floors: &BTreeMap<u8, MinimapFloor>,
+    directory: &Path,
+) -> Result<Vec<PathBuf>, Error> {
+    floors
+        .values()
+        .map(|floor| {
+            let path = directory.join(format!("minimap_{}_{}_{}.png", floor.z, floor.x, floor.y));
+            let image = RgbaImage::from_raw(floor.width, floor.height, floor.rgba.clone())
+                .ok_or(Error::Something)?;
+            image.save(&path).map_err(|_| parse::Error::Io)?;
+            Ok(path)
+        })
+        .collect()
+}
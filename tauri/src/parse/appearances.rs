@@ -0,0 +1,296 @@
+use super::{Error, Parse};
+use model::{Light, Offset, StackOrder};
+use prost::Message;
+use std::{collections::HashMap, io::Read};
+
+pub struct Document {
+    pub items: HashMap<u16, Item>,
+    pub outfits: HashMap<u16, Outfit>,
+}
+
+pub struct Item {
+    pub id: u16,
+    pub name: Option<String>,
+    pub minimap_color: Option<u16>,
+    pub light: Option<Light>,
+    pub ground: bool,
+    pub stackable: bool,
+    pub splash: bool,
+    pub fluid_container: bool,
+    pub stack_order: StackOrder,
+    pub draw_offset: Offset,
+    pub height_offset: Offset,
+    pub textures: Textures,
+}
+
+pub struct Outfit {
+    pub id: u16,
+    pub draw_offset: Offset,
+    pub textures: Textures,
+}
+
+#[derive(Debug)]
+pub struct Textures {
+    pub layers: u8,
+    pub patterns_x: u8,
+    pub patterns_y: u8,
+    pub patterns_z: u8,
+    pub frames: u8,
+    // shortest duration of every phase in milliseconds
+    pub frame_durations: Vec<u32>,
+    pub sprites: Vec<u32>,
+}
+
+// only the subset of appearances.proto that the editor uses, prost skips the rest
+#[derive(Clone, PartialEq, Message)]
+struct Appearances {
+    #[prost(message, repeated, tag = "1")]
+    object: Vec<Appearance>,
+    #[prost(message, repeated, tag = "2")]
+    outfit: Vec<Appearance>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct Appearance {
+    #[prost(uint32, optional, tag = "1")]
+    id: Option<u32>,
+    #[prost(message, repeated, tag = "2")]
+    frame_group: Vec<FrameGroup>,
+    #[prost(message, optional, tag = "3")]
+    flags: Option<AppearanceFlags>,
+    #[prost(bytes = "vec", optional, tag = "4")]
+    name: Option<Vec<u8>>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct FrameGroup {
+    #[prost(message, optional, tag = "3")]
+    sprite_info: Option<SpriteInfo>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct SpriteInfo {
+    #[prost(uint32, optional, tag = "1")]
+    pattern_width: Option<u32>,
+    #[prost(uint32, optional, tag = "2")]
+    pattern_height: Option<u32>,
+    #[prost(uint32, optional, tag = "3")]
+    pattern_depth: Option<u32>,
+    #[prost(uint32, optional, tag = "4")]
+    layers: Option<u32>,
+    #[prost(uint32, repeated, packed = "false", tag = "5")]
+    sprite_id: Vec<u32>,
+    #[prost(message, optional, tag = "6")]
+    animation: Option<SpriteAnimation>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct SpriteAnimation {
+    #[prost(message, repeated, tag = "6")]
+    sprite_phase: Vec<SpritePhase>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct SpritePhase {
+    #[prost(uint32, optional, tag = "1")]
+    duration_min: Option<u32>,
+    #[prost(uint32, optional, tag = "2")]
+    duration_max: Option<u32>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct AppearanceFlags {
+    #[prost(message, optional, tag = "1")]
+    bank: Option<AppearanceFlagBank>,
+    #[prost(bool, optional, tag = "2")]
+    clip: Option<bool>,
+    #[prost(bool, optional, tag = "3")]
+    bottom: Option<bool>,
+    #[prost(bool, optional, tag = "4")]
+    top: Option<bool>,
+    #[prost(bool, optional, tag = "6")]
+    cumulative: Option<bool>,
+    #[prost(bool, optional, tag = "12")]
+    liquidpool: Option<bool>,
+    #[prost(bool, optional, tag = "19")]
+    liquidcontainer: Option<bool>,
+    #[prost(message, optional, tag = "25")]
+    light: Option<AppearanceFlagLight>,
+    #[prost(message, optional, tag = "26")]
+    shift: Option<AppearanceFlagShift>,
+    #[prost(message, optional, tag = "27")]
+    height: Option<AppearanceFlagHeight>,
+    #[prost(message, optional, tag = "30")]
+    automap: Option<AppearanceFlagAutomap>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct AppearanceFlagBank {
+    #[prost(uint32, optional, tag = "1")]
+    waypoints: Option<u32>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct AppearanceFlagLight {
+    #[prost(uint32, optional, tag = "1")]
+    brightness: Option<u32>,
+    #[prost(uint32, optional, tag = "2")]
+    color: Option<u32>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct AppearanceFlagShift {
+    #[prost(uint32, optional, tag = "1")]
+    x: Option<u32>,
+    #[prost(uint32, optional, tag = "2")]
+    y: Option<u32>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct AppearanceFlagHeight {
+    #[prost(uint32, optional, tag = "1")]
+    elevation: Option<u32>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct AppearanceFlagAutomap {
+    #[prost(uint32, optional, tag = "1")]
+    color: Option<u32>,
+}
+
+impl<T: Read + Sized> Parse<Document> for T {
+    fn parse(mut self) -> Result<Document, Error> {
+        let mut buf = Vec::new();
+        self.read_to_end(&mut buf)?;
+        let appearances = Appearances::decode(buf.as_slice()).map_err(|_| Error::Malformed)?;
+
+        let mut items = HashMap::new();
+
+        for appearance in appearances.object.into_iter() {
+            let id = appearance
+                .id
+                .and_then(|id| u16::try_from(id).ok())
+                .ok_or(Error::Malformed)?;
+
+            // objects have a single frame group, only outfits use more of them
+            let Some(sprite_info) = appearance
+                .frame_group
+                .into_iter()
+                .next()
+                .and_then(|frame_group| frame_group.sprite_info)
+            else {
+                println!("Skipping object {id} without sprites");
+                continue;
+            };
+
+            let flags = appearance.flags.unwrap_or_default();
+            let draw_offset = Document::draw_offset(&flags);
+
+            let mut height_offset = Offset::default();
+            if let Some(height) = flags.height {
+                let elevation = height.elevation.unwrap_or_default() as u16;
+                height_offset.x = elevation;
+                height_offset.y = elevation;
+            }
+
+            let stack_order = if flags.bank.is_some() {
+                StackOrder::Ground
+            } else if flags.clip.unwrap_or(false) {
+                StackOrder::Border
+            } else if flags.bottom.unwrap_or(false) {
+                StackOrder::Bottom
+            } else if flags.top.unwrap_or(false) {
+                StackOrder::Top
+            } else {
+                StackOrder::Common
+            };
+
+            let textures = Document::textures(sprite_info);
+
+            let item = Item {
+                id,
+                name: appearance
+                    .name
+                    .map(|name| String::from_utf8_lossy(&name).to_string()),
+                minimap_color: flags
+                    .automap
+                    .and_then(|automap| automap.color)
+                    .map(|color| color as u16),
+                light: flags.light.map(|light| Light {
+                    level: light.brightness.unwrap_or_default() as u16,
+                    color: light.color.unwrap_or_default() as u16,
+                }),
+                ground: flags.bank.is_some(),
+                stackable: flags.cumulative.unwrap_or(false),
+                splash: flags.liquidpool.unwrap_or(false),
+                fluid_container: flags.liquidcontainer.unwrap_or(false),
+                stack_order,
+                draw_offset,
+                height_offset,
+                textures,
+            };
+            items.insert(id, item);
+        }
+
+        // outfits have a frame group for standing and one for walking, the first one is enough for the map
+        let mut outfits = HashMap::new();
+        for appearance in appearances.outfit.into_iter() {
+            let id = appearance
+                .id
+                .and_then(|id| u16::try_from(id).ok())
+                .ok_or(Error::Malformed)?;
+            let Some(sprite_info) = appearance
+                .frame_group
+                .into_iter()
+                .next()
+                .and_then(|frame_group| frame_group.sprite_info)
+            else {
+                continue;
+            };
+
+            let outfit = Outfit {
+                id,
+                draw_offset: Document::draw_offset(&appearance.flags.unwrap_or_default()),
+                textures: Document::textures(sprite_info),
+            };
+            outfits.insert(id, outfit);
+        }
+
+        Ok(Document { items, outfits })
+    }
+}
+
+impl Document {
+    fn draw_offset(flags: &AppearanceFlags) -> Offset {
+        let mut draw_offset = Offset::default();
+        if let Some(shift) = flags.shift.as_ref() {
+            draw_offset.x = shift.x.unwrap_or_default() as u16;
+            draw_offset.y = shift.y.unwrap_or_default() as u16;
+        }
+        draw_offset
+    }
+
+    fn textures(sprite_info: SpriteInfo) -> Textures {
+        let frame_durations: Vec<u32> = sprite_info
+            .animation
+            .map(|animation| {
+                animation
+                    .sprite_phase
+                    .iter()
+                    .map(|phase| phase.duration_min.unwrap_or_default())
+                    .collect()
+            })
+            .unwrap_or_default();
+        let frames = frame_durations.len().max(1);
+
+        Textures {
+            layers: sprite_info.layers.unwrap_or(1) as u8,
+            patterns_x: sprite_info.pattern_width.unwrap_or(1) as u8,
+            patterns_y: sprite_info.pattern_height.unwrap_or(1) as u8,
+            patterns_z: sprite_info.pattern_depth.unwrap_or(1) as u8,
+            frames: frames as u8,
+            frame_durations,
+            sprites: sprite_info.sprite_id,
+        }
+    }
+}
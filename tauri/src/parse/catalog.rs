@@ -0,0 +1,50 @@
+use super::{Error, Parse};
+use serde::Deserialize;
+use std::io::Read;
+
+pub const FILE_NAME: &str = "catalog-content.json";
+
+pub struct Document {
+    pub entries: Vec<Entry>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Entry {
+    Appearances {
+        file: String,
+    },
+    Sprite {
+        file: String,
+        #[serde(rename = "spritetype")]
+        sprite_type: u8,
+        #[serde(rename = "firstspriteid")]
+        first_sprite_id: u32,
+        #[serde(rename = "lastspriteid")]
+        last_sprite_id: u32,
+    },
+    #[serde(other)]
+    Other,
+}
+
+impl<T: Read + Sized> Parse<Document> for T {
+    fn parse(self) -> Result<Document, Error> {
+        let entries = serde_json::from_reader(self).map_err(|_| Error::Malformed)?;
+        Ok(Document { entries })
+    }
+}
+
+impl Document {
+    pub fn appearances_file(&self) -> Option<&str> {
+        self.entries.iter().find_map(|entry| match entry {
+            Entry::Appearances { file } => Some(file.as_str()),
+            _ => None,
+        })
+    }
+
+    pub fn sprite_sheets(&self) -> impl Iterator<Item = &Entry> {
+        self.entries
+            .iter()
+            .filter(|entry| matches!(entry, Entry::Sprite { .. }))
+    }
+}
@@ -0,0 +1,53 @@
+use super::{Error, Parse};
+use model::Look;
+use std::io::Read;
+
+// monster and npc scripts of canary, only the name and the outfit table are read without running lua
+pub struct Document {
+    pub name: String,
+    pub look: Option<Look>,
+}
+
+const NAME_PREFIXES: [&str; 2] = ["Game.createMonsterType(", "internalNpcName ="];
+
+impl<T: Read + Sized> Parse<Document> for T {
+    fn parse(mut self) -> Result<Document, Error> {
+        let mut lua = String::new();
+        self.read_to_string(&mut lua)?;
+
+        let name = NAME_PREFIXES
+            .iter()
+            .find_map(|prefix| {
+                let rest = &lua[lua.find(prefix)? + prefix.len()..];
+                let start = rest.find('"')? + 1;
+                let end = start + rest[start..].find('"')?;
+                Some(rest[start..end].to_string())
+            })
+            .ok_or(Error::Malformed)?;
+
+        let look = lua.find(".outfit = {").and_then(|start| {
+            let table = &lua[start..];
+            let table = &table[..table.find('}')?];
+            let value = |key: &str| {
+                let rest = &table[table.find(key)? + key.len()..];
+                let rest = rest.trim_start().strip_prefix('=')?.trim_start();
+                let end = rest
+                    .find(|c: char| !c.is_ascii_digit())
+                    .unwrap_or(rest.len());
+                rest[..end].parse::<u16>().ok()
+            };
+            Some(Look {
+                look_type: value("lookType")?,
+                head: value("lookHead").unwrap_or(0) as u8,
+                body: value("lookBody").unwrap_or(0) as u8,
+                legs: value("lookLegs").unwrap_or(0) as u8,
+                feet: value("lookFeet").unwrap_or(0) as u8,
+                addons: value("lookAddons").unwrap_or(0) as u8,
+                mount: value("lookMount").unwrap_or(0),
+            })
+            .filter(|look| look.look_type != 0)
+        });
+
+        Ok(Document { name, look })
+    }
+}
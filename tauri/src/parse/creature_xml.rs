@@ -0,0 +1,79 @@
+use super::{Error, Parse};
+use model::Look;
+use quick_xml::{
+    events::{BytesStart, Event},
+    Reader,
+};
+use std::{collections::HashMap, io::Read};
+
+// a monster or npc file, creatures that look like an item have no look type
+pub struct Document {
+    pub name: String,
+    pub look: Option<Look>,
+}
+
+impl<T: Read + Sized> Parse<Document> for T {
+    fn parse(mut self) -> Result<Document, Error> {
+        let mut xml = String::new();
+        self.read_to_string(&mut xml)?;
+        let mut reader = Reader::from_str(&xml);
+
+        let mut name = None;
+        let mut look = None;
+
+        loop {
+            match reader.read_event().map_err(|_| Error::Malformed)? {
+                Event::Start(element) | Event::Empty(element)
+                    if matches!(element.name().as_ref(), b"monster" | b"npc") =>
+                {
+                    name = Document::attributes(&element)?.remove("name");
+                }
+                Event::Start(element) | Event::Empty(element)
+                    if element.name().as_ref() == b"look" =>
+                {
+                    let attributes = Document::attributes(&element)?;
+                    let value = |key: &str| {
+                        attributes
+                            .get(key)
+                            .and_then(|value| value.parse().ok())
+                            .unwrap_or(0)
+                    };
+                    look = Some(Look {
+                        look_type: value("type"),
+                        head: value("head") as u8,
+                        body: value("body") as u8,
+                        legs: value("legs") as u8,
+                        feet: value("feet") as u8,
+                        addons: value("addons") as u8,
+                        mount: value("mount"),
+                    })
+                    .filter(|look| look.look_type != 0);
+                }
+                Event::Eof => break,
+                _ => (),
+            }
+        }
+
+        Ok(Document {
+            name: name.ok_or(Error::Malformed)?,
+            look,
+        })
+    }
+}
+
+impl Document {
+    fn attributes(element: &BytesStart) -> Result<HashMap<String, String>, Error> {
+        element
+            .attributes()
+            .map(|attribute| {
+                let attribute = attribute.map_err(|_| Error::Malformed)?;
+                let key = String::from_utf8_lossy(attribute.key.as_ref()).to_lowercase();
+                let value = attribute
+                    .unescape_value()
+                    .map_err(|_| Error::Malformed)?
+                    .to_string();
+                Ok((key, value))
+            })
+            .collect()
+    }
+}
@@ -1,26 +1,36 @@
 use super::{Error, Parse};
 use bytes::{Buf, Bytes};
-use model::Offset;
+use model::{Light, Offset, StackOrder};
 use num_enum::{IntoPrimitive, TryFromPrimitive};
 use std::{collections::HashMap, io::Read};
 
 pub struct Document {
     pub signature: u32,
     pub items: HashMap<u16, Item>,
+    pub outfits: HashMap<u16, Outfit>,
 }
 
 pub struct Item {
     pub id: u16,
     pub minimap_color: Option<u16>,
+    pub light: Option<Light>,
     pub ground: bool,
     pub stackable: bool,
     pub splash: bool,
     pub fluid_container: bool,
+    pub stack_order: StackOrder,
     pub draw_offset: Offset,
     pub height_offset: Offset,
     pub textures: Textures,
 }
 
+// outfits share the format of items, only their offset and textures are used
+pub struct Outfit {
+    pub id: u16,
+    pub draw_offset: Offset,
+    pub textures: Textures,
+}
+
 #[derive(Debug)]
 pub struct Textures {
     pub width: u8,
@@ -89,109 +99,140 @@ impl<T: Read + Sized> Parse<Document> for T {
         let effects_count = bytes.get_u16_le();
         let distance_effects_count = bytes.get_u16_le();
 
-        // TODO: handle outfits, effects and distances
+        // TODO: handle effects and distances
 
+        // item ids start at 100 and the count is the last one, outfit ids start at 1
         let mut items = HashMap::new();
+        for id in 100..=items_count {
+            items.insert(id, Document::parse_item(&mut bytes, id));
+
+            // TODO: update progress?
+        }
+
+        let mut outfits = HashMap::new();
+        for id in 1..=outfits_count {
+            let item = Document::parse_item(&mut bytes, id);
+            outfits.insert(
+                id,
+                Outfit {
+                    id,
+                    draw_offset: item.draw_offset,
+                    textures: item.textures,
+                },
+            );
+        }
+
+        Ok(Document {
+            signature,
+            items,
+            outfits,
+        })
+    }
+}
 
-        for i in 0..items_count {
-            let mut ground = false;
-            let mut stackable = false;
-            let mut splash = false;
-            let mut fluid_container = false;
-            let mut draw_offset = Offset::default();
-            let mut height_offset = Offset::default();
-            let mut minimap_color = None;
-
-            let mut byte = bytes.get_u8();
-            while byte != u8::from(SpecialCharacter::FlagsEnd) {
-                if let Ok(flag) = ItemFlag::try_from(byte) {
-                    match flag {
-                        ItemFlag::Stackable => stackable = true,
-                        ItemFlag::Splash => splash = true,
-                        ItemFlag::FluidContainer => fluid_container = true,
-                        ItemFlag::MinimapColor => minimap_color = Some(bytes.get_u16_le()),
-                        ItemFlag::Height => {
-                            let height = bytes.get_u16_le();
-                            height_offset.x = height;
-                            height_offset.y = height;
-                        }
-                        ItemFlag::Ground => {
-                            // TODO: handle speed?
-                            let speed = bytes.get_u16_le();
-
-                            ground = true;
-                        }
-                        ItemFlag::Writeable | ItemFlag::Readable | ItemFlag::FloorChange => {
-                            // TODO: handle these properties
-                            bytes.get_u16_le();
-                        }
-                        ItemFlag::LightInfo => {
-                            // TODO: handle this property
-                            bytes.get_u32_le();
-                        }
-                        ItemFlag::DrawOffset => {
-                            draw_offset.x = bytes.get_u16_le();
-                            draw_offset.y = bytes.get_u16_le();
-                        }
-                        _ => (),
+impl Document {
+    fn parse_item(bytes: &mut Bytes, id: u16) -> Item {
+        let mut ground = false;
+        let mut stackable = false;
+        let mut splash = false;
+        let mut fluid_container = false;
+        let mut stack_order = StackOrder::Common;
+        let mut draw_offset = Offset::default();
+        let mut height_offset = Offset::default();
+        let mut minimap_color = None;
+        let mut light = None;
+
+        let mut byte = bytes.get_u8();
+        while byte != u8::from(SpecialCharacter::FlagsEnd) {
+            if let Ok(flag) = ItemFlag::try_from(byte) {
+                match flag {
+                    ItemFlag::Stackable => stackable = true,
+                    ItemFlag::Splash => splash = true,
+                    ItemFlag::FluidContainer => fluid_container = true,
+                    ItemFlag::MinimapColor => minimap_color = Some(bytes.get_u16_le()),
+                    ItemFlag::Height => {
+                        let height = bytes.get_u16_le();
+                        height_offset.x = height;
+                        height_offset.y = height;
                     }
-                }
-                byte = bytes.get_u8();
-            }
+                    ItemFlag::Ground => {
+                        // TODO: handle speed?
+                        let speed = bytes.get_u16_le();
 
-            let width = bytes.get_u8();
-            let height = bytes.get_u8();
-            if width > 1 || height > 1 {
-                bytes.get_u8(); // TODO: check in rme/otclient code maybe we can find what this byte contains
-            }
-            let layers = bytes.get_u8();
-            let patterns_x = bytes.get_u8();
-            let patterns_y = bytes.get_u8();
-            let patterns_z = bytes.get_u8();
-            let frames = bytes.get_u8();
-
-            let sprites_count = width as u16
-                * height as u16
-                * layers as u16
-                * patterns_x as u16
-                * patterns_y as u16
-                * patterns_z as u16
-                * frames as u16;
-
-            let mut sprites = Vec::new();
-
-            for _ in 0..sprites_count {
-                sprites.push(bytes.get_u16_le());
+                        ground = true;
+                        stack_order = StackOrder::Ground;
+                    }
+                    // the old names come from what these orders are usually used for
+                    ItemFlag::OnTop => stack_order = StackOrder::Border,
+                    ItemFlag::WalkThroughDoors => stack_order = StackOrder::Bottom,
+                    ItemFlag::WalkThroughArches => stack_order = StackOrder::Top,
+                    ItemFlag::Writeable | ItemFlag::Readable | ItemFlag::FloorChange => {
+                        // TODO: handle these properties
+                        bytes.get_u16_le();
+                    }
+                    ItemFlag::LightInfo => {
+                        let level = bytes.get_u16_le();
+                        let color = bytes.get_u16_le();
+                        light = Some(Light { level, color });
+                    }
+                    ItemFlag::DrawOffset => {
+                        draw_offset.x = bytes.get_u16_le();
+                        draw_offset.y = bytes.get_u16_le();
+                    }
+                    _ => (),
+                }
             }
+            byte = bytes.get_u8();
+        }
 
-            let textures = Textures {
-                width,
-                height,
-                layers,
-                patterns_x,
-                patterns_y,
-                patterns_z,
-                frames,
-                sprites,
-            };
-
-            let id = i + 100;
-            let item = Item {
-                id,
-                minimap_color,
-                ground,
-                stackable,
-                splash,
-                fluid_container,
-                draw_offset,
-                height_offset,
-                textures,
-            };
-            items.insert(id, item);
-
-            // TODO: update progress?
+        let width = bytes.get_u8();
+        let height = bytes.get_u8();
+        if width > 1 || height > 1 {
+            bytes.get_u8(); // TODO: check in rme/otclient code maybe we can find what this byte contains
+        }
+        let layers = bytes.get_u8();
+        let patterns_x = bytes.get_u8();
+        let patterns_y = bytes.get_u8();
+        let patterns_z = bytes.get_u8();
+        let frames = bytes.get_u8();
+
+        let sprites_count = width as u16
+            * height as u16
+            * layers as u16
+            * patterns_x as u16
+            * patterns_y as u16
+            * patterns_z as u16
+            * frames as u16;
+
+        let mut sprites = Vec::new();
+
+        for _ in 0..sprites_count {
+            sprites.push(bytes.get_u16_le());
         }
 
-        Ok(Document { signature, items })
+        let textures = Textures {
+            width,
+            height,
+            layers,
+            patterns_x,
+            patterns_y,
+            patterns_z,
+            frames,
+            sprites,
+        };
+
+        Item {
+            id,
+            minimap_color,
+            light,
+            ground,
+            stackable,
+            splash,
+            fluid_container,
+            stack_order,
+            draw_offset,
+            height_offset,
+            textures,
+        }
     }
 }
@@ -0,0 +1,209 @@
+use super::{Error, Parse};
+use quick_xml::{
+    events::{BytesStart, Event},
+    Reader,
+};
+use std::{collections::HashMap, io::Read};
+
+pub struct Document {
+    pub items: HashMap<u16, Item>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Item {
+    pub id: u16,
+    pub name: Option<String>,
+    pub article: Option<String>,
+    pub plural: Option<String>,
+    pub item_type: Option<String>,
+    pub weight: Option<u32>,
+    pub armor: Option<u16>,
+    pub decay_to: Option<u16>,
+    pub container_size: Option<u16>,
+    pub floor_change: Option<String>,
+    pub attributes: HashMap<String, String>,
+}
+
+impl<T: Read + Sized> Parse<Document> for T {
+    fn parse(mut self) -> Result<Document, Error> {
+        let mut xml = String::new();
+        self.read_to_string(&mut xml)?;
+        let mut reader = Reader::from_str(&xml);
+
+        let mut items = HashMap::new();
+        // items.xml can describe a whole range of ids with fromid and toid
+        let mut current: Option<(Vec<u16>, Item)> = None;
+        // keys of the attributes the current one is nested in
+        let mut parents: Vec<String> = Vec::new();
+
+        loop {
+            match reader.read_event().map_err(|_| Error::Malformed)? {
+                Event::Start(element) if element.name().as_ref() == b"item" => {
+                    current = Document::parse_item(&element)?;
+                    parents.clear();
+                }
+                Event::Empty(element) if element.name().as_ref() == b"item" => {
+                    if let Some((ids, item)) = Document::parse_item(&element)? {
+                        Document::insert_items(&mut items, ids, item);
+                    }
+                }
+                Event::Start(element) if element.name().as_ref() == b"attribute" => {
+                    let key = Document::set_attribute(current.as_mut(), &parents, &element)?;
+                    parents.push(key);
+                }
+                Event::Empty(element) if element.name().as_ref() == b"attribute" => {
+                    Document::set_attribute(current.as_mut(), &parents, &element)?;
+                }
+                Event::End(element) if element.name().as_ref() == b"attribute" => {
+                    parents.pop();
+                }
+                Event::End(element) if element.name().as_ref() == b"item" => {
+                    if let Some((ids, item)) = current.take() {
+                        Document::insert_items(&mut items, ids, item);
+                    }
+                }
+                Event::Eof => break,
+                _ => (),
+            }
+        }
+
+        Ok(Document { items })
+    }
+}
+
+impl Document {
+    fn attributes(element: &BytesStart) -> Result<HashMap<String, String>, Error> {
+        element
+            .attributes()
+            .map(|attribute| {
+                let attribute = attribute.map_err(|_| Error::Malformed)?;
+                let key = String::from_utf8_lossy(attribute.key.as_ref()).to_lowercase();
+                let value = attribute
+                    .unescape_value()
+                    .map_err(|_| Error::Malformed)?
+                    .to_string();
+                Ok((key, value))
+            })
+            .collect()
+    }
+
+    // attributes of skipped items are dropped, the key is returned for the ones nested in it
+    fn set_attribute(
+        current: Option<&mut (Vec<u16>, Item)>,
+        parents: &[String],
+        element: &BytesStart,
+    ) -> Result<String, Error> {
+        let attributes = Document::attributes(element)?;
+        let key = attributes
+            .get("key")
+            .map(|key| key.to_lowercase())
+            .unwrap_or_default();
+        if let (Some((_, item)), Some(value)) = (current, attributes.get("value")) {
+            item.set_attribute(parents, &key, value);
+        }
+        Ok(key)
+    }
+
+    // none for items with neither an id nor a range of them
+    fn parse_item(element: &BytesStart) -> Result<Option<(Vec<u16>, Item)>, Error> {
+        let attributes = Document::attributes(element)?;
+        let id = |key: &str| {
+            attributes
+                .get(key)
+                .map(|id| id.parse::<u16>().map_err(|_| Error::Malformed))
+                .transpose()
+        };
+
+        let ids = match (id("id")?, id("fromid")?, id("toid")?) {
+            (Some(id), _, _) => vec![id],
+            (None, Some(from_id), Some(to_id)) => (from_id..=to_id).collect(),
+            _ => {
+                println!(
+                    "Skipping item without an id: {}",
+                    attributes.get("name").map_or("", String::as_str)
+                );
+                return Ok(None);
+            }
+        };
+
+        let item = Item {
+            name: attributes.get("name").cloned(),
+            article: attributes.get("article").cloned(),
+            plural: attributes.get("plural").cloned(),
+            ..Default::default()
+        };
+
+        Ok(Some((ids, item)))
+    }
+
+    fn insert_items(items: &mut HashMap<u16, Item>, ids: Vec<u16>, item: Item) {
+        for id in ids.into_iter() {
+            items.insert(id, Item { id, ..item.clone() });
+        }
+    }
+}
+
+impl Item {
+    // nested attributes belong to their parent, they're kept as e.g. "field.ticks"
+    fn set_attribute(&mut self, parents: &[String], key: &str, value: &str) {
+        if !parents.is_empty() {
+            let key = format!("{}.{key}", parents.join("."));
+            self.attributes.insert(key, value.to_string());
+            return;
+        }
+
+        match key {
+            "type" => self.item_type = Some(value.to_string()),
+            "weight" => self.weight = value.parse().ok(),
+            "armor" => self.armor = value.parse().ok(),
+            "decayto" => self.decay_to = value.parse().ok(),
+            "containersize" => self.container_size = value.parse().ok(),
+            "floorchange" => self.floor_change = Some(value.to_string()),
+            key => {
+                self.attributes.insert(key.to_string(), value.to_string());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    const ITEMS_XML: &str = r#"<?xml version="1.0"?>
+<items>
+  <item id="100" name="void" />
+  <item name="without an id">
+    <attribute key="weight" value="1" />
+  </item>
+  <item fromid="101" toid="103" article="an" name="earth &amp; stone" />
+  <item id="1487" article="a" name="fire field">
+    <attribute key="type" value="magicfield" />
+    <attribute key="field" value="fire">
+      <attribute key="type" value="fire" />
+      <attribute key="ticks" value="10000" />
+    </attribute>
+    <attribute key="decayTo" value="1488" />
+  </item>
+</items>"#;
+
+    #[test]
+    fn items_without_ids_are_skipped() {
+        let document: Document = Cursor::new(ITEMS_XML).parse().unwrap();
+        assert_eq!(document.items.len(), 5);
+        assert_eq!(document.items[&102].name.as_deref(), Some("earth & stone"));
+        assert_eq!(document.items[&102].id, 102);
+    }
+
+    #[test]
+    fn nested_attributes_keep_their_parent() {
+        let document: Document = Cursor::new(ITEMS_XML).parse().unwrap();
+        let item = &document.items[&1487];
+        assert_eq!(item.item_type.as_deref(), Some("magicfield"));
+        assert_eq!(item.decay_to, Some(1488));
+        assert_eq!(item.attributes["field"], "fire");
+        assert_eq!(item.attributes["field.type"], "fire");
+        assert_eq!(item.attributes["field.ticks"], "10000");
+    }
+}
@@ -1,4 +1,4 @@
-use std::io::{self, Read};
+use std::io::{self, Read, Write};
 
 use serde::Serialize;
 
@@ -18,7 +18,19 @@ pub trait Parse<T>: Read + Sized {
     fn parse(self) -> Result<T, Error>;
 }
 
+pub trait Encode<T>: Write + Sized {
+    fn encode(self, document: &T) -> Result<(), Error>;
+}
+
+pub mod appearances;
+pub mod catalog;
+pub mod creature_lua;
+pub mod creature_xml;
 pub mod dat;
+pub mod items_xml;
+pub mod monsters_xml;
 pub mod otb;
 pub mod otbm;
+pub mod spawns_xml;
 pub mod spr;
+pub mod sprite_sheet;
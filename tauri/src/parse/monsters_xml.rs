@@ -0,0 +1,55 @@
+use super::{Error, Parse};
+use quick_xml::{events::Event, Reader};
+use std::io::Read;
+
+// the index of monster files, paths are relative to it
+pub struct Document {
+    pub monsters: Vec<Monster>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Monster {
+    pub name: String,
+    pub file: String,
+}
+
+impl<T: Read + Sized> Parse<Document> for T {
+    fn parse(mut self) -> Result<Document, Error> {
+        let mut xml = String::new();
+        self.read_to_string(&mut xml)?;
+        let mut reader = Reader::from_str(&xml);
+
+        let mut monsters = Vec::new();
+
+        loop {
+            match reader.read_event().map_err(|_| Error::Malformed)? {
+                Event::Start(element) | Event::Empty(element)
+                    if element.name().as_ref() == b"monster" =>
+                {
+                    let mut name = None;
+                    let mut file = None;
+                    for attribute in element.attributes() {
+                        let attribute = attribute.map_err(|_| Error::Malformed)?;
+                        let value = attribute
+                            .unescape_value()
+                            .map_err(|_| Error::Malformed)?
+                            .to_string();
+                        match attribute.key.as_ref() {
+                            b"name" => name = Some(value),
+                            b"file" => file = Some(value),
+                            _ => (),
+                        }
+                    }
+                    monsters.push(Monster {
+                        name: name.ok_or(Error::Malformed)?,
+                        file: file.ok_or(Error::Malformed)?,
+                    });
+                }
+                Event::Eof => break,
+                _ => (),
+            }
+        }
+
+        Ok(Document { monsters })
+    }
+}
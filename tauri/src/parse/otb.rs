@@ -1,23 +1,30 @@
-use super::{Error, Parse};
-use bytes::{Buf, Bytes};
-use num_enum::TryFromPrimitive;
+use super::{Encode, Error, Parse};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use itertools::Itertools;
+use model::Light;
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+use serde::Deserialize;
 use std::{
     collections::HashMap,
-    io::Read,
-    mem::{size_of_val, take},
+    io::{Read, Write},
+    mem::{size_of, take},
 };
 
+#[derive(Clone)]
 pub struct Document {
     pub major_version: u32,
     pub minor_version: u32,
     pub build_version: u32,
+    pub description: String,
     pub items: HashMap<u16, Item>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Item {
     pub group: ItemGroup,
-    pub flags: u32, // TODO: decode it into a vector of flags
+    pub flags: Vec<ItemFlag>,
+    // as read from the file, flags this editor doesn't know about are written back from it
+    pub raw_flags: u32,
     pub server_id: u16,
     pub client_id: u16,
     pub name: Option<String>,
@@ -29,12 +36,8 @@ pub struct Item {
     pub light: Option<Light>,
     pub stack_order: Option<StackOrder>,
     pub trade_as: Option<u16>,
-}
-
-#[derive(Debug)]
-pub struct Light {
-    pub level: u16,
-    pub color: u16,
+    // attributes this editor doesn't know about, written back as they were read
+    pub attributes: Vec<(u8, Vec<u8>)>,
 }
 
 #[derive(Debug)]
@@ -44,7 +47,7 @@ struct Node {
 }
 
 #[repr(u8)]
-#[derive(TryFromPrimitive)]
+#[derive(IntoPrimitive, TryFromPrimitive)]
 enum SpecialCharacter {
     Start = 0xFE,
     End = 0xFF,
@@ -52,7 +55,7 @@ enum SpecialCharacter {
 }
 
 #[repr(u8)]
-#[derive(TryFromPrimitive, Debug)]
+#[derive(IntoPrimitive, TryFromPrimitive, Debug, Clone, Copy, PartialEq, Deserialize)]
 pub enum ItemGroup {
     None = 0,
     Ground = 1,
@@ -83,7 +86,7 @@ pub enum ItemType {
 }
 
 #[repr(u8)]
-#[derive(TryFromPrimitive, Debug)]
+#[derive(IntoPrimitive, TryFromPrimitive, Debug, Clone, Copy, PartialEq)]
 pub enum StackOrder {
     None = 0,
     Border = 1,
@@ -91,7 +94,9 @@ pub enum StackOrder {
     Top = 3,
 }
 
-enum ItemFlag {
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ItemFlag {
     None = 0,
     Unpassable = 1 << 0,
     BlockMissiles = 1 << 1,
@@ -122,8 +127,61 @@ enum ItemFlag {
     ForceUse = 1 << 26,
 }
 
+impl ItemFlag {
+    const ALL: [ItemFlag; 27] = [
+        ItemFlag::Unpassable,
+        ItemFlag::BlockMissiles,
+        ItemFlag::BlockPathfinder,
+        ItemFlag::HasElevation,
+        ItemFlag::MultiUse,
+        ItemFlag::Pickupable,
+        ItemFlag::Movable,
+        ItemFlag::Stackable,
+        ItemFlag::FloorChangeDown,
+        ItemFlag::FloorChangeNorth,
+        ItemFlag::FloorChangeEast,
+        ItemFlag::FloorChangeSouth,
+        ItemFlag::FloorChangeWest,
+        ItemFlag::StackOrder,
+        ItemFlag::Readable,
+        ItemFlag::Rotatable,
+        ItemFlag::Hangable,
+        ItemFlag::HookSouth,
+        ItemFlag::HookEast,
+        ItemFlag::CanNotDecay,
+        ItemFlag::AllowDistanceRead,
+        ItemFlag::Unused,
+        ItemFlag::ClientCharges,
+        ItemFlag::IgnoreLook,
+        ItemFlag::IsAnimation,
+        ItemFlag::FullGround,
+        ItemFlag::ForceUse,
+    ];
+
+    pub fn decode(flags: u32) -> Vec<ItemFlag> {
+        ItemFlag::ALL
+            .into_iter()
+            .filter(|flag| flags & *flag as u32 != 0)
+            .collect()
+    }
+
+    pub fn encode(flags: &[ItemFlag]) -> u32 {
+        flags.iter().fold(0, |bits, flag| bits | *flag as u32)
+    }
+
+    pub fn unknown(flags: u32) -> u32 {
+        flags & !ItemFlag::encode(&ItemFlag::ALL)
+    }
+}
+
 #[repr(u8)]
-#[derive(TryFromPrimitive)]
+#[derive(IntoPrimitive, TryFromPrimitive)]
+enum RootAttribute {
+    Version = 0x01,
+}
+
+#[repr(u8)]
+#[derive(IntoPrimitive, TryFromPrimitive)]
 enum ItemAttribute {
     ServerId = 0x10,
     ClientId = 0x11,
@@ -151,21 +209,21 @@ impl<T: Read + Sized> Parse<Document> for T {
         let mut major_version = None;
         let mut minor_version = None;
         let mut build_version = None;
+        let mut description = String::new();
 
         let attr = otb.get_u8().unwrap();
 
-        // TODO: use enum instead
-        if attr == 0x01 {
+        if let Ok(RootAttribute::Version) = RootAttribute::try_from(attr) {
             let data_length = otb.get_u16_le().unwrap();
             major_version = otb.get_u32_le();
             minor_version = otb.get_u32_le();
             build_version = otb.get_u32_le();
-            otb.get_slice(
-                usize::from(data_length)
-                    - size_of_val(&major_version)
-                    - size_of_val(&minor_version)
-                    - size_of_val(&build_version),
-            );
+            let csd_version = otb.get_slice(usize::from(data_length) - size_of::<u32>() * 3);
+            if let Some(csd_version) = csd_version {
+                description = String::from_utf8_lossy(&csd_version)
+                    .trim_end_matches('\0')
+                    .to_string();
+            }
         }
 
         let mut item_nodes = otb.children;
@@ -186,44 +244,43 @@ impl<T: Read + Sized> Parse<Document> for T {
             let mut light = None;
             let mut stack_order = None;
             let mut trade_as = None;
+            let mut attributes = Vec::new();
 
             while node.has_remaining() {
                 let attr = node.get_u8().unwrap();
                 let data_length = node.get_u16_le().unwrap();
 
-                if let Ok(attr) = ItemAttribute::try_from(attr) {
-                    match attr {
-                        ItemAttribute::ServerId => server_id = node.get_u16_le(),
-                        ItemAttribute::ClientId => client_id = node.get_u16_le(),
-                        ItemAttribute::Name => {
-                            let length = node.get_u16_le().unwrap();
-                            name = Some(
-                                String::from_utf8_lossy(&node.get_slice(length.into()).unwrap())
-                                    .to_string(),
-                            );
-                        }
-                        ItemAttribute::GroundSpeed => ground_speed = node.get_u16_le(),
-                        ItemAttribute::SpriteHash => {
-                            sprite_hash = node.get_slice(data_length.into())
-                        }
-                        ItemAttribute::MinimapColor => minimap_color = node.get_u16_le(),
-                        ItemAttribute::MaxReadWriteChars => {
-                            max_read_write_chars = node.get_u16_le()
-                        }
-                        ItemAttribute::MaxReadChars => max_read_chars = node.get_u16_le(),
-                        ItemAttribute::Light => {
-                            let level = node.get_u16_le().unwrap();
-                            let color = node.get_u16_le().unwrap();
-                            light = Some(Light { level, color });
-                        }
-                        ItemAttribute::StackOrder => {
-                            stack_order = node
-                                .get_u8()
-                                .and_then(|stack_order| StackOrder::try_from(stack_order).ok())
-                        }
-                        ItemAttribute::TradeAs => trade_as = node.get_u16_le(),
-                        _ => {
-                            node.get_slice(data_length.into());
+                match ItemAttribute::try_from(attr) {
+                    Ok(ItemAttribute::ServerId) => server_id = node.get_u16_le(),
+                    Ok(ItemAttribute::ClientId) => client_id = node.get_u16_le(),
+                    Ok(ItemAttribute::Name) => {
+                        name = node
+                            .get_slice(data_length.into())
+                            .map(|name| String::from_utf8_lossy(&name).to_string());
+                    }
+                    Ok(ItemAttribute::GroundSpeed) => ground_speed = node.get_u16_le(),
+                    Ok(ItemAttribute::SpriteHash) => {
+                        sprite_hash = node.get_slice(data_length.into())
+                    }
+                    Ok(ItemAttribute::MinimapColor) => minimap_color = node.get_u16_le(),
+                    Ok(ItemAttribute::MaxReadWriteChars) => {
+                        max_read_write_chars = node.get_u16_le()
+                    }
+                    Ok(ItemAttribute::MaxReadChars) => max_read_chars = node.get_u16_le(),
+                    Ok(ItemAttribute::Light) => {
+                        let level = node.get_u16_le().unwrap();
+                        let color = node.get_u16_le().unwrap();
+                        light = Some(Light { level, color });
+                    }
+                    Ok(ItemAttribute::StackOrder) => {
+                        stack_order = node
+                            .get_u8()
+                            .and_then(|stack_order| StackOrder::try_from(stack_order).ok())
+                    }
+                    Ok(ItemAttribute::TradeAs) => trade_as = node.get_u16_le(),
+                    Err(_) => {
+                        if let Some(data) = node.get_slice(data_length.into()) {
+                            attributes.push((attr, data));
                         }
                     }
                 }
@@ -231,7 +288,8 @@ impl<T: Read + Sized> Parse<Document> for T {
 
             let item = Item {
                 group: ItemGroup::try_from(group).unwrap(),
-                flags,
+                flags: ItemFlag::decode(flags),
+                raw_flags: flags,
                 server_id: server_id.unwrap(),
                 client_id: client_id.unwrap(),
                 name,
@@ -243,6 +301,7 @@ impl<T: Read + Sized> Parse<Document> for T {
                 light,
                 stack_order,
                 trade_as,
+                attributes,
             };
             items.insert(item.server_id, item);
 
@@ -253,11 +312,238 @@ impl<T: Read + Sized> Parse<Document> for T {
             major_version: major_version.unwrap(),
             minor_version: minor_version.unwrap(),
             build_version: build_version.unwrap(),
+            description,
             items,
         })
     }
 }
 
+impl<T: Write + Sized> Encode<Document> for T {
+    fn encode(mut self, document: &Document) -> Result<(), Error> {
+        let mut otb = NodeWriter::new();
+
+        otb.start(0);
+        otb.put_u32_le(0);
+        otb.put_attribute(RootAttribute::Version.into(), |data| {
+            data.put_u32_le(document.major_version);
+            data.put_u32_le(document.minor_version);
+            data.put_u32_le(document.build_version);
+            let mut csd_version = document.description.clone().into_bytes();
+            csd_version.resize(Document::CSD_VERSION_LENGTH, 0);
+            data.put_slice(&csd_version);
+        })?;
+
+        for item in document.items.values().sorted_by_key(|item| item.server_id) {
+            otb.start(item.group.into());
+            otb.put_u32_le(ItemFlag::encode(&item.flags) | ItemFlag::unknown(item.raw_flags));
+            otb.put_attribute(ItemAttribute::ServerId.into(), |data| {
+                data.put_u16_le(item.server_id)
+            })?;
+            otb.put_attribute(ItemAttribute::ClientId.into(), |data| {
+                data.put_u16_le(item.client_id)
+            })?;
+            if let Some(name) = item.name.as_ref() {
+                otb.put_attribute(ItemAttribute::Name.into(), |data| {
+                    data.put_slice(name.as_bytes())
+                })?;
+            }
+            if let Some(ground_speed) = item.ground_speed {
+                otb.put_attribute(ItemAttribute::GroundSpeed.into(), |data| {
+                    data.put_u16_le(ground_speed)
+                })?;
+            }
+            if let Some(sprite_hash) = item.sprite_hash.as_ref() {
+                otb.put_attribute(ItemAttribute::SpriteHash.into(), |data| {
+                    data.put_slice(sprite_hash)
+                })?;
+            }
+            if let Some(minimap_color) = item.minimap_color {
+                otb.put_attribute(ItemAttribute::MinimapColor.into(), |data| {
+                    data.put_u16_le(minimap_color)
+                })?;
+            }
+            if let Some(max_read_write_chars) = item.max_read_write_chars {
+                otb.put_attribute(ItemAttribute::MaxReadWriteChars.into(), |data| {
+                    data.put_u16_le(max_read_write_chars)
+                })?;
+            }
+            if let Some(max_read_chars) = item.max_read_chars {
+                otb.put_attribute(ItemAttribute::MaxReadChars.into(), |data| {
+                    data.put_u16_le(max_read_chars)
+                })?;
+            }
+            if let Some(Light { level, color }) = item.light {
+                otb.put_attribute(ItemAttribute::Light.into(), |data| {
+                    data.put_u16_le(level);
+                    data.put_u16_le(color);
+                })?;
+            }
+            if let Some(stack_order) = item.stack_order {
+                otb.put_attribute(ItemAttribute::StackOrder.into(), |data| {
+                    data.put_u8(stack_order.into())
+                })?;
+            }
+            if let Some(trade_as) = item.trade_as {
+                otb.put_attribute(ItemAttribute::TradeAs.into(), |data| {
+                    data.put_u16_le(trade_as)
+                })?;
+            }
+            for (attribute, bytes) in item.attributes.iter() {
+                otb.put_attribute(*attribute, |data| data.put_slice(bytes))?;
+            }
+            otb.end();
+        }
+
+        otb.end();
+
+        self.write_all(&[0; 4])?;
+        self.write_all(&otb.into_bytes())?;
+        self.flush()?;
+
+        Ok(())
+    }
+}
+
+impl Document {
+    const CSD_VERSION_LENGTH: usize = 128;
+    const FIRST_SERVER_ID: u16 = 100;
+
+    pub fn item(&self, server_id: u16) -> Option<&Item> {
+        self.items.get(&server_id)
+    }
+
+    // items are keyed by their server id, it's only changed through set_server_id
+    pub fn item_mut(&mut self, server_id: u16) -> Option<&mut Item> {
+        self.items.get_mut(&server_id)
+    }
+
+    // none when there's no such item or the new server id is taken
+    pub fn set_server_id(&mut self, server_id: u16, new_server_id: u16) -> Option<&mut Item> {
+        if server_id != new_server_id && self.items.contains_key(&new_server_id) {
+            return None;
+        }
+        let mut item = self.items.remove(&server_id)?;
+        item.server_id = new_server_id;
+        Some(self.items.entry(new_server_id).or_insert(item))
+    }
+
+    pub fn items_by_client_id(&self, client_id: u16) -> impl Iterator<Item = &Item> {
+        self.items
+            .values()
+            .filter(move |item| item.client_id == client_id)
+    }
+
+    pub fn server_ids_by_client_id(&self) -> HashMap<u16, Vec<u16>> {
+        let mut server_ids: HashMap<u16, Vec<u16>> = HashMap::new();
+        for item in self.items.values().sorted_by_key(|item| item.server_id) {
+            server_ids
+                .entry(item.client_id)
+                .or_default()
+                .push(item.server_id);
+        }
+        server_ids
+    }
+
+    pub fn next_server_id(&self) -> u16 {
+        self.items
+            .keys()
+            .max()
+            .map(|server_id| server_id + 1)
+            .unwrap_or(Document::FIRST_SERVER_ID)
+    }
+
+    // none when the server id is taken, the item there is kept
+    pub fn add_item(&mut self, item: Item) -> Option<&mut Item> {
+        if self.items.contains_key(&item.server_id) {
+            return None;
+        }
+        Some(self.items.entry(item.server_id).or_insert(item))
+    }
+
+    pub fn remove_item(&mut self, server_id: u16) -> Option<Item> {
+        self.items.remove(&server_id)
+    }
+}
+
+impl Item {
+    pub fn new(server_id: u16, client_id: u16, group: ItemGroup) -> Self {
+        Self {
+            group,
+            flags: Vec::new(),
+            raw_flags: 0,
+            server_id,
+            client_id,
+            name: None,
+            ground_speed: None,
+            sprite_hash: None,
+            minimap_color: None,
+            max_read_write_chars: None,
+            max_read_chars: None,
+            light: None,
+            stack_order: None,
+            trade_as: None,
+            attributes: Vec::new(),
+        }
+    }
+
+    pub fn has_flag(&self, flag: ItemFlag) -> bool {
+        self.flags.contains(&flag)
+    }
+
+    pub fn set_flag(&mut self, flag: ItemFlag, value: bool) {
+        if value && !self.has_flag(flag) {
+            self.flags.push(flag);
+        } else if !value {
+            self.flags.retain(|item_flag| *item_flag != flag);
+        }
+        if value {
+            self.raw_flags |= flag as u32;
+        } else {
+            self.raw_flags &= !(flag as u32);
+        }
+    }
+
+    pub fn group(mut self, group: ItemGroup) -> Self {
+        self.group = group;
+        self
+    }
+
+    pub fn flag(mut self, flag: ItemFlag) -> Self {
+        self.set_flag(flag, true);
+        self
+    }
+
+    pub fn client_id(mut self, client_id: u16) -> Self {
+        self.client_id = client_id;
+        self
+    }
+
+    pub fn name(mut self, name: Option<String>) -> Self {
+        self.name = name;
+        self
+    }
+
+    pub fn light(mut self, light: Option<Light>) -> Self {
+        self.light = light;
+        self
+    }
+
+    pub fn stack_order(mut self, stack_order: Option<StackOrder>) -> Self {
+        self.set_stack_order(stack_order);
+        self
+    }
+
+    pub fn trade_as(mut self, trade_as: Option<u16>) -> Self {
+        self.trade_as = trade_as;
+        self
+    }
+
+    pub fn set_stack_order(&mut self, stack_order: Option<StackOrder>) {
+        self.set_flag(ItemFlag::StackOrder, stack_order.is_some());
+        self.stack_order = stack_order;
+    }
+}
+
 impl Node {
     fn new(bytes: Bytes) -> Self {
         let mut otb_node = Node {
@@ -304,6 +590,8 @@ impl Node {
 
         if otb_node.node.is_none() {
             otb_node.set_node(node_escaped);
+        } else if !node.is_empty() {
+            otb_node.children.push(Node::new(Bytes::from(node)));
         }
 
         otb_node
@@ -344,3 +632,177 @@ impl Node {
         self.node = Some(Bytes::from(node));
     }
 }
+
+struct NodeWriter {
+    bytes: BytesMut,
+}
+
+impl NodeWriter {
+    fn new() -> Self {
+        Self {
+            bytes: BytesMut::new(),
+        }
+    }
+
+    fn start(&mut self, node_type: u8) {
+        self.bytes.put_u8(SpecialCharacter::Start.into());
+        self.put_u8(node_type);
+    }
+
+    fn end(&mut self) {
+        self.bytes.put_u8(SpecialCharacter::End.into());
+    }
+
+    fn put_u8(&mut self, byte: u8) {
+        if SpecialCharacter::try_from(byte).is_ok() {
+            self.bytes.put_u8(SpecialCharacter::Escape.into());
+        }
+        self.bytes.put_u8(byte);
+    }
+
+    fn put_u32_le(&mut self, value: u32) {
+        self.put_slice(&value.to_le_bytes());
+    }
+
+    fn put_slice(&mut self, slice: &[u8]) {
+        slice.iter().for_each(|byte| self.put_u8(*byte));
+    }
+
+    // the length of an attribute is stored in two bytes, longer data can't be written
+    fn put_attribute(
+        &mut self,
+        attribute: u8,
+        data: impl FnOnce(&mut BytesMut),
+    ) -> Result<(), Error> {
+        let mut bytes = BytesMut::new();
+        data(&mut bytes);
+        let length = u16::try_from(bytes.len()).map_err(|_| Error::Malformed)?;
+        self.put_u8(attribute);
+        self.put_slice(&length.to_le_bytes());
+        self.put_slice(&bytes);
+        Ok(())
+    }
+
+    fn into_bytes(self) -> Bytes {
+        self.bytes.freeze()
+    }
+}
+
+impl From<&Item> for model::StackOrder {
+    fn from(item: &Item) -> Self {
+        match (item.group, item.stack_order) {
+            (ItemGroup::Ground, _) => model::StackOrder::Ground,
+            (_, Some(StackOrder::Border)) => model::StackOrder::Border,
+            (_, Some(StackOrder::Bottom)) => model::StackOrder::Bottom,
+            (_, Some(StackOrder::Top)) => model::StackOrder::Top,
+            _ => model::StackOrder::Common,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    const ITEMS_OTB: &[u8] = include_bytes!("../../tests/fixtures/items.otb");
+    // items.otb with the attribute 0x30 after the minimap color of the item 100
+    const UNKNOWN_ATTRIBUTE_OTB: &[u8] =
+        include_bytes!("../../tests/fixtures/items-unknown-attribute.otb");
+
+    #[test]
+    fn encode_round_trip() {
+        let document: Document = Cursor::new(ITEMS_OTB).parse().unwrap();
+        let mut bytes = Vec::new();
+        (&mut bytes).encode(&document).unwrap();
+        assert_eq!(bytes, ITEMS_OTB);
+    }
+
+    #[test]
+    fn unknown_flags_are_kept() {
+        let mut document: Document = Cursor::new(ITEMS_OTB).parse().unwrap();
+        let item = document.item_mut(100).unwrap();
+        assert_eq!(item.flags, vec![ItemFlag::FullGround]);
+        item.set_flag(ItemFlag::FullGround, false);
+        item.set_flag(ItemFlag::Movable, true);
+
+        let mut bytes = Vec::new();
+        (&mut bytes).encode(&document).unwrap();
+        let document: Document = Cursor::new(bytes).parse().unwrap();
+        let item = document.item(100).unwrap();
+        assert_eq!(item.flags, vec![ItemFlag::Movable]);
+        assert_eq!(item.raw_flags, 1 << 30 | ItemFlag::Movable as u32);
+    }
+
+    #[test]
+    fn unknown_attributes_are_kept() {
+        let document: Document = Cursor::new(UNKNOWN_ATTRIBUTE_OTB).parse().unwrap();
+        let item = document.item(100).unwrap();
+        assert_eq!(item.minimap_color, Some(0xFE));
+        assert_eq!(item.attributes, vec![(0x30, vec![0x01, 0xFE, 0x03])]);
+        assert_eq!(
+            document.item(0xFE).unwrap().name.as_deref(),
+            Some("gold coin")
+        );
+
+        let mut bytes = Vec::new();
+        (&mut bytes).encode(&document).unwrap();
+        assert_eq!(bytes, UNKNOWN_ATTRIBUTE_OTB);
+    }
+
+    #[test]
+    fn attributes_longer_than_their_length_are_rejected() {
+        let mut document: Document = Cursor::new(ITEMS_OTB).parse().unwrap();
+        document.item_mut(100).unwrap().name = Some("a".repeat(u16::MAX as usize + 1));
+        assert!((&mut Vec::new()).encode(&document).is_err());
+    }
+
+    #[test]
+    fn taken_server_id_is_not_added() {
+        let mut document: Document = Cursor::new(ITEMS_OTB).parse().unwrap();
+        let item = Item::new(100, 0x200, ItemGroup::None);
+        assert!(document.add_item(item).is_none());
+        assert_eq!(document.item(100).unwrap().client_id, 0x66);
+        let item = Item::new(document.next_server_id(), 0x200, ItemGroup::None);
+        assert_eq!(document.add_item(item).unwrap().server_id, 0x200);
+    }
+
+    #[test]
+    fn last_item_is_read() {
+        let document: Document = Cursor::new(ITEMS_OTB).parse().unwrap();
+        assert_eq!(document.items.len(), 3);
+        let item = document.item(0x1FF).unwrap();
+        assert_eq!(item.client_id, 0x2FE);
+        assert_eq!(item.stack_order, Some(StackOrder::Border));
+        assert_eq!(item.trade_as, Some(0xFEFF));
+    }
+
+    #[test]
+    fn name_is_the_whole_attribute() {
+        let document: Document = Cursor::new(ITEMS_OTB).parse().unwrap();
+        assert_eq!(document.description, "OTB 3.62.78-11.1");
+        let item = document.item(0xFE).unwrap();
+        assert_eq!(item.name.as_deref(), Some("gold coin"));
+        assert_eq!(
+            item.light,
+            Some(Light {
+                level: 0xFF,
+                color: 0xD7
+            })
+        );
+        assert_eq!(
+            item.sprite_hash.as_deref(),
+            Some((0xF0..=0xFF).collect::<Vec<u8>>().as_slice())
+        );
+    }
+
+    #[test]
+    fn server_id_is_rekeyed() {
+        let mut document: Document = Cursor::new(ITEMS_OTB).parse().unwrap();
+        assert!(document.set_server_id(100, 0xFE).is_none());
+        assert_eq!(document.set_server_id(100, 101).unwrap().server_id, 101);
+        assert!(document.item(100).is_none());
+        assert_eq!(document.item(101).unwrap().ground_speed, Some(150));
+        assert!(document.set_server_id(100, 102).is_none());
+    }
+}
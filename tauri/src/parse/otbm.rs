@@ -1,13 +1,47 @@
-use super::{dat, otb, Error, Parse};
+use super::{appearances, dat, otb, Error, Parse};
 use bytes::{Buf, Bytes};
+use model::{attributes, AttributesType, Entity};
 use num_enum::{IntoPrimitive, TryFromPrimitive};
 use serde::Serialize;
 use std::{collections::HashMap, fs::File, io::Read, path::PathBuf};
 
 pub struct Reader<'a> {
     pub path: &'a PathBuf,
-    pub dat: &'a dat::Document,
-    pub otb: &'a otb::Document,
+    pub items: &'a dyn ItemTypes,
+    pub otb: Option<&'a otb::Document>,
+}
+
+pub trait ItemTypes: Sync {
+    fn is_stackable(&self, client_id: u16) -> bool;
+    fn is_fluid(&self, client_id: u16) -> bool;
+}
+
+impl ItemTypes for dat::Document {
+    fn is_stackable(&self, client_id: u16) -> bool {
+        self.items
+            .get(&client_id)
+            .is_some_and(|item| item.stackable)
+    }
+
+    fn is_fluid(&self, client_id: u16) -> bool {
+        self.items
+            .get(&client_id)
+            .is_some_and(|item| item.splash || item.fluid_container)
+    }
+}
+
+impl ItemTypes for appearances::Document {
+    fn is_stackable(&self, client_id: u16) -> bool {
+        self.items
+            .get(&client_id)
+            .is_some_and(|item| item.stackable)
+    }
+
+    fn is_fluid(&self, client_id: u16) -> bool {
+        self.items
+            .get(&client_id)
+            .is_some_and(|item| item.splash || item.fluid_container)
+    }
 }
 
 impl<'a> Read for Reader<'a> {
@@ -29,12 +63,14 @@ impl<'a> Parse<Document> for Reader<'a> {
     fn parse(mut self) -> Result<Document, Error> {
         let mut buf = Vec::new();
         let read = self.read_to_end(&mut buf)?;
-        let mut bytes = OtbmParser::new(Bytes::from(buf), self.dat, self.otb);
+        let mut bytes = OtbmParser::new(Bytes::from(buf), self.items, self.otb);
 
         let mut otbm = Document {
             map: Map {
                 width: 0,
                 height: 0,
+                spawn_file: None,
+                house_file: None,
                 tiles: Vec::new(),
             },
         };
@@ -68,12 +104,10 @@ impl<'a> Parse<Document> for Reader<'a> {
                                 println!("ext_file {ext_file}");
                             }
                             AttributeCode::ExtSpawnFile => {
-                                let ext_spawn_file = bytes.get_string();
-                                println!("ext_spawn_file {ext_spawn_file}");
+                                otbm.map.spawn_file = Some(bytes.get_string());
                             }
                             AttributeCode::ExtHouseFile => {
-                                let ext_house_file = bytes.get_string();
-                                println!("ext_house_file {ext_house_file}");
+                                otbm.map.house_file = Some(bytes.get_string());
                             }
                             _ => (),
                         }
@@ -105,8 +139,14 @@ impl<'a> Parse<Document> for Reader<'a> {
                                                 z: offset_z,
                                             };
 
+                                            // the house id comes right after the tile coordinates
+                                            let house_id = (tile_type == NodeType::HouseTile)
+                                                .then(|| bytes.get_u32_le());
+
                                             let mut tile = Tile {
                                                 position,
+                                                flags: 0,
+                                                house_id,
                                                 things: Vec::new(),
                                             };
 
@@ -126,7 +166,7 @@ impl<'a> Parse<Document> for Reader<'a> {
                                                 {
                                                     match attribute {
                                                         AttributeCode::TileFlags => {
-                                                            let tile_flags = bytes.get_u32_le();
+                                                            tile.flags = bytes.get_u32_le();
                                                         }
                                                         AttributeCode::Item => {
                                                             let ground_id = bytes.get_item_id();
@@ -141,10 +181,6 @@ impl<'a> Parse<Document> for Reader<'a> {
                                                     }
                                                 }
 
-                                                if tile_type == NodeType::HouseTile {
-                                                    let house_id = bytes.get_u32_le();
-                                                }
-
                                                 if let Some(thing) = bytes.get_thing(byte3, version)
                                                 {
                                                     tile.things.push(thing);
@@ -186,16 +222,16 @@ impl<'a> Parse<Document> for Reader<'a> {
 struct OtbmParser<'a> {
     bytes: Bytes,
     pos: u32,
-    dat: &'a dat::Document,
-    otb: &'a otb::Document,
+    items: &'a dyn ItemTypes,
+    otb: Option<&'a otb::Document>,
 }
 
 impl<'a> OtbmParser<'a> {
-    fn new(bytes: Bytes, dat: &'a dat::Document, otb: &'a otb::Document) -> Self {
+    fn new(bytes: Bytes, items: &'a dyn ItemTypes, otb: Option<&'a otb::Document>) -> Self {
         Self {
             bytes,
             pos: 0,
-            dat,
+            items,
             otb,
         }
     }
@@ -262,15 +298,11 @@ impl<'a> OtbmParser<'a> {
                 }
                 AttributeCode::Count => {
                     let value = self.get_u8();
-                    let dat_item = self.dat.items.get(&item_id);
-                    if let Some(dat_item) = dat_item {
-                        if dat_item.stackable {
-                            Some(Attribute::Count(value))
-                        } else if dat_item.splash || dat_item.fluid_container {
-                            Some(Attribute::Fluid(value))
-                        } else {
-                            None
-                        }
+                    let client_id = self.client_id(item_id);
+                    if self.items.is_stackable(client_id) {
+                        Some(Attribute::Count(value))
+                    } else if self.items.is_fluid(client_id) {
+                        Some(Attribute::Fluid(value))
                     } else {
                         None
                     }
@@ -338,14 +370,12 @@ impl<'a> OtbmParser<'a> {
                 } else if byte2 == u8::from(AttributeCode::Item) {
                     item_id = self.get_item_id();
                     if version == 1 {
-                        let dat_item = self.dat.items.get(&item_id);
-                        if let Some(dat_item) = dat_item {
-                            if dat_item.stackable {
-                                count = Some(self.get_u8());
-                            }
-                            if dat_item.splash || dat_item.fluid_container {
-                                fluid = Some(self.get_u8());
-                            }
+                        let client_id = self.client_id(item_id);
+                        if self.items.is_stackable(client_id) {
+                            count = Some(self.get_u8());
+                        }
+                        if self.items.is_fluid(client_id) {
+                            fluid = Some(self.get_u8());
                         }
                     }
                 } else {
@@ -376,9 +406,18 @@ impl<'a> OtbmParser<'a> {
     }
 
     fn get_item_id(&mut self) -> u16 {
-        let server_id = self.get_u16_le();
-        let otb_item = self.otb.items.get(&server_id).unwrap();
-        otb_item.client_id
+        self.get_u16_le()
+    }
+
+    fn client_id(&self, server_id: u16) -> u16 {
+        // maps made for clients without items.otb use client ids directly
+        match self.otb {
+            Some(otb) => otb
+                .items
+                .get(&server_id)
+                .map_or(server_id, |otb_item| otb_item.client_id),
+            None => server_id,
+        }
     }
 }
 
@@ -453,6 +492,43 @@ pub struct Thing {
     pub attributes: Vec<Attribute>,
 }
 
+impl From<Thing> for Entity {
+    fn from(Thing { attributes }: Thing) -> Self {
+        let mut entity = Entity::new();
+        for attribute in attributes.into_iter() {
+            match attribute {
+                Attribute::Item(id) => {
+                    entity.attributes.insert(
+                        "item".to_string(),
+                        model::Attribute::Item(attributes::Item(id)),
+                    );
+                }
+                Attribute::Container(things) => {
+                    entity.attributes.insert(
+                        "container".to_string(),
+                        model::Attribute::Container(attributes::Container(
+                            things.into_iter().map(Entity::from).collect(),
+                        )),
+                    );
+                }
+                Attribute::Count(count) => {
+                    entity.attributes.insert(
+                        "count".to_string(),
+                        model::Attribute::Count(attributes::Count(count)),
+                    );
+                }
+                Attribute::Fluid(fluid) => {
+                    entity.attributes.insert(
+                        "fluid".to_string(),
+                        model::Attribute::Fluid(attributes::Fluid(fluid)),
+                    );
+                }
+            }
+        }
+        entity
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Hash, Serialize)]
 pub struct Position {
     pub x: u16,
@@ -463,13 +539,49 @@ pub struct Position {
 #[derive(Debug, Serialize)]
 pub struct Tile {
     pub position: Position,
+    pub flags: u32,
+    pub house_id: Option<u32>,
     pub things: Vec<Thing>,
 }
 
+impl From<Tile> for (model::Position, model::Tile) {
+    fn from(
+        Tile {
+            position: Position { x, y, z },
+            flags,
+            house_id,
+            things,
+        }: Tile,
+    ) -> Self {
+        let mut tile_attributes = AttributesType::new();
+        if flags != 0 {
+            tile_attributes.insert(
+                "tile_flags".to_string(),
+                model::Attribute::TileFlags(attributes::TileFlags(flags)),
+            );
+        }
+        if let Some(house_id) = house_id {
+            tile_attributes.insert(
+                "house".to_string(),
+                model::Attribute::House(attributes::House(house_id)),
+            );
+        }
+        (
+            model::Position(x, y, z),
+            model::Tile {
+                attributes: tile_attributes,
+                entities: things.into_iter().map(Entity::from).collect(),
+            },
+        )
+    }
+}
+
 #[derive(Serialize)]
 pub struct Map {
     pub width: u32,
     pub height: u32,
+    pub spawn_file: Option<String>,
+    pub house_file: Option<String>,
     pub tiles: Vec<Tile>,
 }
 
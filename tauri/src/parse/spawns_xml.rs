@@ -0,0 +1,154 @@
+use super::{Error, Parse};
+use quick_xml::{
+    events::{BytesStart, Event},
+    Reader,
+};
+use std::{collections::HashMap, io::Read};
+
+pub struct Document {
+    pub spawns: Vec<Spawn>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Spawn {
+    pub x: u16,
+    pub y: u16,
+    pub z: u8,
+    pub radius: u16,
+    pub creatures: Vec<Creature>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Creature {
+    pub name: String,
+    pub npc: bool,
+    // offsets are relative to the spawn center
+    pub x: i32,
+    pub y: i32,
+    // the floor of the spawn when it's missing
+    pub z: Option<u8>,
+    pub direction: u8,
+    pub spawn_time: Option<u32>,
+}
+
+impl<T: Read + Sized> Parse<Document> for T {
+    fn parse(mut self) -> Result<Document, Error> {
+        let mut xml = String::new();
+        self.read_to_string(&mut xml)?;
+        let mut reader = Reader::from_str(&xml);
+
+        let mut spawns = Vec::new();
+        let mut current: Option<Spawn> = None;
+
+        loop {
+            match reader.read_event().map_err(|_| Error::Malformed)? {
+                Event::Start(element) if element.name().as_ref() == b"spawn" => {
+                    current = Some(Document::parse_spawn(&element)?);
+                }
+                Event::Empty(element) if element.name().as_ref() == b"spawn" => {
+                    spawns.push(Document::parse_spawn(&element)?);
+                }
+                Event::Start(element) | Event::Empty(element)
+                    if matches!(element.name().as_ref(), b"monster" | b"npc") =>
+                {
+                    if let Some(spawn) = current.as_mut() {
+                        spawn.creatures.push(Document::parse_creature(&element)?);
+                    }
+                }
+                Event::End(element) if element.name().as_ref() == b"spawn" => {
+                    if let Some(spawn) = current.take() {
+                        spawns.push(spawn);
+                    }
+                }
+                Event::Eof => break,
+                _ => (),
+            }
+        }
+
+        Ok(Document { spawns })
+    }
+}
+
+impl Document {
+    fn attributes(element: &BytesStart) -> Result<HashMap<String, String>, Error> {
+        element
+            .attributes()
+            .map(|attribute| {
+                let attribute = attribute.map_err(|_| Error::Malformed)?;
+                let key = String::from_utf8_lossy(attribute.key.as_ref()).to_lowercase();
+                let value = attribute
+                    .unescape_value()
+                    .map_err(|_| Error::Malformed)?
+                    .to_string();
+                Ok((key, value))
+            })
+            .collect()
+    }
+
+    fn parse_spawn(element: &BytesStart) -> Result<Spawn, Error> {
+        let attributes = Document::attributes(element)?;
+        let value = |key: &str| {
+            attributes
+                .get(key)
+                .and_then(|value| value.parse().ok())
+                .ok_or(Error::Malformed)
+        };
+
+        Ok(Spawn {
+            x: value("centerx")?,
+            y: value("centery")?,
+            z: value("centerz")? as u8,
+            radius: value("radius")?,
+            creatures: Vec::new(),
+        })
+    }
+
+    fn parse_creature(element: &BytesStart) -> Result<Creature, Error> {
+        let attributes = Document::attributes(element)?;
+
+        Ok(Creature {
+            name: attributes.get("name").cloned().ok_or(Error::Malformed)?,
+            npc: element.name().as_ref() == b"npc",
+            x: attributes
+                .get("x")
+                .and_then(|x| x.parse().ok())
+                .unwrap_or(0),
+            y: attributes
+                .get("y")
+                .and_then(|y| y.parse().ok())
+                .unwrap_or(0),
+            z: attributes.get("z").and_then(|z| z.parse().ok()),
+            direction: attributes
+                .get("direction")
+                .and_then(|direction| direction.parse().ok())
+                .unwrap_or(model::Direction::South as u8),
+            spawn_time: attributes
+                .get("spawntime")
+                .and_then(|spawn_time| spawn_time.parse().ok()),
+        })
+    }
+}
+
+impl Spawn {
+    // creature names are matched case-insensitively against the looks of monsters and npcs
+    pub fn to_model(&self, looks: &HashMap<String, model::Look>) -> model::Spawn {
+        model::Spawn {
+            position: model::Position(self.x, self.y, self.z),
+            radius: self.radius,
+            creatures: self
+                .creatures
+                .iter()
+                .map(|creature| model::Creature {
+                    name: creature.name.clone(),
+                    position: model::Position(
+                        (self.x as i32 + creature.x).clamp(0, u16::MAX as i32) as u16,
+                        (self.y as i32 + creature.y).clamp(0, u16::MAX as i32) as u16,
+                        creature.z.unwrap_or(self.z),
+                    ),
+                    direction: model::Direction::from(creature.direction),
+                    look: looks.get(&creature.name.to_lowercase()).copied(),
+                })
+                .collect(),
+        }
+    }
+}
@@ -1,9 +1,10 @@
 use super::Parse;
 use bytes::{Buf, BufMut, Bytes, BytesMut};
-use rayon::iter::IntoParallelRefIterator;
-use rayon::iter::ParallelIterator;
+use lru::LruCache;
 use std::collections::HashMap;
 use std::io::Read;
+use std::num::NonZeroUsize;
+use std::sync::{Arc, Mutex, PoisonError};
 
 #[derive(Debug)]
 pub enum Error {
@@ -14,6 +15,8 @@ pub const SPRITE_SIZE: usize = 32;
 const SPRITE_PIXELS: usize = SPRITE_SIZE * SPRITE_SIZE;
 const BYTES_PER_PIXEL: usize = 4;
 const SPRITE_BYTES: usize = SPRITE_PIXELS * BYTES_PER_PIXEL;
+// 16 MiB of decoded sprites
+const CACHED_SPRITES: usize = 4096;
 
 #[derive(Debug, Clone)]
 pub struct SpriteBytes([u8; SPRITE_BYTES]);
@@ -35,60 +38,90 @@ impl From<Error> for super::Error {
     }
 }
 
+impl AsRef<[u8]> for SpriteBytes {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
 impl From<SpriteBytes> for Vec<u8> {
     fn from(value: SpriteBytes) -> Self {
         value.0.to_vec()
     }
 }
 
+// sprites are decoded the first time they're read, the most recently read ones are kept
 pub struct Document {
     pub signature: u32,
     pub count: u16,
-    pub sprites: HashMap<u16, SpriteBytes>,
+    bytes: Bytes,
+    offsets: HashMap<u16, u32>,
+    cache: Mutex<LruCache<u16, Arc<SpriteBytes>>>,
 }
 
-// FIXME: this parser can panic because of bytes::Bytes methods
-// to avoid that we can use byteorder crate instead
-// or add some checks here before we try to read bytes
-
 impl<T: Read + Sized> Parse<Document> for T {
     fn parse(mut self) -> Result<Document, super::Error> {
         let mut buf = Vec::new();
         self.read_to_end(&mut buf)?;
-        let mut bytes = Bytes::from(buf);
-        let mut bytes_clone = bytes.clone();
+        let bytes = Bytes::from(buf);
+        let mut header = bytes.clone();
 
-        let signature = bytes.get_u32_le();
-        let count = bytes.get_u16_le();
+        if header.remaining() < 6 {
+            return Err(Error::Malformed.into());
+        }
+        let signature = header.get_u32_le();
+        let count = header.get_u16_le();
+        if header.remaining() < count as usize * 4 {
+            return Err(Error::Malformed.into());
+        }
 
         let offsets = (0..count)
-            .map(|index| (bytes.get_u32_le(), index + 1))
-            .collect::<Vec<(u32, u16)>>();
-
-        let sprites = offsets
-            .par_iter()
-            .filter(|(offset, _)| *offset != 0)
-            .map(|(offset, id)| {
-                Document::parse_sprite(bytes_clone.clone(), *offset).map(|sprite| (*id, sprite))
-            })
-            .collect::<Result<HashMap<u16, SpriteBytes>, Error>>()?;
+            .map(|index| (index + 1, header.get_u32_le()))
+            .filter(|(_, offset)| *offset != 0)
+            .collect::<HashMap<u16, u32>>();
+        if offsets
+            .values()
+            .any(|offset| *offset as usize >= bytes.len())
+        {
+            return Err(Error::Malformed.into());
+        }
 
         Ok(Document {
             signature,
             count,
-            sprites,
+            bytes,
+            offsets,
+            cache: Mutex::new(LruCache::new(NonZeroUsize::new(CACHED_SPRITES).unwrap())),
         })
     }
 }
 
 impl Document {
-    fn parse_sprite(mut bytes: Bytes, offset: u32) -> Result<SpriteBytes, Error> {
-        // FIXME: can this panic when we try to parse a malformed spr?
-        bytes.advance(offset.try_into().unwrap());
+    // sprites that are missing or can't be decoded are None
+    pub fn sprite(&self, id: u16) -> Option<Arc<SpriteBytes>> {
+        let cached = self
+            .cache
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(&id)
+            .cloned();
+        if cached.is_some() {
+            return cached;
+        }
 
-        bytes.get_u8();
-        bytes.get_u8();
-        bytes.get_u8();
+        let offset = self.offsets.get(&id)?;
+        let sprite = Arc::new(Self::parse_sprite(self.bytes.clone(), *offset).ok()?);
+        self.cache
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .put(id, sprite.clone());
+        Some(sprite)
+    }
+
+    fn parse_sprite(mut bytes: Bytes, offset: u32) -> Result<SpriteBytes, Error> {
+        // the transparent color and the size of the pixels come first
+        Self::ensure(&bytes, offset as usize + 5)?;
+        bytes.advance(offset as usize + 3);
 
         let colored_bytes_count = bytes.get_u16_le() as u32;
         let mut bytes_put = 0;
@@ -96,6 +129,7 @@ impl Document {
         let mut pixels = BytesMut::new();
 
         while colored_bytes_put < colored_bytes_count && bytes_put < SPRITE_BYTES {
+            Self::ensure(&bytes, 2)?;
             let transparent_pixels = bytes.get_u16_le();
             for _ in 0..transparent_pixels {
                 if bytes_put >= SPRITE_BYTES {
@@ -104,11 +138,13 @@ impl Document {
                 pixels.put_u32(0);
                 bytes_put += 4;
             }
+            Self::ensure(&bytes, 2)?;
             let colored_pixels = bytes.get_u16_le() as u32;
             for _ in 0..colored_pixels {
                 if bytes_put >= SPRITE_BYTES {
                     break;
                 }
+                Self::ensure(&bytes, 3)?;
                 pixels.put_u8(bytes.get_u8());
                 pixels.put_u8(bytes.get_u8());
                 pixels.put_u8(bytes.get_u8());
@@ -126,4 +162,54 @@ impl Document {
 
         pixels.to_vec().try_into()
     }
+
+    fn ensure(bytes: &Bytes, count: usize) -> Result<(), Error> {
+        if bytes.remaining() < count {
+            return Err(Error::Malformed);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    // one sprite with a transparent pixel and a red one
+    fn spr(sprite: &[u8]) -> Vec<u8> {
+        let mut bytes = vec![0x4F, 0x57, 0x00, 0x00, 0x01, 0x00, 0x0A, 0x00, 0x00, 0x00];
+        bytes.extend_from_slice(sprite);
+        bytes
+    }
+
+    const SPRITE: &[u8] = &[
+        0xFF, 0x00, 0xFF, 0x07, 0x00, 0x01, 0x00, 0x01, 0x00, 0xFF, 0x00, 0x00,
+    ];
+
+    #[test]
+    fn sprite_is_decoded() {
+        let document: Document = Cursor::new(spr(SPRITE)).parse().unwrap();
+        let sprite = document.sprite(1).unwrap();
+        let pixels: &[u8] = (*sprite).as_ref();
+        assert_eq!(pixels[..8], [0, 0, 0, 0, 0xFF, 0, 0, 0xFF]);
+        assert!(pixels[8..].iter().all(|byte| *byte == 0));
+    }
+
+    #[test]
+    fn truncated_sprite_is_none() {
+        for length in 1..SPRITE.len() {
+            let document: Document = Cursor::new(spr(&SPRITE[..length])).parse().unwrap();
+            assert!(document.sprite(1).is_none());
+        }
+    }
+
+    #[test]
+    fn truncated_header_is_malformed() {
+        let bytes = spr(SPRITE);
+        for length in [0, 5, 9] {
+            let document: Result<Document, _> = Cursor::new(&bytes[..length]).parse();
+            assert!(document.is_err());
+        }
+    }
 }
@@ -0,0 +1,93 @@
+use super::{Error, Parse};
+use image::{imageops::crop_imm, ImageFormat, RgbaImage};
+use lzma_rs::decompress::{Options, Stream, UnpackedSize};
+use std::io::{Read, Write};
+
+pub const SHEET_SIZE: u32 = 384;
+
+pub struct Document {
+    pub image: RgbaImage,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum SpriteType {
+    Small,
+    Tall,
+    Wide,
+    Large,
+}
+
+impl TryFrom<u8> for SpriteType {
+    type Error = Error;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(SpriteType::Small),
+            1 => Ok(SpriteType::Tall),
+            2 => Ok(SpriteType::Wide),
+            3 => Ok(SpriteType::Large),
+            _ => Err(Error::Malformed),
+        }
+    }
+}
+
+impl SpriteType {
+    pub fn size(&self) -> (u32, u32) {
+        match self {
+            SpriteType::Small => (32, 32),
+            SpriteType::Tall => (32, 64),
+            SpriteType::Wide => (64, 32),
+            SpriteType::Large => (64, 64),
+        }
+    }
+}
+
+// sheets are LZMA-compressed BMPs with a CIP header in front:
+// a few 0x00 padding bytes, a 5 byte constant starting with 0x70 and the compressed size as a 7-bit encoded integer
+impl<T: Read + Sized> Parse<Document> for T {
+    fn parse(mut self) -> Result<Document, Error> {
+        let mut buf = Vec::new();
+        self.read_to_end(&mut buf)?;
+
+        let mut position = buf
+            .iter()
+            .position(|byte| *byte != 0x00)
+            .ok_or(Error::Malformed)?
+            + 5;
+        while buf.get(position).ok_or(Error::Malformed)? & 0x80 != 0 {
+            position += 1;
+        }
+        let lzma = buf.get(position + 1..).ok_or(Error::Malformed)?;
+
+        let mut stream = Stream::new_with_options(
+            &Options {
+                // CIP stores the compressed size where LZMA expects the unpacked one
+                unpacked_size: UnpackedSize::ReadHeaderButUseProvided(None),
+                memlimit: None,
+                allow_incomplete: true,
+            },
+            Vec::new(),
+        );
+        stream.write_all(lzma)?;
+        let bmp = stream.finish().map_err(|_| Error::Malformed)?;
+
+        let image = image::load_from_memory_with_format(&bmp, ImageFormat::Bmp)
+            .map_err(|_| Error::Malformed)?
+            .into_rgba8();
+
+        Ok(Document { image })
+    }
+}
+
+impl Document {
+    pub fn sprite(&self, sprite_type: SpriteType, index: u32) -> Option<RgbaImage> {
+        let (width, height) = sprite_type.size();
+        let columns = SHEET_SIZE / width;
+        let x = (index % columns) * width;
+        let y = (index / columns) * height;
+        if y + height > self.image.height() {
+            return None;
+        }
+        Some(crop_imm(&self.image, x, y, width, height).to_image())
+    }
+}
@@ -1,4 +1,5 @@
 use crate::{
+    canary::CanaryProject,
     load::{Error, Load},
     skyless::SkylessProject,
     tfs::TfsProject,
@@ -10,6 +11,7 @@ use std::sync::Arc;
 
 #[derive(Debug, Deserialize, Serialize)]
 pub enum Project {
+    CanaryProject(CanaryProject),
     SkylessProject(SkylessProject),
     TfsProject(TfsProject),
 }
@@ -22,6 +24,7 @@ impl Load for Project {
         transport: Arc<impl Transport + Send + Sync + 'static>,
     ) -> Result<(), Error> {
         match self {
+            Project::CanaryProject(project) => project.load(transport),
             Project::SkylessProject(project) => project.load(transport),
             Project::TfsProject(project) => project.load(transport),
         }
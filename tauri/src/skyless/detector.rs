@@ -3,7 +3,8 @@ use crate::detect::Detect;
 use std::path::Path;
 
 impl Detect for SkylessProject {
-    fn detect(directory: &Path) -> Option<Self> {
-        todo!()
+    // skyless projects can't be loaded yet, their directories are never picked up
+    fn detect(_directory: &Path) -> Option<Self> {
+        None
     }
 }
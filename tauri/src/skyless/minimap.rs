@@ -0,0 +1,10 @@
+use super::SkylessProject;
+use crate::{load::Error, minimap::Minimap};
+use model::minimap::MinimapFloor;
+use std::collections::BTreeMap;
+
+impl Minimap for SkylessProject {
+    fn minimap(&self) -> Result<BTreeMap<u8, MinimapFloor>, Error> {
+        Err(Error::Unsupported)
+    }
+}
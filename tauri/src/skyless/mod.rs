@@ -17,6 +17,8 @@ impl From<SkylessProject> for Project {
 
 mod detector;
 mod loader;
+mod minimap;
+mod sprites;
 
 pub use detector::*;
 pub use loader::*;
@@ -0,0 +1,12 @@
+use super::SkylessProject;
+use crate::{
+    load::Error,
+    sprites::{SpriteSource, Sprites},
+};
+use std::sync::Arc;
+
+impl Sprites for SkylessProject {
+    fn sprite_source(&self) -> Result<Arc<dyn SpriteSource>, Error> {
+        Err(Error::Unsupported)
+    }
+}
@@ -0,0 +1,426 @@
+use crate::{
+    load::Error,
+    transport::{self, Transport, SCRATCH_SPACE},
+};
+use md5::{Digest, Md5};
+use memmap2::Mmap;
+use model::{
+    ArchivedCreature, ArchivedEntity, ArchivedItem, ArchivedOutfit, ArchivedPosition,
+    ArchivedSpawn, ArchivedSpriteLayout, ArchivedTile, Item, Outfit, Position, Spawn, Tile,
+};
+use rkyv::{
+    check_archived_root,
+    ser::{
+        serializers::{
+            AllocScratch, CompositeSerializer, FallbackScratch, HeapScratch, SharedSerializeMap,
+            WriteSerializer,
+        },
+        Serializer,
+    },
+    Archive, Deserialize, Serialize,
+};
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    mem::{self, align_of, size_of},
+    ops::Range,
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+use transport::{ArchivedMessage, Message};
+
+pub const EXTENSION: &str = "snapshot";
+
+// bumped whenever the archived model changes in a way the sizes in layout() don't show,
+// older snapshots are then made again
+const VERSION: u32 = 3;
+
+// the same batches the loaders send
+const ITEMS_BATCH: usize = 100;
+const TILES_BATCH: usize = 10_000;
+
+// archived messages are read where they are, each of them starts aligned
+const ALIGNMENT: usize = 16;
+
+// a file the snapshot was made from, the hash is only compared when the modification time changed
+#[derive(Debug, Clone, Archive, Deserialize, Serialize)]
+#[archive(check_bytes)]
+pub struct Source {
+    pub path: String,
+    pub modified: u64,
+    pub hash: Vec<u8>,
+}
+
+// everything a project sends to the editor, in the order it's sent, sprites are sent when the
+// editor asks for them
+#[derive(Debug, Default)]
+pub struct Snapshot {
+    pub sources: Vec<Source>,
+    pub outfits: Vec<Outfit>,
+    pub spawns: Vec<Spawn>,
+    pub items: Vec<Item>,
+    pub tiles: Vec<(Position, Tile)>,
+}
+
+// written after the messages, the last 8 bytes of the file are where it starts
+#[derive(Debug, Archive, Deserialize, Serialize)]
+#[archive(check_bytes)]
+struct Index {
+    layout: Vec<u32>,
+    sources: Vec<Source>,
+    // start and length of every message, in the order they're sent
+    messages: Vec<(u64, u64)>,
+}
+
+// every message is archived on its own, so it's sent as it's stored
+struct Writer<W: Write> {
+    writer: W,
+    position: usize,
+    messages: Vec<(u64, u64)>,
+}
+
+// a validated snapshot memory-mapped from the cache
+pub struct Archived {
+    mmap: Mmap,
+    messages: Vec<Range<usize>>,
+}
+
+impl Source {
+    pub fn new(path: &Path) -> io::Result<Self> {
+        Ok(Source {
+            path: path.to_string_lossy().into_owned(),
+            modified: modified(path)?,
+            hash: hash(path)?,
+        })
+    }
+}
+
+impl ArchivedSource {
+    // a file that was only touched keeps the snapshot
+    fn is_unchanged(&self) -> bool {
+        let path = Path::new(self.path.as_str());
+        modified(path).is_ok_and(|modified| modified == self.modified)
+            || hash(path).is_ok_and(|hash| hash == self.hash.as_slice())
+    }
+}
+
+impl Snapshot {
+    pub fn new(sources: &[PathBuf]) -> io::Result<Self> {
+        Ok(Snapshot {
+            sources: sources
+                .iter()
+                .map(|path| Source::new(path))
+                .collect::<io::Result<_>>()?,
+            ..Default::default()
+        })
+    }
+
+    // one batch is archived at a time, written next to the final path first so a crash never
+    // leaves half a snapshot behind
+    pub fn write(&self, path: &Path) -> Result<(), Error> {
+        let temporary_path = path.with_extension(format!("{EXTENSION}.tmp"));
+        let mut writer = Writer::new(BufWriter::new(File::create(&temporary_path)?));
+        for message in self.messages() {
+            writer.message(&message)?;
+        }
+        writer.finish(&self.sources)?;
+        fs::rename(&temporary_path, path)?;
+        Ok(())
+    }
+
+    // when the snapshot can't be written
+    pub async fn send(
+        &self,
+        transport: &(impl Transport + Send + Sync),
+    ) -> Result<(), transport::Error> {
+        for message in self.messages() {
+            transport.transport(message).await?;
+        }
+        Ok(())
+    }
+
+    // batches are copied one at a time when they're archived or sent
+    fn messages(&self) -> impl Iterator<Item = Message> + '_ {
+        let spawns = (!self.spawns.is_empty()).then(|| Message::Spawns(self.spawns.clone()));
+        [Message::Outfits(self.outfits.clone())]
+            .into_iter()
+            .chain(spawns)
+            .chain([Message::ItemsCount(self.items.len())])
+            .chain(
+                self.items
+                    .chunks(ITEMS_BATCH)
+                    .map(|items| Message::Items(items.to_vec())),
+            )
+            .chain([Message::MapTilesCount(self.tiles.len())])
+            .chain(
+                self.tiles
+                    .chunks(TILES_BATCH)
+                    .map(|tiles| Message::MapTiles(tiles.to_vec())),
+            )
+    }
+}
+
+impl<W: Write> Writer<W> {
+    fn new(writer: W) -> Self {
+        Writer {
+            writer,
+            position: 0,
+            messages: Vec::new(),
+        }
+    }
+
+    fn message(&mut self, message: &Message) -> Result<(), Error> {
+        let length = self.archive(message)?;
+        self.messages.push((self.position as u64, length as u64));
+        self.position += length;
+        self.align()
+    }
+
+    fn finish(mut self, sources: &[Source]) -> Result<(), Error> {
+        let start = self.position;
+        let index = Index {
+            layout: layout(),
+            sources: sources.to_vec(),
+            messages: mem::take(&mut self.messages),
+        };
+        self.position += self.archive(&index)?;
+        self.writer.write_all(&(start as u64).to_le_bytes())?;
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    fn archive<T>(&mut self, value: &T) -> Result<usize, Error>
+    where
+        T: for<'a> Serialize<
+            CompositeSerializer<
+                WriteSerializer<&'a mut W>,
+                FallbackScratch<HeapScratch<SCRATCH_SPACE>, AllocScratch>,
+                SharedSerializeMap,
+            >,
+        >,
+    {
+        let mut serializer = CompositeSerializer::new(
+            WriteSerializer::new(&mut self.writer),
+            FallbackScratch::default(),
+            SharedSerializeMap::new(),
+        );
+        serializer
+            .serialize_value(value)
+            .map_err(|_| Error::Something)?;
+        Ok(serializer.pos())
+    }
+
+    fn align(&mut self) -> Result<(), Error> {
+        let padding = self.position.next_multiple_of(ALIGNMENT) - self.position;
+        self.writer.write_all(&[0; ALIGNMENT][..padding])?;
+        self.position += padding;
+        Ok(())
+    }
+}
+
+impl Archived {
+    // none when there's no snapshot, it doesn't validate, it's from another layout or a source changed
+    pub fn open(path: &Path, sources: &[PathBuf]) -> Option<Self> {
+        let file = File::open(path).ok()?;
+        // the snapshot is only replaced by renaming a new file over it, never written in place
+        let mmap = unsafe { Mmap::map(&file) }.ok()?;
+
+        let end = mmap.len().checked_sub(size_of::<u64>())?;
+        let start = u64::from_le_bytes(mmap[end..].try_into().ok()?) as usize;
+        let index = check_archived_root::<Index>(mmap.get(start..end)?).ok()?;
+
+        let unchanged = index.layout.as_slice() == layout()
+            && index.sources.len() == sources.len()
+            && index.sources.iter().zip(sources).all(|(source, path)| {
+                source.path.as_str() == path.to_string_lossy() && source.is_unchanged()
+            });
+        if !unchanged {
+            return None;
+        }
+
+        // every message is checked once here, the editor checks them again when they arrive
+        let messages: Vec<_> = index
+            .messages
+            .iter()
+            .map(|(start, length)| *start as usize..(*start + *length) as usize)
+            .collect();
+        let valid = messages.iter().all(|range| {
+            mmap.get(range.clone())
+                .is_some_and(|bytes| check_archived_root::<Message>(bytes).is_ok())
+        });
+        valid.then_some(Archived { mmap, messages })
+    }
+
+    pub async fn send(
+        &self,
+        transport: &(impl Transport + Send + Sync),
+    ) -> Result<(), transport::Error> {
+        for range in &self.messages {
+            transport
+                .transport_archived(&self.mmap[range.clone()])
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+// sent from the snapshot next to the map, it's made again when it's missing or a source changed
+pub async fn load(
+    path: &Path,
+    sources: &[PathBuf],
+    snapshot: impl FnOnce(&[PathBuf]) -> Result<Snapshot, Error>,
+    transport: &(impl Transport + Send + Sync),
+) -> Result<(), Error> {
+    match Archived::open(path, sources) {
+        Some(archived) => archived.send(transport).await?,
+        None => {
+            let snapshot = snapshot(sources)?;
+            let written = snapshot.write(path).is_ok();
+            match written.then(|| Archived::open(path, sources)).flatten() {
+                Some(archived) => {
+                    drop(snapshot);
+                    archived.send(transport).await?
+                }
+                None => {
+                    println!("Couldn't write snapshot");
+                    snapshot.send(transport).await?
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+// sizes of the archived model, a snapshot made by a build where one of them differs is made again
+fn layout() -> Vec<u32> {
+    let sizes = [
+        (size_of::<ArchivedMessage>(), align_of::<ArchivedMessage>()),
+        (size_of::<ArchivedItem>(), align_of::<ArchivedItem>()),
+        (size_of::<ArchivedOutfit>(), align_of::<ArchivedOutfit>()),
+        (size_of::<ArchivedSpawn>(), align_of::<ArchivedSpawn>()),
+        (
+            size_of::<ArchivedCreature>(),
+            align_of::<ArchivedCreature>(),
+        ),
+        (size_of::<ArchivedTile>(), align_of::<ArchivedTile>()),
+        (size_of::<ArchivedEntity>(), align_of::<ArchivedEntity>()),
+        (
+            size_of::<ArchivedPosition>(),
+            align_of::<ArchivedPosition>(),
+        ),
+        (
+            size_of::<ArchivedSpriteLayout>(),
+            align_of::<ArchivedSpriteLayout>(),
+        ),
+    ];
+    [VERSION]
+        .into_iter()
+        .chain(
+            sizes
+                .into_iter()
+                .flat_map(|(size, align)| [size as u32, align as u32]),
+        )
+        .collect()
+}
+
+fn modified(path: &Path) -> io::Result<u64> {
+    let modified = fs::metadata(path)?.modified()?;
+    Ok(modified
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_nanos() as u64))
+}
+
+fn hash(path: &Path) -> io::Result<Vec<u8>> {
+    let mut hasher = Md5::new();
+    io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(hasher.finalize().to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use futures::executor::block_on;
+    use rkyv::AlignedVec;
+    use std::{env, sync::Mutex};
+
+    // keeps what would go over the websocket
+    #[derive(Default)]
+    struct Recorder(Mutex<Vec<Vec<u8>>>);
+
+    #[async_trait]
+    impl Transport for Recorder {
+        async fn transport(&self, data: Message) -> Result<(), transport::Error> {
+            self.transport_archived(&transport::serialize(&data, 0)?)
+                .await
+        }
+
+        async fn transport_archived(&self, bytes: &[u8]) -> Result<(), transport::Error> {
+            self.0.lock().unwrap().push(bytes.to_vec());
+            Ok(())
+        }
+    }
+
+    fn sent(recorder: Recorder) -> Vec<Message> {
+        recorder
+            .0
+            .into_inner()
+            .unwrap()
+            .iter()
+            .map(|bytes| {
+                let mut aligned = AlignedVec::new();
+                aligned.extend_from_slice(bytes);
+                rkyv::from_bytes::<Message>(&aligned).unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn messages_are_sent_as_they_are_stored() {
+        let directory = env::temp_dir().join(format!("snapshot-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let map_path = directory.join("map.otbm");
+        fs::write(&map_path, b"map").unwrap();
+        let path = directory.join("map.snapshot");
+        let sources = vec![map_path];
+
+        let mut snapshot = Snapshot::new(&sources).unwrap();
+        snapshot.tiles = (0..=TILES_BATCH as u16)
+            .map(|x| {
+                let position = Position {
+                    x,
+                    y: 0,
+                    z: 7,
+                    stack_pos: None,
+                };
+                (position, Tile::default())
+            })
+            .collect();
+        assert!(snapshot.write(&path).is_ok());
+
+        let recorder = Recorder::default();
+        let archived = Archived::open(&path, &sources).unwrap();
+        block_on(archived.send(&recorder)).unwrap();
+        let messages = sent(recorder);
+
+        let expected = Recorder::default();
+        block_on(snapshot.send(&expected)).unwrap();
+        assert_eq!(messages.len(), sent(expected).len());
+        assert!(matches!(
+            messages.as_slice(),
+            [
+                Message::Outfits(_),
+                Message::ItemsCount(0),
+                Message::MapTilesCount(count),
+                Message::MapTiles(first),
+                Message::MapTiles(last),
+            ] if *count == TILES_BATCH + 1 && first.len() == TILES_BATCH && last[0].0.x == TILES_BATCH as u16
+        ));
+
+        let bytes = fs::read(&path).unwrap();
+        fs::write(&path, &bytes[..bytes.len() / 2]).unwrap();
+        assert!(Archived::open(&path, &sources).is_none());
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
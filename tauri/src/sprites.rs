@@ -0,0 +1,50 @@
+use crate::{
+    load::Error,
+    project::Project,
+    transport::{self, Transport},
+};
+use model::Sprite;
+use rayon::prelude::*;
+use std::sync::Arc;
+use transport::Message;
+
+const SPRITES_BATCH: usize = 1_000;
+
+// sprites are read when the editor asks for them, most of a client's sprites are never drawn
+pub trait SpriteSource: Send + Sync {
+    // sprites that are missing or can't be read are None
+    fn sprite(&self, id: u32) -> Option<Sprite>;
+}
+
+pub trait Sprites {
+    fn sprite_source(&self) -> Result<Arc<dyn SpriteSource>, Error>;
+}
+
+// TODO: use some macro to auto-impl this
+impl Sprites for Project {
+    fn sprite_source(&self) -> Result<Arc<dyn SpriteSource>, Error> {
+        match self {
+            Project::CanaryProject(project) => project.sprite_source(),
+            Project::SkylessProject(project) => project.sprite_source(),
+            Project::TfsProject(project) => project.sprite_source(),
+        }
+    }
+}
+
+// the answer to a sprite request, it ends with Loaded even when no sprite could be read
+pub async fn send(
+    source: &dyn SpriteSource,
+    sprite_ids: &[u32],
+    transport: &(impl Transport + Send + Sync),
+) -> Result<(), transport::Error> {
+    for sprite_ids in sprite_ids.chunks(SPRITES_BATCH) {
+        let sprites: Vec<_> = sprite_ids
+            .par_iter()
+            .filter_map(|sprite_id| source.sprite(*sprite_id))
+            .collect();
+        if !sprites.is_empty() {
+            transport.transport(Message::Sprites(sprites)).await?;
+        }
+    }
+    transport.transport(Message::Loaded).await
+}
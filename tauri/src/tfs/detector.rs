@@ -1,9 +1,46 @@
 use super::TfsProject;
-use crate::detect::Detect;
-use std::path::Path;
+use crate::detect::{find_file, Detect, SEARCH_DEPTH};
+use std::{ffi::OsStr, path::Path};
 
 impl Detect for TfsProject {
+    // spawns and houses are next to the map, named after it the way map editors save them
     fn detect(directory: &Path) -> Option<Self> {
-        todo!()
+        let spr_path = find_file(
+            directory,
+            &|path| path.extension() == Some(OsStr::new("spr")),
+            SEARCH_DEPTH,
+        )?;
+        let dat_path = Some(spr_path.with_extension("dat"))
+            .filter(|path| path.is_file())
+            .or_else(|| {
+                find_file(
+                    directory,
+                    &|path| path.extension() == Some(OsStr::new("dat")),
+                    SEARCH_DEPTH,
+                )
+            })?;
+        let otb_path = find_file(
+            directory,
+            &|path| path.file_name() == Some(OsStr::new("items.otb")),
+            SEARCH_DEPTH,
+        )?;
+        let otbm_path = find_file(
+            directory,
+            &|path| path.extension() == Some(OsStr::new("otbm")),
+            SEARCH_DEPTH,
+        )?;
+        let items_xml_path =
+            Some(otb_path.with_file_name("items.xml")).filter(|path| path.is_file());
+        let map_name = otbm_path.file_stem()?.to_string_lossy().into_owned();
+
+        Some(TfsProject {
+            spr_path,
+            dat_path,
+            items_xml_path,
+            houses_path: otbm_path.with_file_name(format!("{map_name}-house.xml")),
+            spawns_path: otbm_path.with_file_name(format!("{map_name}-spawn.xml")),
+            otb_path,
+            otbm_path,
+        })
     }
 }
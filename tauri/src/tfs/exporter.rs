@@ -0,0 +1,93 @@
+use super::TfsProject;
+use crate::{
+    load::Error,
+    parse::{dat, otb, spr, Parse},
+};
+use model::Textures;
+use rayon::prelude::*;
+use std::{
+    fs::{self, File},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, PoisonError},
+    time::SystemTime,
+};
+
+pub struct Documents {
+    pub spr: spr::Document,
+    pub dat: dat::Document,
+    pub otb: otb::Document,
+}
+
+// the files are parsed again only when one of them changed, exports and sprite hash checks come
+// one after another
+#[derive(Default)]
+pub struct ExportCache(Mutex<Option<(Vec<(PathBuf, SystemTime)>, Arc<Documents>)>>);
+
+impl ExportCache {
+    pub fn documents(&self, project: &TfsProject) -> Result<Arc<Documents>, Error> {
+        let key = [&project.spr_path, &project.dat_path, &project.otb_path]
+            .into_iter()
+            .map(|path| Ok((path.clone(), Self::modified(path)?)))
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        let mut cached = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some((cached_key, documents)) = cached.as_ref() {
+            if *cached_key == key {
+                return Ok(documents.clone());
+            }
+        }
+
+        let documents = Arc::new(Documents {
+            spr: File::open(&project.spr_path)?.parse()?,
+            dat: File::open(&project.dat_path)?.parse()?,
+            otb: File::open(&project.otb_path)?.parse()?,
+        });
+        *cached = Some((key, documents.clone()));
+        Ok(documents)
+    }
+
+    fn modified(path: &Path) -> Result<SystemTime, Error> {
+        Ok(fs::metadata(path)?.modified()?)
+    }
+}
+
+impl TfsProject {
+    // composed the same way the editor composes them, server items sharing a sprite compose it again
+    // every item comes with its frame durations in milliseconds
+    pub fn item_textures(
+        &self,
+        cache: &ExportCache,
+        filter: impl Fn(&otb::Item) -> bool + Sync,
+    ) -> Result<Vec<(u16, Vec<u32>, Textures)>, Error> {
+        let documents = cache.documents(self)?;
+        let Documents { spr, dat, otb } = documents.as_ref();
+
+        let otb_items: Vec<_> = otb
+            .items
+            .values()
+            .filter(|otb_item| filter(otb_item))
+            .collect();
+        let sprite_ids = otb_items
+            .iter()
+            .filter_map(|otb_item| dat.items.get(&otb_item.client_id))
+            .flat_map(|item| {
+                item.textures
+                    .sprites
+                    .iter()
+                    .map(|sprite_id| *sprite_id as u32)
+            });
+        let sprites = Self::sprites(spr, sprite_ids);
+
+        let mut textures: Vec<_> = otb_items
+            .into_par_iter()
+            .filter_map(|otb_item| {
+                let item = dat.items.get(&otb_item.client_id)?;
+                let layout = Self::sprite_layout(&item.textures);
+                let textures = layout.compose(&sprites, false);
+                Some((otb_item.server_id, layout.frame_durations, textures))
+            })
+            .collect();
+        textures.sort_by_key(|(server_id, _, _)| *server_id);
+        Ok(textures)
+    }
+}
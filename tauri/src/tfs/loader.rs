@@ -1,30 +1,29 @@
 use super::TfsProject;
 use crate::{
     load::{self, Error, Load},
-    parse::{self, dat, otb, otbm, spr, Parse},
+    parse::{self, creature_xml, dat, items_xml, monsters_xml, otb, otbm, spawns_xml, spr, Parse},
+    snapshot::{self, Snapshot},
+    sprites::SpriteSource,
     transport::Transport,
 };
 use async_trait::async_trait;
-use base64::{engine::general_purpose, Engine};
 use futures::{
     future::{join_all, try_join_all},
     Future,
 };
-use image::{imageops::overlay, DynamicImage, GenericImage, ImageOutputFormat, RgbaImage};
 use itertools::Itertools;
 use model::{
-    attributes, Attribute, Entity, Item, Position, Texture, TextureFrame, TextureLayer,
-    TexturePatternX, TexturePatternY, TexturePatternZ, Textures, Tile,
+    attributes, minimap, Attribute, Item, Look, Outfit, Position, Sprite, SpriteLayout, Textures,
+    Tile,
 };
 use rayon::prelude::*;
 use std::{
-    collections::HashMap,
+    collections::{BTreeSet, HashMap},
     fs::File,
-    io::Cursor,
+    path::{Path, PathBuf},
     pin::{pin, Pin},
     sync::Arc,
 };
-use transport::Message;
 
 #[async_trait]
 impl Load for TfsProject {
@@ -32,202 +31,324 @@ impl Load for TfsProject {
         &self,
         transport: Arc<impl Transport + Send + Sync + 'static>,
     ) -> Result<(), Error> {
-        println!("Loading SPR");
-        let spr: spr::Document = File::open(&self.spr_path)?.parse()?;
+        snapshot::load(
+            &self.otbm_path.with_extension(snapshot::EXTENSION),
+            &self.sources(),
+            |sources| self.snapshot(sources),
+            transport.as_ref(),
+        )
+        .await
+    }
+}
+
+impl TfsProject {
+    // every file the snapshot is made from, in a stable order
+    pub fn sources(&self) -> Vec<PathBuf> {
+        let mut sources = vec![
+            self.spr_path.clone(),
+            self.dat_path.clone(),
+            self.otb_path.clone(),
+            self.otbm_path.clone(),
+        ];
+        sources.extend(self.items_xml_path.clone());
+        sources.push(self.spawns_path.clone());
+        if let Some(data_path) = self.data_path() {
+            sources.push(data_path.join("monster").join("monsters.xml"));
+        }
+        let mut creature_paths = self.creature_paths();
+        creature_paths.sort();
+        sources.extend(creature_paths);
+        sources.retain(|path| path.is_file());
+        sources
+    }
+
+    pub fn snapshot(&self, sources: &[PathBuf]) -> Result<Snapshot, Error> {
         println!("Loading DAT");
         let dat: dat::Document = File::open(&self.dat_path)?.parse()?;
         println!("Loading OTB");
         let otb: otb::Document = File::open(&self.otb_path)?.parse()?;
+        let items_xml = self.load_items_xml()?;
+        let server_items = Self::server_items(&otb, items_xml.as_ref());
+        let server_ids = otb.server_ids_by_client_id();
         println!("Loading OTBM");
         let otbm: otbm::Document = otbm::Reader {
             path: &self.otbm_path,
-            dat: &dat,
-            otb: &otb,
+            items: &dat,
+            otb: Some(&otb),
         }
         .parse()?;
+        let spawns = self.load_spawns_xml()?;
 
-        let tiles: Vec<_> = otbm.map.tiles;
+        let mut snapshot = Snapshot::new(sources)?;
 
-        println!("Sending items");
+        snapshot.outfits = dat
+            .outfits
+            .values()
+            .map(|outfit| Outfit {
+                id: outfit.id,
+                draw_offset: outfit.draw_offset.clone(),
+                sprite_layout: Self::sprite_layout(&outfit.textures),
+                textures: Default::default(),
+            })
+            .collect();
 
-        transport
-            .transport(Message::ItemsCount(dat.items.len()))
-            .await;
+        if let Some(spawns) = spawns {
+            let looks = self.load_creature_looks();
+            snapshot.spawns = spawns
+                .spawns
+                .iter()
+                .map(|spawn| spawn.to_model(&looks))
+                .collect();
+        }
 
-        let items_vec: Vec<_> = dat.items.into_values().collect();
-        items_vec.into_par_iter().chunks(100).for_each(|items| {
-            let transport = transport.clone();
-            let items = items
-                .into_iter()
-                .map(|item| Item {
-                    id: item.id,
+        load::report_unmapped_items(dat.items.keys().copied(), &server_ids);
+        snapshot.items = dat
+            .items
+            .values()
+            .collect::<Vec<_>>()
+            .into_par_iter()
+            .flat_map_iter(|item| {
+                // the client composes the textures once for every server item sharing them
+                let client_item = Item {
+                    server_id: item.id,
+                    client_id: item.id,
                     name: None,
+                    minimap_color: item.minimap_color.and_then(minimap::minimap_color),
+                    light: item.light,
                     ground: item.ground,
                     stackable: item.stackable,
                     splash: item.splash,
                     fluid_container: item.fluid_container,
-                    draw_offset: item.draw_offset,
-                    height_offset: item.height_offset,
-                    textures: Self::get_item_textures(&item.textures, &spr.sprites),
-                })
-                .collect();
-            tauri::async_runtime::spawn(async move {
-                transport.transport(Message::Items(items)).await;
-            });
-        });
-
-        let tiles_len = tiles.len();
-
-        println!("Sending map, tiles: {}", tiles_len);
-
-        transport.transport(Message::MapTilesCount(tiles_len)).await;
-
-        tiles.into_par_iter().chunks(10_000).for_each(|tiles| {
-            let transport = transport.clone();
-            let tiles = tiles
-                .into_iter()
-                .map(
-                    |otbm::Tile {
-                         position: otbm::Position { x, y, z, .. },
-                         things,
-                     }| {
-                        (
-                            Position(x, y, z),
-                            Tile {
-                                entities: things.into_iter().map(Self::thing_to_entity).collect(),
-                                ..Default::default()
-                            },
-                        )
-                    },
-                )
-                .collect();
-            tauri::async_runtime::spawn(async move {
-                transport.transport(Message::MapTiles(tiles)).await;
-            });
-        });
+                    stack_order: item.stack_order,
+                    draw_offset: item.draw_offset.clone(),
+                    height_offset: item.height_offset.clone(),
+                    sprite_layout: Self::sprite_layout(&item.textures),
+                    textures: Default::default(),
+                };
+                let server_ids = server_ids.get(&item.id).map_or(&[][..], Vec::as_slice);
+                load::server_items(&client_item, server_ids, Some(&otb))
+                    .into_iter()
+                    .map(|mut server_item| {
+                        server_item.name = server_items
+                            .get(&server_item.server_id)
+                            .and_then(|server_item| server_item.name.clone());
+                        server_item
+                    })
+            })
+            .collect();
+
+        snapshot.tiles = otbm
+            .map
+            .tiles
+            .into_par_iter()
+            .map(<(Position, Tile)>::from)
+            .collect();
 
-        Ok(())
+        Ok(snapshot)
     }
-}
 
-impl TfsProject {
-    fn get_item_textures(
-        textures: &dat::Textures,
-        sprites: &HashMap<u16, spr::SpriteBytes>,
-    ) -> Textures {
-        let mut sprite_index = 0;
-        let mut frames = Vec::new();
-        for _ in 0..textures.frames {
-            let mut patterns_z = Vec::new();
-            for _ in 0..textures.patterns_z {
-                let mut patterns_y = Vec::new();
-                for _ in 0..textures.patterns_y {
-                    let mut patterns_x = Vec::new();
-                    for _ in 0..textures.patterns_x {
-                        let mut image = DynamicImage::ImageRgba8(RgbaImage::new(
-                            (textures.width as usize * spr::SPRITE_SIZE)
-                                .try_into()
-                                .unwrap(),
-                            (textures.height as usize * spr::SPRITE_SIZE)
-                                .try_into()
-                                .unwrap(),
-                        ));
-                        let mut layers = Vec::new();
-
-                        for _ in 0..textures.layers {
-                            for height in 0..textures.height {
-                                for width in 0..textures.width {
-                                    let sprite_id = textures.sprites[sprite_index];
-                                    sprite_index += 1;
-                                    let sprite = if sprite_id >= 2
-                                        && sprite_id as usize <= sprites.len() + 2
-                                    {
-                                        sprites.get(&sprite_id)
-                                    } else {
-                                        None
-                                    };
-
-                                    if let Some(sprite_pixels) = sprite.cloned() {
-                                        let sprite_image = DynamicImage::ImageRgba8(
-                                            RgbaImage::from_raw(
-                                                spr::SPRITE_SIZE.try_into().unwrap(),
-                                                spr::SPRITE_SIZE.try_into().unwrap(),
-                                                From::from(sprite_pixels),
-                                            )
-                                            .unwrap(),
-                                        );
-
-                                        overlay(
-                                            &mut image,
-                                            &sprite_image,
-                                            ((textures.width - width - 1) as usize
-                                                * spr::SPRITE_SIZE)
-                                                .try_into()
-                                                .unwrap(),
-                                            ((textures.height - height - 1) as usize
-                                                * spr::SPRITE_SIZE)
-                                                .try_into()
-                                                .unwrap(),
-                                        )
-                                    }
-                                }
-                            }
-                        }
-
-                        let mut buffer = Cursor::new(Vec::new());
-                        image.write_to(&mut buffer, ImageOutputFormat::Png);
-                        let image_base64 = general_purpose::STANDARD.encode(buffer.get_ref());
-
-                        layers.push(TextureLayer {
-                            texture: Texture {
-                                width: image.width().try_into().unwrap(),
-                                height: image.height().try_into().unwrap(),
-                                image: image_base64,
-                                rgba_bytes: image.into_bytes(),
-                            },
-                        });
-                        patterns_x.push(TexturePatternX { layers });
-                    }
-                    patterns_y.push(TexturePatternY { patterns_x });
-                }
-                patterns_z.push(TexturePatternZ { patterns_y });
+    pub fn load_items_xml(&self) -> Result<Option<items_xml::Document>, Error> {
+        match self.items_xml_path.as_ref().filter(|path| path.is_file()) {
+            Some(items_xml_path) => {
+                println!("Loading items.xml");
+                Ok(Some(File::open(items_xml_path)?.parse()?))
             }
-            frames.push(TextureFrame { patterns_z });
+            None => Ok(None),
         }
-        Textures { frames }
     }
 
-    fn thing_to_entity(otbm::Thing { attributes }: otbm::Thing) -> Entity {
-        let mut entity = Entity::new();
-        for attribute in attributes.into_iter() {
-            match attribute {
-                otbm::Attribute::Item(id) => {
-                    entity
-                        .attributes
-                        .insert("item".to_string(), Attribute::Item(attributes::Item(id)));
-                }
-                otbm::Attribute::Container(things) => {
-                    entity.attributes.insert(
-                        "container".to_string(),
-                        Attribute::Container(attributes::Container(
-                            things.into_iter().map(Self::thing_to_entity).collect(),
-                        )),
-                    );
-                }
-                otbm::Attribute::Count(count) => {
-                    entity.attributes.insert(
-                        "count".to_string(),
-                        Attribute::Count(attributes::Count(count)),
-                    );
-                }
-                otbm::Attribute::Fluid(fluid) => {
-                    entity.attributes.insert(
-                        "fluid".to_string(),
-                        Attribute::Fluid(attributes::Fluid(fluid)),
-                    );
-                }
+    pub fn load_spawns_xml(&self) -> Result<Option<spawns_xml::Document>, Error> {
+        if self.spawns_path.is_file() {
+            println!("Loading spawns");
+            Ok(Some(File::open(&self.spawns_path)?.parse()?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    // monsters.xml and the npc folder live in the data folder above the spawn file
+    fn data_path(&self) -> Option<&Path> {
+        self.spawns_path
+            .ancestors()
+            .find(|path| path.join("monster").is_dir() || path.join("npc").is_dir())
+    }
+
+    fn creature_paths(&self) -> Vec<PathBuf> {
+        let Some(data_path) = self.data_path() else {
+            return Vec::new();
+        };
+
+        let monsters_path = data_path.join("monster");
+        let monsters: Option<monsters_xml::Document> =
+            File::open(monsters_path.join("monsters.xml"))
+                .ok()
+                .and_then(|file| file.parse().ok());
+        monsters
+            .into_iter()
+            .flat_map(|monsters| monsters.monsters)
+            .map(|monster| monsters_path.join(monster.file))
+            .chain(load::files_with_extension(&data_path.join("npc"), "xml"))
+            .collect()
+    }
+
+    // creatures that can't be read are left without a look
+    pub fn load_creature_looks(&self) -> HashMap<String, Look> {
+        let mut looks = HashMap::new();
+        println!("Loading creatures");
+
+        for creature_path in self.creature_paths() {
+            let creature: Option<creature_xml::Document> = File::open(creature_path)
+                .ok()
+                .and_then(|file| file.parse().ok());
+            if let Some((name, Some(look))) =
+                creature.map(|creature| (creature.name, creature.look))
+            {
+                looks.insert(name.to_lowercase(), look);
             }
         }
-        entity
+
+        looks
+    }
+
+    // sprites missing from the spr file are left out, their part of the texture stays transparent
+    pub fn sprites(
+        spr: &spr::Document,
+        sprite_ids: impl IntoIterator<Item = u32>,
+    ) -> HashMap<u32, Sprite> {
+        sprite_ids
+            .into_iter()
+            .collect::<BTreeSet<_>>()
+            .into_par_iter()
+            .filter_map(|sprite_id| Some((sprite_id, SpriteSource::sprite(spr, sprite_id)?)))
+            .collect()
+    }
+
+    pub fn sprite_layout(textures: &dat::Textures) -> SpriteLayout {
+        SpriteLayout {
+            width: textures.width,
+            height: textures.height,
+            layers: textures.layers,
+            patterns_x: textures.patterns_x,
+            patterns_y: textures.patterns_y,
+            patterns_z: textures.patterns_z,
+            frames: textures.frames,
+            frame_durations: Vec::new(),
+            sprite_ids: textures
+                .sprites
+                .iter()
+                .map(|sprite_id| *sprite_id as u32)
+                .collect(),
+        }
+    }
+
+    pub fn get_item_textures(
+        textures: &dat::Textures,
+        sprites: &HashMap<u32, Sprite>,
+        split_layers: bool,
+    ) -> Textures {
+        Self::sprite_layout(textures).compose(sprites, split_layers)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{codecs::png::PngEncoder, ColorType, ImageEncoder};
+    use std::collections::HashSet;
+
+    // what loading sends and keeps before and after sprite layouts, run it against a client:
+    // TFS_ASSETS=<directory with Tibia.spr, Tibia.dat and items.otb> cargo test transfer_sizes -- --ignored --nocapture
+    #[test]
+    #[ignore]
+    fn transfer_sizes() {
+        let directory = PathBuf::from(std::env::var("TFS_ASSETS").expect("TFS_ASSETS isn't set"));
+        let spr: spr::Document = File::open(directory.join("Tibia.spr"))
+            .unwrap()
+            .parse()
+            .unwrap();
+        let dat: dat::Document = File::open(directory.join("Tibia.dat"))
+            .unwrap()
+            .parse()
+            .unwrap();
+        let otb: otb::Document = File::open(directory.join("items.otb"))
+            .unwrap()
+            .parse()
+            .unwrap();
+
+        let items: Vec<_> = otb
+            .items
+            .values()
+            .filter_map(|otb_item| dat.items.get(&otb_item.client_id))
+            .collect();
+        let sprite_ids: BTreeSet<_> = items
+            .iter()
+            .flat_map(|item| {
+                item.textures
+                    .sprites
+                    .iter()
+                    .map(|sprite_id| *sprite_id as u32)
+            })
+            .collect();
+        let sprites = TfsProject::sprites(&spr, sprite_ids.iter().copied());
+
+        // every server item used to carry its composed rgba and a base64 png of it
+        let composed_sizes: HashMap<u16, (usize, usize)> = items
+            .iter()
+            .map(|item| item.id)
+            .collect::<HashSet<_>>()
+            .into_par_iter()
+            .map(|client_id| {
+                let textures =
+                    TfsProject::get_item_textures(&dat.items[&client_id].textures, &sprites, false);
+                let (rgba, png) = textures
+                    .get_all()
+                    .iter()
+                    .fold((0, 0), |(rgba, png), texture| {
+                        let mut bytes = Vec::new();
+                        PngEncoder::new(&mut bytes)
+                            .write_image(
+                                &texture.rgba_bytes,
+                                texture.width.into(),
+                                texture.height.into(),
+                                ColorType::Rgba8,
+                            )
+                            .unwrap();
+                        (
+                            rgba + texture.rgba_bytes.len(),
+                            png + bytes.len().div_ceil(3) * 4,
+                        )
+                    });
+                (client_id, (rgba, png))
+            })
+            .collect();
+        let before: usize = items
+            .iter()
+            .map(|item| {
+                let (rgba, png) = composed_sizes[&item.id];
+                rgba + png
+            })
+            .sum();
+
+        // now every server item carries sprite ids, every sprite is sent once
+        // and the client composes once per client item
+        let sprites_size: usize = sprites.values().map(|sprite| sprite.rgba_bytes.len()).sum();
+        let layouts_size: usize = items
+            .iter()
+            .map(|item| item.textures.sprites.len() * 4)
+            .sum();
+        let composed_size: usize = composed_sizes.values().map(|(rgba, _)| rgba).sum();
+
+        println!(
+            "{} server items, {} client items, {} sprites",
+            items.len(),
+            composed_sizes.len(),
+            sprites.len()
+        );
+        println!("before: {} KiB sent and kept", before / 1024);
+        println!(
+            "after: {} KiB sent, {} KiB kept",
+            (sprites_size + layouts_size) / 1024,
+            (sprites_size + layouts_size + composed_size) / 1024
+        );
     }
 }
@@ -0,0 +1,42 @@
+use super::TfsProject;
+use crate::{
+    load::Error,
+    minimap::Minimap,
+    parse::{dat, otb, otbm, Parse},
+};
+use model::{
+    minimap::{minimap_color, minimap_floors, MinimapFloor},
+    Position, Tile,
+};
+use std::{collections::BTreeMap, fs::File};
+
+impl Minimap for TfsProject {
+    fn minimap(&self) -> Result<BTreeMap<u8, MinimapFloor>, Error> {
+        let dat: dat::Document = File::open(&self.dat_path)?.parse()?;
+        let otb: otb::Document = File::open(&self.otb_path)?.parse()?;
+        let otbm: otbm::Document = otbm::Reader {
+            path: &self.otbm_path,
+            items: &dat,
+            otb: Some(&otb),
+        }
+        .parse()?;
+
+        let tiles: Vec<(Position, Tile)> = otbm.map.tiles.into_iter().map(Into::into).collect();
+
+        // items.otb can override the color of the client item
+        Ok(minimap_floors(
+            tiles.iter().map(|(position, tile)| (position, tile)),
+            |server_id| {
+                let otb_item = otb.item(server_id)?;
+                otb_item
+                    .minimap_color
+                    .or_else(|| {
+                        dat.items
+                            .get(&otb_item.client_id)
+                            .and_then(|item| item.minimap_color)
+                    })
+                    .and_then(minimap_color)
+            },
+        ))
+    }
+}
@@ -9,6 +9,7 @@ pub struct TfsProject {
     pub dat_path: PathBuf,
     pub otb_path: PathBuf,
     pub otbm_path: PathBuf,
+    pub items_xml_path: Option<PathBuf>,
     pub houses_path: PathBuf,
     pub spawns_path: PathBuf,
 }
@@ -20,7 +21,15 @@ impl From<TfsProject> for Project {
 }
 
 mod detector;
+mod exporter;
 mod loader;
+mod minimap;
+mod server_items;
+mod sprites;
+mod verifier;
 
 pub use detector::*;
+pub use exporter::*;
 pub use loader::*;
+pub use server_items::*;
+pub use verifier::*;
@@ -0,0 +1,53 @@
+use super::TfsProject;
+use crate::parse::{items_xml, otb};
+use serde::Serialize;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServerItem {
+    pub server_id: u16,
+    pub client_id: u16,
+    pub name: Option<String>,
+    pub article: Option<String>,
+    pub plural: Option<String>,
+    pub item_type: Option<String>,
+    pub weight: Option<u32>,
+    pub armor: Option<u16>,
+    pub decay_to: Option<u16>,
+    pub container_size: Option<u16>,
+    pub floor_change: Option<String>,
+    pub attributes: HashMap<String, String>,
+}
+
+impl TfsProject {
+    pub fn server_items(
+        otb: &otb::Document,
+        items_xml: Option<&items_xml::Document>,
+    ) -> HashMap<u16, ServerItem> {
+        otb.items
+            .values()
+            .map(|otb_item| {
+                let xml_item = items_xml
+                    .and_then(|items_xml| items_xml.items.get(&otb_item.server_id))
+                    .cloned()
+                    .unwrap_or_default();
+                let server_item = ServerItem {
+                    server_id: otb_item.server_id,
+                    client_id: otb_item.client_id,
+                    name: xml_item.name.or_else(|| otb_item.name.clone()),
+                    article: xml_item.article,
+                    plural: xml_item.plural,
+                    item_type: xml_item.item_type,
+                    weight: xml_item.weight,
+                    armor: xml_item.armor,
+                    decay_to: xml_item.decay_to,
+                    container_size: xml_item.container_size,
+                    floor_change: xml_item.floor_change,
+                    attributes: xml_item.attributes,
+                };
+                (server_item.server_id, server_item)
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,27 @@
+use super::TfsProject;
+use crate::{
+    load::Error,
+    parse::{spr, Parse},
+    sprites::{SpriteSource, Sprites},
+};
+use model::Sprite;
+use std::{fs::File, sync::Arc};
+
+impl Sprites for TfsProject {
+    fn sprite_source(&self) -> Result<Arc<dyn SpriteSource>, Error> {
+        let spr: spr::Document = File::open(&self.spr_path)?.parse()?;
+        Ok(Arc::new(spr))
+    }
+}
+
+impl SpriteSource for spr::Document {
+    fn sprite(&self, id: u32) -> Option<Sprite> {
+        let sprite = spr::Document::sprite(self, id.try_into().ok()?)?;
+        Some(Sprite {
+            id,
+            width: Sprite::SIZE,
+            height: Sprite::SIZE,
+            rgba_bytes: sprite.as_ref().as_ref().to_vec(),
+        })
+    }
+}
@@ -0,0 +1,240 @@
+use super::TfsProject;
+use crate::parse::{dat, otb, spr};
+use md5::{Digest, Md5};
+use model::{Sprite, Textures, TexturesGetBuilder};
+use rayon::prelude::*;
+use serde::Serialize;
+use std::collections::HashMap;
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpriteHashMismatch {
+    pub server_id: u16,
+    pub client_id: u16,
+    pub expected: Vec<u8>,
+    pub actual: Option<Vec<u8>>,
+    pub suggested_client_ids: Vec<u16>,
+}
+
+// transparent pixels are hashed with this colour, the same way item editors do it
+const TRANSPARENT_COLOR: u8 = 0x11;
+
+impl TfsProject {
+    // item editors hash the sprites of the first pattern, one by one in the dat order and
+    // bottom row first, they're cut back out of the composed layers
+    pub fn sprite_hash(textures: &Textures) -> Vec<u8> {
+        let size = Sprite::SIZE as usize;
+        let mut hasher = Md5::new();
+
+        for layer in 0..textures.layers_count() {
+            let texture = textures.get(TexturesGetBuilder::new().layer(layer));
+            let (width, height) = (texture.width as usize, texture.height as usize);
+
+            for y in (0..height / size).rev() {
+                for x in (0..width / size).rev() {
+                    let mut bgra = [0; Sprite::SIZE as usize * Sprite::SIZE as usize * 4];
+                    for row in 0..size {
+                        for column in 0..size {
+                            let source =
+                                ((y * size + size - row - 1) * width + x * size + column) * 4;
+                            let target = (row * size + column) * 4;
+                            let pixel = &texture.rgba_bytes[source..source + 4];
+                            let [red, green, blue] = match pixel[3] {
+                                0 => [TRANSPARENT_COLOR; 3],
+                                _ => [pixel[0], pixel[1], pixel[2]],
+                            };
+                            bgra[target..target + 4].copy_from_slice(&[blue, green, red, 0]);
+                        }
+                    }
+                    hasher.update(bgra);
+                }
+            }
+        }
+
+        hasher.finalize().to_vec()
+    }
+
+    // composed from the sprites of the first pattern only, that's all the hash covers
+    fn sprite_hashes(dat: &dat::Document, spr: &spr::Document) -> HashMap<u16, Vec<u8>> {
+        let textures: Vec<_> = dat
+            .items
+            .values()
+            .map(|item| {
+                let sprites_count = item.textures.width as usize
+                    * item.textures.height as usize
+                    * item.textures.layers as usize;
+                let textures = dat::Textures {
+                    patterns_x: 1,
+                    patterns_y: 1,
+                    patterns_z: 1,
+                    frames: 1,
+                    sprites: item.textures.sprites
+                        [..sprites_count.min(item.textures.sprites.len())]
+                        .to_vec(),
+                    ..item.textures
+                };
+                (item.id, textures)
+            })
+            .collect();
+
+        let sprite_ids = textures
+            .iter()
+            .flat_map(|(_, textures)| textures.sprites.iter().map(|sprite_id| *sprite_id as u32));
+        let sprites = Self::sprites(spr, sprite_ids);
+
+        textures
+            .into_par_iter()
+            .map(|(client_id, textures)| {
+                let textures = Self::get_item_textures(&textures, &sprites, true);
+                (client_id, Self::sprite_hash(&textures))
+            })
+            .collect()
+    }
+
+    pub fn verify_sprite_hashes(
+        otb: &otb::Document,
+        dat: &dat::Document,
+        spr: &spr::Document,
+    ) -> Vec<SpriteHashMismatch> {
+        let hashes = Self::sprite_hashes(dat, spr);
+
+        let mut client_ids_by_hash: HashMap<&[u8], Vec<u16>> = HashMap::new();
+        for (client_id, hash) in hashes.iter() {
+            client_ids_by_hash
+                .entry(hash.as_slice())
+                .or_default()
+                .push(*client_id);
+        }
+
+        let mut mismatches: Vec<_> = otb
+            .items
+            .values()
+            .filter_map(|item| {
+                let expected = item.sprite_hash.as_ref()?;
+                let actual = hashes.get(&item.client_id);
+                if actual == Some(expected) {
+                    return None;
+                }
+
+                let mut suggested_client_ids = client_ids_by_hash
+                    .get(expected.as_slice())
+                    .cloned()
+                    .unwrap_or_default();
+                suggested_client_ids.sort();
+
+                Some(SpriteHashMismatch {
+                    server_id: item.server_id,
+                    client_id: item.client_id,
+                    expected: expected.clone(),
+                    actual: actual.cloned(),
+                    suggested_client_ids,
+                })
+            })
+            .collect();
+        mismatches.sort_by_key(|mismatch| mismatch.server_id);
+        mismatches
+    }
+
+    pub fn regenerate_sprite_hashes(
+        otb: &mut otb::Document,
+        dat: &dat::Document,
+        spr: &spr::Document,
+    ) {
+        let hashes = Self::sprite_hashes(dat, spr);
+        for item in otb.items.values_mut() {
+            if let Some(hash) = hashes.get(&item.client_id) {
+                item.sprite_hash = Some(hash.clone());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::Parse;
+    use model::{Offset, StackOrder};
+    use std::io::Cursor;
+
+    const ITEMS_OTB: &[u8] = include_bytes!("../../tests/fixtures/items.otb");
+
+    // the sprite 1 has a red pixel, the sprite 2 is missing so it's transparent
+    fn spr() -> spr::Document {
+        let mut bytes = vec![0x4F, 0x57, 0x00, 0x00, 0x02, 0x00];
+        bytes.extend_from_slice(&[0x0E, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]);
+        bytes.extend_from_slice(&[0xFF, 0x00, 0xFF, 0x05, 0x00, 0x00, 0x00, 0x01, 0x00]);
+        bytes.extend_from_slice(&[0xFF, 0x00, 0x00]);
+        Cursor::new(bytes).parse().unwrap()
+    }
+
+    // the items of items.otb have the client ids 0x66, 0xFD and 0x2FE
+    fn dat() -> dat::Document {
+        let items = [(0x66, 1), (0xFD, 2), (0x2FE, 2)]
+            .into_iter()
+            .map(|(id, sprite_id)| {
+                let item = dat::Item {
+                    id,
+                    minimap_color: None,
+                    light: None,
+                    ground: false,
+                    stackable: false,
+                    splash: false,
+                    fluid_container: false,
+                    stack_order: StackOrder::Common,
+                    draw_offset: Offset::default(),
+                    height_offset: Offset::default(),
+                    textures: dat::Textures {
+                        width: 1,
+                        height: 1,
+                        layers: 1,
+                        patterns_x: 1,
+                        patterns_y: 1,
+                        patterns_z: 1,
+                        frames: 1,
+                        sprites: vec![sprite_id],
+                    },
+                };
+                (id, item)
+            })
+            .collect();
+        dat::Document {
+            signature: 0,
+            items,
+            outfits: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn regenerated_hashes_match() {
+        let (spr, dat) = (spr(), dat());
+        let mut otb: otb::Document = Cursor::new(ITEMS_OTB).parse().unwrap();
+        let mismatches = TfsProject::verify_sprite_hashes(&otb, &dat, &spr);
+        assert_eq!(mismatches.len(), 1);
+        assert_eq!(mismatches[0].server_id, 0xFE);
+        assert_eq!(mismatches[0].expected, (0xF0..=0xFF).collect::<Vec<u8>>());
+        assert!(mismatches[0].suggested_client_ids.is_empty());
+
+        TfsProject::regenerate_sprite_hashes(&mut otb, &dat, &spr);
+        assert!(otb.items.values().all(|item| item.sprite_hash.is_some()));
+        assert!(TfsProject::verify_sprite_hashes(&otb, &dat, &spr).is_empty());
+    }
+
+    #[test]
+    fn mismatching_hash_suggests_client_ids() {
+        let (spr, dat) = (spr(), dat());
+        let mut otb: otb::Document = Cursor::new(ITEMS_OTB).parse().unwrap();
+        TfsProject::regenerate_sprite_hashes(&mut otb, &dat, &spr);
+        let red = otb.item(100).unwrap().sprite_hash.clone();
+        let transparent = otb.item(0xFE).unwrap().sprite_hash.clone().unwrap();
+        assert_ne!(red.as_ref(), Some(&transparent));
+
+        otb.item_mut(0xFE).unwrap().client_id = 0x66;
+        let mismatches = TfsProject::verify_sprite_hashes(&otb, &dat, &spr);
+        assert_eq!(mismatches.len(), 1);
+        let mismatch = &mismatches[0];
+        assert_eq!((mismatch.server_id, mismatch.client_id), (0xFE, 0x66));
+        assert_eq!(mismatch.expected, transparent);
+        assert_eq!(mismatch.actual, red);
+        assert_eq!(mismatch.suggested_client_ids, vec![0xFD, 0x2FE]);
+    }
+}
@@ -1,12 +1,18 @@
-use crate::transport::{Error, Message as TransportMessage, Transport};
+use crate::{
+    sprites::{self, SpriteSource},
+    transport::{serialize, Error, Message as TransportMessage, Transport},
+};
 use async_trait::async_trait;
-use futures::{lock::Mutex, sink::SinkExt, stream::SplitSink, Stream, StreamExt};
+use futures::{lock::Mutex, sink::SinkExt, stream::SplitSink, StreamExt};
 use model::{Item, Position, Tile};
-use rkyv::{Archive, Deserialize, Serialize};
+use rkyv::{AlignedVec, Archive, Deserialize, Serialize};
 use std::{
     collections::HashMap,
     net::{Ipv4Addr, SocketAddr, SocketAddrV4},
-    sync::Arc,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, PoisonError, RwLock,
+    },
 };
 use tauri::{
     plugin::{Builder, TauriPlugin},
@@ -29,6 +35,11 @@ pub enum WebSocketMessage {
 pub struct WebSocket {
     url: SocketAddr,
     client: Arc<Mutex<Option<SplitSink<WebSocketStream<TcpStream>, Message>>>>,
+    // the size of the last message, the next one is usually a batch of the same size so it's
+    // archived without growing
+    capacity: AtomicUsize,
+    // sprite requests are answered from the project loaded last
+    sprite_source: RwLock<Option<Arc<dyn SpriteSource>>>,
 }
 
 impl WebSocket {
@@ -44,14 +55,26 @@ impl WebSocket {
                     app.manage(Arc::new(WebSocket {
                         url: listener.local_addr().unwrap(),
                         client: Arc::new(Mutex::new(None)),
+                        capacity: AtomicUsize::new(0),
+                        sprite_source: RwLock::new(None),
                     }));
 
                     while let Ok((client, _)) = listener.accept().await {
                         let ws_stream = tokio_tungstenite::accept_async(client).await.unwrap();
-                        let (write, _) = ws_stream.split();
-                        let ws = app.state::<Arc<WebSocket>>();
-                        let mut client = ws.client.lock().await;
-                        *client = Some(write);
+                        let (write, mut read) = ws_stream.split();
+                        let ws = app.state::<Arc<WebSocket>>().inner().clone();
+                        *ws.client.lock().await = Some(write);
+
+                        tauri::async_runtime::spawn(async move {
+                            while let Some(Ok(message)) = read.next().await {
+                                // the editor sees the connection close, the load or request
+                                // waiting for the reply fails there
+                                if ws.receive(message).await.is_err() {
+                                    ws.close().await;
+                                    break;
+                                }
+                            }
+                        });
                     }
                 });
                 Ok(())
@@ -62,6 +85,45 @@ impl WebSocket {
     pub fn url(&self) -> SocketAddr {
         self.url
     }
+
+    pub fn set_sprite_source(&self, sprite_source: Arc<dyn SpriteSource>) {
+        *self
+            .sprite_source
+            .write()
+            .unwrap_or_else(PoisonError::into_inner) = Some(sprite_source);
+    }
+
+    // the editor only sends sprite requests, anything else is ignored
+    async fn receive(&self, message: Message) -> Result<(), Error> {
+        let Message::Binary(data) = message else {
+            return Ok(());
+        };
+        let mut bytes = AlignedVec::with_capacity(data.len());
+        bytes.extend_from_slice(&data);
+        let Ok(TransportMessage::SpriteRequest(sprite_ids)) =
+            rkyv::from_bytes::<TransportMessage>(&bytes)
+        else {
+            return Ok(());
+        };
+
+        let sprite_source = self
+            .sprite_source
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone();
+        // without a project the request still ends, the editor is waiting for it
+        match sprite_source {
+            Some(sprite_source) => sprites::send(sprite_source.as_ref(), &sprite_ids, self).await,
+            None => self.transport(TransportMessage::Loaded).await,
+        }
+    }
+
+    async fn close(&self) {
+        if let Some(mut client) = self.client.lock().await.take() {
+            // it's dropped either way, a connection that's already gone can't be closed
+            let _ = client.close().await;
+        }
+    }
 }
 
 #[async_trait]
@@ -70,25 +132,21 @@ impl Transport for WebSocket {
         let mut client = self.client.lock().await;
         let mut sink = client.as_mut().ok_or(Error::Something)?; // FIXME: return proper error
 
-        sink.send(Message::Binary(
-            // FIXME: 1024 is a kinda randomly chose number of bytes to allocate, try to find a better one
-            rkyv::to_bytes::<TransportMessage, 1024>(&data)
-                .unwrap()
-                .to_vec(),
-        ))
-        .await
-        .unwrap(); // FIXME: unwraps
+        let bytes = serialize(&data, self.capacity.load(Ordering::Relaxed))?;
+        self.capacity.store(bytes.len(), Ordering::Relaxed);
+
+        sink.send(Message::Binary(bytes))
+            .await
+            .map_err(|_| Error::WebSocket)?;
         Ok(())
     }
-}
-
-impl Stream for WebSocket {
-    type Item = TransportMessage;
 
-    fn poll_next(
-        self: std::pin::Pin<&mut Self>,
-        cx: &mut std::task::Context<'_>,
-    ) -> std::task::Poll<Option<Self::Item>> {
-        todo!()
+    // tungstenite owns what it sends, the bytes are copied once into the frame
+    async fn transport_archived(&self, bytes: &[u8]) -> Result<(), Error> {
+        let mut client = self.client.lock().await;
+        let sink = client.as_mut().ok_or(Error::Something)?; // FIXME: return proper error
+        sink.send(Message::Binary(bytes.to_vec()))
+            .await
+            .map_err(|_| Error::WebSocket)
     }
 }
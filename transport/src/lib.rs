@@ -2,34 +2,70 @@
 #![allow(unused)]
 
 use async_trait::async_trait;
-use futures::Stream;
-use model::{Item, Position, Tile};
-use rkyv::{Archive, Deserialize, Serialize};
+use model::{Item, Outfit, Position, Spawn, Sprite, Tile};
+use rkyv::{
+    ser::{
+        serializers::{
+            AllocScratch, CompositeSerializer, FallbackScratch, HeapScratch, SharedSerializeMap,
+            WriteSerializer,
+        },
+        Serializer,
+    },
+    Archive, Deserialize, Serialize,
+};
 use std::collections::HashMap;
 
+// the resolvers of a batch of items or tiles, bigger batches fall back to allocating
+pub const SCRATCH_SPACE: usize = 64 * 1024;
+
 #[derive(Debug)]
 pub enum Error {
     Something, // TODO:
     WebSocket,
+    Encode,
+    Decode,
 }
 
 #[async_trait]
-pub trait Transport: Stream<Item = Message> {
+pub trait Transport {
     async fn transport(&self, data: Message) -> Result<(), Error>;
+    // a message archived before, e.g. one stored in a snapshot
+    async fn transport_archived(&self, bytes: &[u8]) -> Result<(), Error>;
 }
 
 #[derive(Debug, Archive, Deserialize, Serialize)]
+#[archive(check_bytes)]
 pub enum Message {
     Bytes(Vec<u8>),
+    Sprites(Vec<Sprite>),
     ItemsCount(usize),
     Item(Item),
     Items(Vec<Item>),
+    Outfits(Vec<Outfit>),
     MapTilesCount(usize),
     MapTile((Position, Tile)),
     MapTiles(Vec<(Position, Tile)>),
+    Spawns(Vec<Spawn>),
+    // sent by the editor for the sprites it's about to draw, they come back in Sprites batches
+    // followed by Loaded
+    SpriteRequest(Vec<u32>),
     Loaded,
 }
 
+// archived straight into the bytes a websocket frame takes, the receiving side aligns them when it
+// reads them, the capacity is a guess of the size so the bytes don't grow
+pub fn serialize(message: &Message, capacity: usize) -> Result<Vec<u8>, Error> {
+    let mut serializer = CompositeSerializer::new(
+        WriteSerializer::new(Vec::with_capacity(capacity)),
+        FallbackScratch::<HeapScratch<SCRATCH_SPACE>, AllocScratch>::default(),
+        SharedSerializeMap::new(),
+    );
+    serializer
+        .serialize_value(message)
+        .map_err(|_| Error::Encode)?;
+    Ok(serializer.into_serializer().into_inner())
+}
+
 // FIXME: should it actually be here? probably not
 impl From<tungstenite::Error> for Error {
     fn from(value: tungstenite::Error) -> Self {
@@ -1,10 +1,11 @@
 import { createSignal, Show } from 'solid-js';
+import { invoke } from '@tauri-apps/api/core';
 import { FilePicker } from './FilePicker';
-import { ProjectLoader, TfsProject } from './ProjectLoader';
+import { ProjectLoader, ProjectToLoad } from './ProjectLoader';
 import { join } from 'path-browserify';
 
 export const App = () => {
-  const [projectToLoad, setProjectToLoad] = createSignal<TfsProject>();
+  const [projectToLoad, setProjectToLoad] = createSignal<ProjectToLoad>();
 
   return (
     <Show
@@ -12,15 +13,19 @@ export const App = () => {
       fallback={
         <FilePicker
           onPick={async (path: string) => {
+            const detected = await invoke<ProjectToLoad | null>('detect', {
+              directory: path,
+            }).catch(() => null);
             const tfsProject = {
               sprPath: join(path, 'Tibia.spr'),
               datPath: join(path, 'Tibia.dat'),
               otbPath: join(path, 'items.otb'),
               otbmPath: join(path, 'map.otbm'),
+              itemsXmlPath: join(path, 'items.xml'),
               housesPath: join(path, 'map-house.xml'),
               spawnsPath: join(path, 'map-spawn.xml'),
             };
-            setProjectToLoad(tfsProject);
+            setProjectToLoad(detected ?? { TfsProject: tfsProject });
           }}
           directory={true}
         >
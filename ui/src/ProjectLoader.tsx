@@ -2,7 +2,8 @@ import { invoke } from '@tauri-apps/api/core';
 import { createResource, createSignal, Show } from 'solid-js';
 import { ProgressBar } from './ProgressBar';
 import { Project } from './project/Project';
-import init, { Project as WasmProject } from '@wasm';
+import init, { Project as WasmProject, ProjectLoadError } from '@wasm';
+import { ServerItem } from './model';
 
 export type SkylessProject = {
   assetsPath: string;
@@ -14,33 +15,79 @@ export type TfsProject = {
   datPath: string;
   otbPath: string;
   otbmPath: string;
+  itemsXmlPath?: string;
   housesPath: string;
   spawnsPath: string;
 };
 
+export type CanaryProject = {
+  assetsPath: string;
+  otbPath?: string;
+  otbmPath: string;
+};
+
+export type ProjectToLoad =
+  | { CanaryProject: CanaryProject }
+  | { SkylessProject: SkylessProject }
+  | { TfsProject: TfsProject };
+
 type Progress = {
   progress: number;
   label?: string;
 };
 
+// the editor rejects with a ProjectLoadError, tauri with the serialized load error
+const loadErrorMessage = (reason: unknown) => {
+  switch (reason) {
+    case ProjectLoadError.Transport:
+      return 'Lost the connection to the project';
+    case ProjectLoadError.Decode:
+      return 'The project sent data the editor could not read';
+    default:
+      return `Could not load the project: ${
+        typeof reason === 'string' ? reason : JSON.stringify(reason)
+      }`;
+  }
+};
+
 export const ProjectLoader = ({
   projectToLoad,
 }: {
-  projectToLoad: SkylessProject | TfsProject;
+  projectToLoad: ProjectToLoad;
 }) => {
   const [progress, setProgress] = createSignal<Progress>({
     progress: 0,
   });
+  const [error, setError] = createSignal<string>();
 
   const [project] = createResource(async () => {
     const wsUrl = await invoke<string>('get_websocket_url');
     await init();
 
-    const [, project] = await Promise.all([
-      invoke<TfsProject>('load', { project: projectToLoad }),
+    // only TFS projects have items.xml to describe server items
+    const serverItems =
+      'TfsProject' in projectToLoad
+        ? invoke<ServerItem[]>('server_items', {
+            project: projectToLoad.TfsProject,
+          })
+        : Promise.resolve([]);
+
+    const loaded = await Promise.all([
+      invoke<void>('load', { project: projectToLoad }),
       WasmProject.load(wsUrl, setProgress),
-    ]);
-    return project;
+      serverItems,
+    ]).catch((reason) => {
+      setError(loadErrorMessage(reason));
+      return undefined;
+    });
+    if (!loaded) {
+      return undefined;
+    }
+    const [, project, items] = loaded;
+    return {
+      project,
+      serverItems: new Map(items.map((item) => [item.serverId, item])),
+    };
   });
 
   return (
@@ -48,14 +95,24 @@ export const ProjectLoader = ({
       when={project()}
       fallback={
         <div class="flex flex-col h-screen justify-center bg-neutral-800">
-          <ProgressBar
-            progress={progress().progress}
-            label={progress().label || 'Loading'}
-          />
+          <Show
+            when={error()}
+            fallback={
+              <ProgressBar
+                progress={progress().progress}
+                label={progress().label || 'Loading'}
+              />
+            }
+          >
+            <p class="text-center text-sm text-red-400">{error()}</p>
+          </Show>
         </div>
       }
     >
-      <Project project={project()!} />
+      <Project
+        project={project()!.project}
+        serverItems={project()!.serverItems}
+      />
     </Show>
   );
 };
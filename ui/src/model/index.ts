@@ -1 +1,2 @@
 export * from './position';
+export * from './server-item';
@@ -0,0 +1,15 @@
+// an items.otb entry merged with items.xml, as the server_items command returns it
+export type ServerItem = {
+  serverId: number;
+  clientId: number;
+  name: string | null;
+  article: string | null;
+  plural: string | null;
+  itemType: string | null;
+  weight: number | null;
+  armor: number | null;
+  decayTo: number | null;
+  containerSize: number | null;
+  floorChange: string | null;
+  attributes: Record<string, string>;
+};
@@ -1,25 +1,30 @@
 import { Component, createContext, For, onMount, useContext } from 'solid-js';
 import { Project as ProjectModel } from '@wasm';
 import { Map } from './map';
-import { Position } from '../model';
+import { Position, ServerItem } from '../model';
 
 const ProjectContext = createContext<ProjectModel>();
+const ServerItemsContext = createContext<Map<number, ServerItem>>(new Map());
 
 export const useProject = () => useContext(ProjectContext);
+export const useServerItems = () => useContext(ServerItemsContext);
 
 type ProjectProps = {
   project: ProjectModel;
+  serverItems: Map<number, ServerItem>;
 };
 
 export const Project: Component<ProjectProps> = (props) => {
   return (
     <ProjectContext.Provider value={props.project}>
-      <div class="flex h-screen">
-        <div class="flex-1">
-          {/* FIXME:  hardcoded position */}
-          <Map center={new Position(32369, 32241, 7)} interactive={true} />
+      <ServerItemsContext.Provider value={props.serverItems}>
+        <div class="flex h-screen">
+          <div class="flex-1">
+            {/* FIXME:  hardcoded position */}
+            <Map center={new Position(32369, 32241, 7)} interactive={true} />
+          </div>
         </div>
-      </div>
+      </ServerItemsContext.Provider>
     </ProjectContext.Provider>
   );
 };
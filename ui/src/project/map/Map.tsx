@@ -1,12 +1,51 @@
-import { Component, createEffect, createSignal, on, onMount } from 'solid-js';
+import {
+  Component,
+  createEffect,
+  createSignal,
+  For,
+  on,
+  onCleanup,
+  onMount,
+  Show,
+} from 'solid-js';
 import { v1 } from 'uuid';
 import { WebGLMapRenderer } from '@wasm';
 import { Position } from '../../model';
 import { Matrix3 } from '../../utils';
 import { ListenerType, MapInputHandler } from './hooks/map-input-handler';
-import { getProjectionViewMatrix } from './utils';
-import { MAX_ZOOM, MIN_ZOOM, TILE_SIZE, ZOOM_SPEED } from './constants';
-import { useProject } from '../Project';
+import {
+  AnyMapRenderer,
+  createAnyMapRenderer,
+  getProjectionViewMatrix,
+} from './utils';
+import {
+  ANIMATION_INTERVAL,
+  MAX_LEVEL,
+  MAX_ZOOM,
+  MIN_LEVEL,
+  MIN_ZOOM,
+  OVERLAY_KEYS,
+  TILE_SIZE,
+  ZOOM_SPEED,
+} from './constants';
+import { useProject, useServerItems } from '../Project';
+
+type PickedItem = {
+  entity: number;
+  serverId: number;
+  clientId: number;
+  name?: string;
+  count?: number;
+  fluid?: number;
+};
+
+type Pick = {
+  x: number;
+  y: number;
+  z: number;
+  entity?: number;
+  items: PickedItem[];
+};
 
 interface IMapProps {
   center?: Position;
@@ -18,16 +57,30 @@ export const Map: Component<IMapProps> = (props) => {
   const canvasId = v1();
   let canvasRef: HTMLCanvasElement;
   const project = useProject();
+  const serverItems = useServerItems();
   const [zoom, setZoom] = createSignal(props.zoom || 1);
+  const [level, setLevel] = createSignal(props.center?.z ?? 7);
   const [translation, setTranslation] = createSignal<[number, number]>([0, 0]);
   const [transformation, setTransformation] = createSignal<Matrix3>();
-  const [renderer, setRenderer] = createSignal<WebGLMapRenderer>();
+  const [renderer, setRenderer] = createSignal<AnyMapRenderer>();
   const [inputHandler, setInputHandler] = createSignal<MapInputHandler>();
+  const [hovered, setHovered] = createSignal<Pick>();
 
   createEffect(
     on(transformation, () => renderer()?.render(transformation()!.into())),
   );
 
+  createEffect(
+    on(
+      level,
+      () => {
+        renderer()?.setCurrentLevel(level());
+        renderer()?.render(transformation()!.into());
+      },
+      { defer: true },
+    ),
+  );
+
   onMount(() => {
     canvasRef.width = canvasRef.clientWidth;
     canvasRef.height = canvasRef.clientHeight;
@@ -41,20 +94,104 @@ export const Map: Component<IMapProps> = (props) => {
     initialTransformation.scale(zoom());
     initialTransformation.translate(...translation());
 
-    setRenderer(new WebGLMapRenderer(project!, canvasRef));
+    setRenderer(createAnyMapRenderer(project!, canvasRef));
+    renderer()!.setCurrentLevel(level());
+
+    const animation = setInterval(() => {
+      renderer()?.setTime(performance.now());
+      renderer()?.render(transformation()!.into());
+    }, ANIMATION_INTERVAL);
+    onCleanup(() => clearInterval(animation));
     setTransformation(initialTransformation);
 
     const observer = new ResizeObserver(() => {
-      canvasRef.width = canvasRef.clientWidth;
-      canvasRef.height = canvasRef.clientHeight;
-
+      renderer()?.resize(canvasRef.clientWidth, canvasRef.clientHeight);
       renderer()?.render(transformation()!.into());
     });
     observer.observe(canvasRef);
 
+    // the renderer prevents the default on webglcontextlost so the context comes back
+    const onContextRestored = () => {
+      const current = renderer();
+      if (current instanceof WebGLMapRenderer) {
+        current.restore(project!);
+        current.render(transformation()!.into());
+      }
+    };
+    canvasRef.addEventListener('webglcontextrestored', onContextRestored);
+
+    onCleanup(() => {
+      observer.disconnect();
+      canvasRef.removeEventListener('webglcontextrestored', onContextRestored);
+      renderer()?.dispose();
+      setRenderer(undefined);
+    });
+
     if (props.interactive) {
       setInputHandler(new MapInputHandler(canvasRef));
 
+      const onKeyDown = (event: KeyboardEvent) => {
+        if (event.key === 'PageUp') {
+          setLevel(Math.max(MIN_LEVEL, level() - 1));
+        } else if (event.key === 'PageDown') {
+          setLevel(Math.min(MAX_LEVEL, level() + 1));
+        } else if (event.key === 'Escape') {
+          renderer()?.clearSelection();
+          renderer()?.render(transformation()!.into());
+        } else if (event.key === 'l') {
+          const current = renderer();
+          if (current instanceof WebGLMapRenderer) {
+            current.setLighting(!current.lighting);
+            current.render(transformation()!.into());
+          }
+        } else if (event.key === 'b' && import.meta.env.DEV) {
+          const current = renderer();
+          if (current instanceof WebGLMapRenderer) {
+            const benchmark = current.benchmark(transformation()!.into(), 100);
+            console.table({
+              instances: benchmark.instances,
+              bufferSize: benchmark.bufferSize,
+              vertexBufferSize: benchmark.vertexBufferSize,
+              frameTime: benchmark.frameTime,
+            });
+          }
+        } else if (event.key in OVERLAY_KEYS) {
+          const overlay = OVERLAY_KEYS[event.key];
+          renderer()?.setOverlayVisible(
+            overlay,
+            !renderer()?.isOverlayVisible(overlay),
+          );
+          renderer()?.render(transformation()!.into());
+        }
+      };
+      window.addEventListener('keydown', onKeyDown);
+      onCleanup(() => window.removeEventListener('keydown', onKeyDown));
+
+      canvasRef.addEventListener('mousemove', (event: MouseEvent) => {
+        setHovered(
+          renderer()?.pick(
+            project!,
+            event.offsetX,
+            event.offsetY,
+            transformation()!.into(),
+          ) ?? undefined,
+        );
+      });
+      canvasRef.addEventListener('mouseleave', () => setHovered(undefined));
+
+      canvasRef.addEventListener('click', () => {
+        const pick = hovered();
+        if (!pick) {
+          return;
+        }
+        if (renderer()?.isTileSelected(pick.x, pick.y, pick.z)) {
+          renderer()?.deselectTile(pick.x, pick.y, pick.z);
+        } else {
+          renderer()?.selectTile(pick.x, pick.y, pick.z);
+        }
+        renderer()?.render(transformation()!.into());
+      });
+
       inputHandler()!.addEventListener(ListenerType.MOVE, (event: Event) => {
         if (!(event instanceof CustomEvent)) {
           throw new Error('Got MOVE event which is not CustomEvent');
@@ -104,12 +241,42 @@ export const Map: Component<IMapProps> = (props) => {
   });
 
   return (
-    <div class="flex h-full">
+    <div class="relative flex h-full">
       <canvas
         ref={canvasRef!}
         id={canvasId}
         class="flex-1 w-full h-full bg-black"
       ></canvas>
+      <Show when={hovered()}>
+        {(pick) => (
+          <div class="absolute top-2 left-2 p-2 text-xs text-white bg-black/75 pointer-events-none">
+            <div>
+              {pick().x}, {pick().y}, {pick().z}
+            </div>
+            <For each={pick().items}>
+              {(item) => (
+                <div classList={{ 'font-bold': item.entity === pick().entity }}>
+                  {item.name ?? 'Item'} #{item.serverId}
+                  {item.count !== undefined ? ` x${item.count}` : ''}
+                  <Show when={serverItems.get(item.serverId)?.itemType}>
+                    {(itemType) => (
+                      <span class="text-neutral-400"> {itemType()}</span>
+                    )}
+                  </Show>
+                  <Show when={serverItems.get(item.serverId)?.weight}>
+                    {(weight) => (
+                      <span class="text-neutral-400">
+                        {' '}
+                        {(weight() / 100).toFixed(2)} oz
+                      </span>
+                    )}
+                  </Show>
+                </div>
+              )}
+            </For>
+          </div>
+        )}
+      </Show>
     </div>
   );
 };
@@ -1,4 +1,15 @@
+import { Overlay } from '@wasm';
+
 export const TILE_SIZE = 32;
 export const MIN_ZOOM = 0.02;
 export const MAX_ZOOM = 10;
 export const ZOOM_SPEED = 0.01;
+export const MIN_LEVEL = 0;
+export const MAX_LEVEL = 15;
+export const ANIMATION_INTERVAL = 100;
+export const OVERLAY_KEYS: Record<string, Overlay> = {
+  g: Overlay.Grid,
+  z: Overlay.Zones,
+  h: Overlay.Houses,
+  p: Overlay.Spawns,
+};
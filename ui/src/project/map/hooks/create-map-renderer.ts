@@ -1,7 +1,7 @@
 import { Accessor, createEffect, createSignal, on } from 'solid-js';
-import { WebGLMapRenderer } from '@wasm';
 import { MapTransformation } from '.';
 import { useProject } from '../../Project';
+import { AnyMapRenderer, createAnyMapRenderer } from '../utils';
 
 export type MapRenderer = {
   render: () => void;
@@ -13,12 +13,13 @@ export const createMapRenderer = (
 ): MapRenderer => {
   const project = useProject();
 
-  const [renderer, setRenderer] = createSignal<WebGLMapRenderer>();
+  const [renderer, setRenderer] = createSignal<AnyMapRenderer>();
 
   createEffect(
     on(
       canvas,
-      () => canvas() && setRenderer(new WebGLMapRenderer(project!, canvas()!)),
+      () =>
+        canvas() && setRenderer(createAnyMapRenderer(project!, canvas()!)),
     ),
   );
 
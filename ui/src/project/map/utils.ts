@@ -1,5 +1,21 @@
+import { Canvas2DMapRenderer, Project, WebGLMapRenderer } from '@wasm';
 import { Matrix3 } from '../../utils';
 
+export type AnyMapRenderer = WebGLMapRenderer | Canvas2DMapRenderer;
+
+// machines with WebGL2 blocked can still edit, without the lighting pass
+export const createAnyMapRenderer = (
+  project: Project,
+  canvas: HTMLCanvasElement,
+): AnyMapRenderer => {
+  try {
+    return new WebGLMapRenderer(project, canvas);
+  } catch (error) {
+    console.warn('WebGL2 is not available, falling back to Canvas2D', error);
+    return new Canvas2DMapRenderer(project, canvas);
+  }
+};
+
 export const getProjectionViewMatrix = (
   canvasRef: HTMLCanvasElement,
 ): Matrix3 => {
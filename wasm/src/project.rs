@@ -1,16 +1,25 @@
 use crate::{progress::Progress, transport::WebSocket};
-use futures::{Stream, StreamExt};
-use js_sys::Function;
-use model::{Item, World};
-use std::collections::HashMap;
-use transport::Message;
+use futures::{lock::Mutex, StreamExt};
+use js_sys::{Function, Promise};
+use model::{
+    attributes, compose_look, share_item_textures, Attribute, Direction, Item, LazyTextures, Look,
+    Outfit, SharedSprites, Sprite, SpriteLayout, Texture, World,
+};
+use std::{
+    collections::{BTreeSet, HashMap},
+    rc::Rc,
+    sync::PoisonError,
+};
+use transport::{Error, Message};
 use wasm_bindgen::{prelude::wasm_bindgen, JsValue};
+use wasm_bindgen_futures::future_to_promise;
 
 #[wasm_bindgen]
 pub struct Project {
     #[wasm_bindgen(skip)]
     pub data: ProjectData,
-    transport: Box<dyn Stream<Item = Message>>,
+    // sprites are asked for while the project is open
+    transport: Rc<Mutex<WebSocket>>,
 }
 
 #[wasm_bindgen]
@@ -25,26 +34,48 @@ pub struct ProjectData {
 pub struct ProjectAssets {
     #[wasm_bindgen(skip)]
     pub items: HashMap<u16, Item>,
+    #[wasm_bindgen(skip)]
+    pub outfits: HashMap<u16, Outfit>,
+    #[wasm_bindgen(skip)]
+    pub sprites: SharedSprites,
+}
+
+#[wasm_bindgen]
+#[derive(Debug)]
+pub enum ProjectLoadError {
+    Transport,
+    Decode,
+}
+
+impl From<Error> for ProjectLoadError {
+    fn from(error: Error) -> Self {
+        match error {
+            Error::Decode => ProjectLoadError::Decode,
+            _ => ProjectLoadError::Transport,
+        }
+    }
 }
 
 #[wasm_bindgen]
 impl Project {
-    pub async fn load(ws_url: &str, callback: Function) -> Self {
+    // a message that doesn't validate stops loading, the rest of the project can't be trusted
+    pub async fn load(ws_url: &str, callback: Function) -> Result<Project, ProjectLoadError> {
         let mut ws = WebSocket::new(ws_url);
 
         let mut items = HashMap::new();
+        let mut outfits = HashMap::new();
         let mut world = World::empty();
 
         let mut total_items = 0;
         let mut total_tiles = 0;
 
         while let Some(msg) = ws.next().await {
-            match msg {
+            match msg? {
                 Message::ItemsCount(total) => {
                     total_items = total;
                 }
                 Message::Item(item) => {
-                    items.insert(item.id, item);
+                    items.insert(item.server_id, item);
 
                     if total_items > 0
                         && items.len() == total_items
@@ -67,7 +98,7 @@ impl Project {
                 }
                 Message::Items(msg_items) => {
                     for item in msg_items.into_iter() {
-                        items.insert(item.id, item);
+                        items.insert(item.server_id, item);
                     }
 
                     if total_items > 0
@@ -89,6 +120,16 @@ impl Project {
                         )
                         .unwrap();
                 }
+                Message::Outfits(msg_outfits) => {
+                    for outfit in msg_outfits.into_iter() {
+                        outfits.insert(outfit.id, outfit);
+                    }
+                }
+                Message::Spawns(spawns) => {
+                    for spawn in spawns.into_iter() {
+                        world.add_spawn(spawn);
+                    }
+                }
                 Message::MapTilesCount(count) => {
                     total_tiles = count;
                 }
@@ -144,17 +185,167 @@ impl Project {
             };
         }
 
-        Self {
-            data: ProjectData {
-                assets: ProjectAssets { items },
-                world,
-            },
-            transport: Box::new(ws),
-        }
+        // what the first frame draws, the sprites of other items come when they're asked for
+        let assets = ProjectAssets::new(items, outfits);
+        let sprite_ids = assets.map_sprite_ids(&world);
+        request_sprites(&mut ws, &assets.sprites, sprite_ids, |progress| {
+            callback
+                .call1(
+                    &JsValue::null(),
+                    &serde_wasm_bindgen::to_value(&Progress {
+                        progress,
+                        label: Some("Loading sprites".to_string()),
+                    })
+                    .unwrap(),
+                )
+                .unwrap();
+        })
+        .await?;
+
+        Ok(Self {
+            data: ProjectData { assets, world },
+            transport: Rc::new(Mutex::new(ws)),
+        })
+    }
+
+    // sprites of items that aren't on the map, before their textures are first used
+    #[wasm_bindgen(js_name = loadSprites)]
+    pub fn load_sprites(&self, server_ids: Vec<u16>) -> Promise {
+        let sprite_layouts = server_ids
+            .iter()
+            .filter_map(|server_id| self.data.assets.items.get(server_id))
+            .map(|item| &item.sprite_layout);
+        self.request_sprites(self.data.assets.missing_sprite_ids(sprite_layouts))
+    }
+
+    // sprites of outfits and mounts no spawn on the map has, before outfitTexture is asked for them
+    #[wasm_bindgen(js_name = loadOutfitSprites)]
+    pub fn load_outfit_sprites(&self, outfit_ids: Vec<u16>) -> Promise {
+        let sprite_layouts = outfit_ids
+            .iter()
+            .filter_map(|outfit_id| self.data.assets.outfits.get(outfit_id))
+            .map(|outfit| &outfit.sprite_layout);
+        self.request_sprites(self.data.assets.missing_sprite_ids(sprite_layouts))
+    }
+
+    fn request_sprites(&self, sprite_ids: Vec<u32>) -> Promise {
+        let sprites = self.data.assets.sprites.clone();
+        let transport = self.transport.clone();
+        future_to_promise(async move {
+            if !sprite_ids.is_empty() {
+                let mut ws = transport.lock().await;
+                request_sprites(&mut ws, &sprites, sprite_ids, |_| ()).await?;
+            }
+            Ok(JsValue::UNDEFINED)
+        })
     }
 
     #[wasm_bindgen(getter, js_name = tilesLen)]
     pub fn tiles_len(&self) -> usize {
         self.data.world.tiles().len()
     }
+
+    // the standing frame with colours, addons and mount of the look
+    #[wasm_bindgen(js_name = outfitTexture)]
+    pub fn outfit_texture(&self, look: &Look, direction: Direction) -> Option<Texture> {
+        self.data.assets.outfit_texture(look, direction)
+    }
+}
+
+impl ProjectAssets {
+    // textures are composed when the renderer or palette first asks for them
+    pub fn new(mut items: HashMap<u16, Item>, mut outfits: HashMap<u16, Outfit>) -> Self {
+        let sprites = SharedSprites::default();
+        share_item_textures(items.values_mut(), &sprites);
+        for outfit in outfits.values_mut() {
+            outfit.textures =
+                LazyTextures::new(outfit.sprite_layout.clone(), true, sprites.clone());
+        }
+        Self {
+            items,
+            outfits,
+            sprites,
+        }
+    }
+
+    // sprites of the items on the map and of the creatures of its spawns
+    pub fn map_sprite_ids(&self, world: &World) -> Vec<u32> {
+        let items = world
+            .tiles()
+            .values()
+            .flat_map(|tile| tile.entities.iter())
+            .filter_map(|entity| match entity.attributes.get("item") {
+                Some(Attribute::Item(attributes::Item(item_id))) => self.items.get(item_id),
+                _ => None,
+            })
+            .map(|item| &item.sprite_layout);
+        let outfits = world
+            .spawns()
+            .iter()
+            .flat_map(|spawn| spawn.creatures.iter())
+            .filter_map(|creature| creature.look)
+            .flat_map(|look| [look.look_type, look.mount])
+            .filter_map(|outfit_id| self.outfits.get(&outfit_id))
+            .map(|outfit| &outfit.sprite_layout);
+        items
+            .chain(outfits)
+            .flat_map(|sprite_layout| sprite_layout.sprite_ids.iter().copied())
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect()
+    }
+
+    pub fn missing_sprite_ids<'a>(
+        &self,
+        sprite_layouts: impl Iterator<Item = &'a SpriteLayout>,
+    ) -> Vec<u32> {
+        let sprites = self.sprites.read().unwrap_or_else(PoisonError::into_inner);
+        sprite_layouts
+            .flat_map(|sprite_layout| sprite_layout.sprite_ids.iter().copied())
+            .filter(|sprite_id| !sprites.contains_key(sprite_id))
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect()
+    }
+
+    pub fn outfit_texture(&self, look: &Look, direction: Direction) -> Option<Texture> {
+        compose_look(&self.outfits, look, direction, 0).map(|(_, texture)| texture)
+    }
+}
+
+// the sprites of a request come in batches until Loaded, the ones that didn't come are marked
+// missing so textures waiting for them are kept
+async fn request_sprites(
+    ws: &mut WebSocket,
+    sprites: &SharedSprites,
+    sprite_ids: Vec<u32>,
+    mut progress: impl FnMut(f32),
+) -> Result<(), ProjectLoadError> {
+    let requested = sprite_ids.len().max(1);
+    ws.send(&Message::SpriteRequest(sprite_ids.clone()))?;
+
+    let mut received = 0;
+    while let Some(msg) = ws.next().await {
+        match msg? {
+            Message::Sprites(msg_sprites) => {
+                received += msg_sprites.len();
+                sprites
+                    .write()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .extend(msg_sprites.into_iter().map(|sprite| (sprite.id, sprite)));
+                progress(received as f32 / requested as f32);
+            }
+            Message::Loaded => {
+                let mut sprites = sprites.write().unwrap_or_else(PoisonError::into_inner);
+                for sprite_id in sprite_ids {
+                    sprites
+                        .entry(sprite_id)
+                        .or_insert_with(|| Sprite::missing(sprite_id));
+                }
+                return Ok(());
+            }
+            _ => (),
+        }
+    }
+    Err(ProjectLoadError::Transport)
 }
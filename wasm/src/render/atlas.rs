@@ -0,0 +1,195 @@
+use model::{
+    frame_at, frame_duration,
+    scene::{SceneChunk, SpriteKey},
+    Direction, Item, Look, Texture,
+};
+use std::{cmp::Reverse, collections::HashMap, sync::Arc};
+
+// every pattern of an item takes one block, its frames are laid out in a grid inside the block
+// so the shader can pick the current frame without another lookup
+#[derive(Debug, Clone)]
+pub struct AtlasEntry {
+    pub page: u32,
+    // top left corner of the first frame, padding excluded
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    pub frames: u32,
+    pub columns: u32,
+    pub cell_width: u32,
+    pub cell_height: u32,
+    // shared by the patterns of an item
+    pub frame_durations: Arc<[u32]>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct AtlasRect {
+    pub page: u32,
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+// a sprite doesn't fit in a page, or the pages are more than a backend can hold
+#[derive(Debug, Clone, Copy)]
+pub struct AtlasFull;
+
+#[derive(Debug, Clone, Default)]
+pub struct Atlas {
+    pub page_size: u32,
+    pub padding: u32,
+    pub pages: u32,
+    entries: HashMap<SpriteKey, AtlasEntry>,
+}
+
+impl Atlas {
+    // shelf packing with blocks sorted by height, server items sharing a client id share the blocks
+    pub fn pack<'a>(
+        items: impl Iterator<Item = &'a Item>,
+        creatures: impl Iterator<Item = (Look, Direction, &'a Texture)>,
+        page_size: u32,
+        padding: u32,
+    ) -> Result<Self, AtlasFull> {
+        let mut blocks = Vec::new();
+        for item in items {
+            let textures = item.textures.composed();
+            let frames = textures.frames_count() as u32;
+            let frame_durations: Arc<[u32]> = item.sprite_layout.frame_durations.as_slice().into();
+            for pattern in textures.patterns() {
+                let texture = textures.get(pattern);
+                let mut entry = Self::block(texture, frames, page_size, padding)?;
+                entry.frame_durations = frame_durations.clone();
+                blocks.push((SpriteKey::Item(item.client_id, pattern), entry));
+            }
+        }
+        for (look, direction, texture) in creatures {
+            blocks.push((
+                SpriteKey::Creature(look, direction),
+                Self::block(texture, 1, page_size, padding)?,
+            ));
+        }
+
+        blocks.sort_by_key(|(key, entry)| (Reverse(entry.block_height()), *key));
+
+        let mut entries = HashMap::new();
+        let (mut page, mut x, mut y, mut shelf_height) = (0, 0, 0, 0);
+        for (key, mut entry) in blocks.into_iter() {
+            if x + entry.block_width() > page_size {
+                x = 0;
+                y += shelf_height;
+                shelf_height = 0;
+            }
+            if y + entry.block_height() > page_size {
+                page += 1;
+                x = 0;
+                y = 0;
+                shelf_height = 0;
+            }
+
+            entry.page = page;
+            entry.x = x + padding;
+            entry.y = y + padding;
+            x += entry.block_width();
+            shelf_height = shelf_height.max(entry.block_height());
+
+            entries.insert(key, entry);
+        }
+
+        Ok(Atlas {
+            page_size,
+            padding,
+            pages: page + 1,
+            entries,
+        })
+    }
+
+    fn block(
+        texture: &Texture,
+        frames: u32,
+        page_size: u32,
+        padding: u32,
+    ) -> Result<AtlasEntry, AtlasFull> {
+        let width = texture.width as u32;
+        let height = texture.height as u32;
+        let cell_width = width + 2 * padding;
+        let cell_height = height + 2 * padding;
+        if cell_width > page_size || cell_height > page_size {
+            return Err(AtlasFull);
+        }
+        let columns = frames.min(page_size / cell_width);
+        let rows = frames.div_ceil(columns);
+        if rows * cell_height > page_size {
+            return Err(AtlasFull);
+        }
+
+        Ok(AtlasEntry {
+            page: 0,
+            x: 0,
+            y: 0,
+            width,
+            height,
+            frames,
+            columns,
+            cell_width,
+            cell_height,
+            frame_durations: Arc::new([]),
+        })
+    }
+
+    pub fn entry(&self, key: &SpriteKey) -> Option<&AtlasEntry> {
+        match key {
+            SpriteKey::Item(client_id, pattern) => self
+                .entries
+                .get(&SpriteKey::Item(*client_id, pattern.frame(0))),
+            SpriteKey::Creature(..) => self.entries.get(key),
+        }
+    }
+
+    // items placed for the first time after the atlas was packed
+    pub fn misses(&self, chunk: &SceneChunk) -> bool {
+        chunk.tiles.values().flatten().any(|sprite| {
+            matches!(sprite.key, SpriteKey::Item(..)) && self.entry(&sprite.key).is_none()
+        })
+    }
+
+    // the frame shown at a time in milliseconds
+    pub fn frame(&self, key: &SpriteKey, time: u64) -> usize {
+        self.entry(key).map_or(0, |entry| {
+            frame_at(&entry.frame_durations, entry.frames as usize, time)
+        })
+    }
+
+    // frames of a block are laid out left to right, top to bottom
+    pub fn rect(&self, key: &SpriteKey, frame: usize) -> Option<AtlasRect> {
+        self.entry(key).map(|entry| {
+            let frame = frame as u32 % entry.frames;
+            AtlasRect {
+                page: entry.page,
+                x: entry.x + frame % entry.columns * entry.cell_width,
+                y: entry.y + frame / entry.columns * entry.cell_height,
+                width: entry.width,
+                height: entry.height,
+            }
+        })
+    }
+}
+
+impl AtlasEntry {
+    // the shader shows every frame for the same time, the mean of the phases
+    pub fn frame_duration(&self) -> u32 {
+        let cycle: u32 = (0..self.frames as usize)
+            .map(|frame| frame_duration(&self.frame_durations, frame))
+            .sum();
+        (cycle / self.frames.max(1)).max(1)
+    }
+
+    fn block_width(&self) -> u32 {
+        self.columns * self.cell_width
+    }
+
+    fn block_height(&self) -> u32 {
+        self.frames.div_ceil(self.columns) * self.cell_height
+    }
+}
@@ -0,0 +1,533 @@
+use super::super::{
+    atlas::{Atlas, AtlasFull},
+    overlay::{
+        grid_overlays, house_overlay, selection_overlay, spawn_overlays, zone_overlays, Overlay,
+        OverlayInstance,
+    },
+    picking::Pick,
+};
+use crate::project::{Project, ProjectData};
+use itertools::Itertools;
+use js_sys::Float32Array;
+use model::{
+    layout::TILE_SIZE,
+    minimap::{minimap_floors, MinimapFloor},
+    scene::{unproject, ChunkKey, Floors, Render, Scene, SpriteKey, View, ViewMode, MAX_LEVEL},
+    Position, Texture,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    cell::Cell,
+    collections::{BTreeMap, BTreeSet, HashSet},
+};
+use wasm_bindgen::{prelude::wasm_bindgen, Clamped, JsCast, JsValue};
+use web_sys::{CanvasRenderingContext2d, HtmlCanvasElement, ImageData};
+use webgl_matrix::Mat3;
+
+// pages are plain canvases, kept small enough for every browser
+const ATLAS_PAGE_SIZE: u32 = 2048;
+// sprites are drawn without smoothing, so nothing bleeds in from their neighbours
+const ATLAS_PADDING: u32 = 0;
+
+// zoomed out so far that a tile takes fewer screen pixels, floors are drawn from their minimaps
+const MINIMAP_TILE_SIZE: f32 = 4.;
+
+// fallback for browsers without WebGL2, it draws the same scene without the lighting pass
+#[wasm_bindgen]
+#[derive(Clone)]
+pub struct Canvas2DMapRenderer {
+    canvas: HtmlCanvasElement,
+    context: CanvasRenderingContext2d,
+    scene: Scene,
+    floors: Floors,
+    atlas: Atlas,
+    pages: Vec<HtmlCanvasElement>,
+    // (zones, houses) of every chunk
+    chunk_overlays: BTreeMap<ChunkKey, (Vec<OverlayInstance>, Vec<OverlayInstance>)>,
+    spawn_overlays: BTreeMap<u8, Vec<OverlayInstance>>,
+    overlays: HashSet<Overlay>,
+    selection: BTreeSet<Position>,
+    minimap: BTreeMap<u8, (MinimapFloor, HtmlCanvasElement)>,
+    time: Cell<f64>,
+}
+
+#[wasm_bindgen]
+#[derive(Debug, Serialize, Deserialize)]
+#[non_exhaustive]
+pub enum Canvas2DSetupError {
+    ContextCreation,
+    TextureAtlasFull,
+}
+
+#[wasm_bindgen]
+#[derive(Debug, Serialize, Deserialize)]
+#[non_exhaustive]
+pub enum Canvas2DRenderError {
+    Draw,
+    Transformation,
+}
+
+impl From<AtlasFull> for Canvas2DSetupError {
+    fn from(_: AtlasFull) -> Self {
+        Canvas2DSetupError::TextureAtlasFull
+    }
+}
+
+impl From<JsValue> for Canvas2DRenderError {
+    fn from(_: JsValue) -> Self {
+        Canvas2DRenderError::Draw
+    }
+}
+
+fn context_2d(canvas: &HtmlCanvasElement) -> Option<CanvasRenderingContext2d> {
+    canvas.get_context("2d").ok()??.dyn_into().ok()
+}
+
+// an offscreen canvas for atlas pages and minimaps
+fn create_canvas(width: u32, height: u32) -> Option<(HtmlCanvasElement, CanvasRenderingContext2d)> {
+    let canvas: HtmlCanvasElement = web_sys::window()?
+        .document()?
+        .create_element("canvas")
+        .ok()?
+        .dyn_into()
+        .ok()?;
+    canvas.set_width(width);
+    canvas.set_height(height);
+    let context = context_2d(&canvas)?;
+    Some((canvas, context))
+}
+
+fn put_rgba(
+    context: &CanvasRenderingContext2d,
+    rgba: &[u8],
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+) {
+    if width == 0 || height == 0 {
+        return;
+    }
+    if let Ok(image_data) =
+        ImageData::new_with_u8_clamped_array_and_sh(Clamped(rgba), width, height)
+    {
+        context.put_image_data(&image_data, x as f64, y as f64).ok();
+    }
+}
+
+#[wasm_bindgen]
+impl Canvas2DMapRenderer {
+    #[wasm_bindgen(constructor)]
+    pub fn new_wasm(
+        project: &Project,
+        canvas: HtmlCanvasElement,
+    ) -> Result<Canvas2DMapRenderer, JsValue> {
+        Canvas2DMapRenderer::new(project, canvas).map_err(|error| error.into())
+    }
+
+    #[wasm_bindgen(js_name = render)]
+    pub fn render_wasm(&self, transformation: &Float32Array) -> Result<(), JsValue> {
+        let mat3 = Mat3::try_from(transformation.to_vec())
+            .map_err(|_| Canvas2DRenderError::Transformation)?;
+        self.render(&self.scene, &self.view(mat3))
+            .map_err(|error| error.into())
+    }
+
+    // time in milliseconds, animated items show the frame for it on the next render
+    #[wasm_bindgen(js_name = setTime)]
+    pub fn set_time(&self, time: f64) {
+        self.time.set(time);
+    }
+
+    // x and y are canvas coordinates in CSS pixels, like offsetX and offsetY of mouse events
+    pub fn pick(
+        &self,
+        project: &Project,
+        x: f32,
+        y: f32,
+        transformation: &Float32Array,
+    ) -> Result<JsValue, JsValue> {
+        let mat3 = Mat3::try_from(transformation.to_vec())
+            .map_err(|_| Canvas2DRenderError::Transformation)?;
+        let clip_x = x / self.canvas.client_width() as f32 * 2. - 1.;
+        let clip_y = 1. - y / self.canvas.client_height() as f32 * 2.;
+
+        let Some((pixel_x, pixel_y)) = unproject(&mat3, clip_x, clip_y) else {
+            return Ok(JsValue::null());
+        };
+        let (position, entity) = self.scene.pick(
+            &project.data.assets.items,
+            &self.floors,
+            pixel_x / TILE_SIZE as f32,
+            pixel_y / TILE_SIZE as f32,
+        );
+        let tile = project.data.world.tiles().get(&position);
+        let pick = Pick::new(project, &position, tile, entity);
+
+        serde_wasm_bindgen::to_value(&pick).map_err(JsValue::from)
+    }
+
+    #[wasm_bindgen(getter, js_name = currentLevel)]
+    pub fn current_level(&self) -> u8 {
+        self.floors.current_level
+    }
+
+    #[wasm_bindgen(js_name = setCurrentLevel)]
+    pub fn set_current_level(&mut self, level: u8) {
+        self.floors.current_level = level.min(MAX_LEVEL);
+    }
+
+    #[wasm_bindgen(getter, js_name = viewMode)]
+    pub fn view_mode(&self) -> ViewMode {
+        self.floors.view_mode
+    }
+
+    #[wasm_bindgen(js_name = setViewMode)]
+    pub fn set_view_mode(&mut self, view_mode: ViewMode) {
+        self.floors.view_mode = view_mode;
+    }
+
+    #[wasm_bindgen(js_name = isOverlayVisible)]
+    pub fn is_overlay_visible(&self, overlay: Overlay) -> bool {
+        self.overlays.contains(&overlay)
+    }
+
+    #[wasm_bindgen(js_name = setOverlayVisible)]
+    pub fn set_overlay_visible(&mut self, overlay: Overlay, visible: bool) {
+        if visible {
+            self.overlays.insert(overlay);
+        } else {
+            self.overlays.remove(&overlay);
+        }
+    }
+
+    #[wasm_bindgen(js_name = isTileSelected)]
+    pub fn is_tile_selected(&self, x: u16, y: u16, z: u8) -> bool {
+        self.selection.contains(&Position(x, y, z))
+    }
+
+    #[wasm_bindgen(js_name = selectTile)]
+    pub fn select_tile(&mut self, x: u16, y: u16, z: u8) {
+        self.selection.insert(Position(x, y, z));
+    }
+
+    #[wasm_bindgen(js_name = deselectTile)]
+    pub fn deselect_tile(&mut self, x: u16, y: u16, z: u8) {
+        self.selection.remove(&Position(x, y, z));
+    }
+
+    #[wasm_bindgen(js_name = clearSelection)]
+    pub fn clear_selection(&mut self) {
+        self.selection.clear();
+    }
+
+    pub fn resize(&self, width: u32, height: u32) {
+        self.canvas.set_width(width);
+        self.canvas.set_height(height);
+    }
+
+    // pages and minimaps are only canvases, dropping them is enough to free them
+    pub fn dispose(&mut self) {
+        self.pages.clear();
+        self.minimap.clear();
+    }
+
+    // the chunk with the tile and its minimap floor are rebuilt
+    #[wasm_bindgen(js_name = updateTile)]
+    pub fn update_tile(&mut self, project: &Project, x: u16, y: u16, z: u8) {
+        let ProjectData { assets, world } = &project.data;
+        let position = Position(x, y, z);
+        let key = self
+            .scene
+            .update_tile(world, &assets.items, &assets.outfits, &position);
+        if self
+            .scene
+            .chunk(&key)
+            .is_some_and(|chunk| self.atlas.misses(chunk))
+        {
+            self.fill_pages(project).ok();
+        }
+        self.fill_chunk_overlays(project, &key);
+        self.fill_minimap(project, Some(z));
+    }
+}
+
+impl Canvas2DMapRenderer {
+    pub fn new(project: &Project, canvas: HtmlCanvasElement) -> Result<Self, Canvas2DSetupError> {
+        let context = context_2d(&canvas).ok_or(Canvas2DSetupError::ContextCreation)?;
+        let ProjectData { assets, world } = &project.data;
+
+        let mut renderer = Canvas2DMapRenderer {
+            canvas,
+            context,
+            scene: Scene::new(world, &assets.items, &assets.outfits),
+            floors: Floors::default(),
+            atlas: Atlas::default(),
+            pages: Vec::new(),
+            chunk_overlays: BTreeMap::new(),
+            spawn_overlays: BTreeMap::new(),
+            overlays: HashSet::from([Overlay::Selection]),
+            selection: BTreeSet::new(),
+            minimap: BTreeMap::new(),
+            time: Cell::new(0.),
+        };
+
+        renderer.fill_pages(project)?;
+        for key in renderer.scene.chunks().keys().copied().collect_vec() {
+            renderer.fill_chunk_overlays(project, &key);
+        }
+        for spawn in world.spawns() {
+            renderer
+                .spawn_overlays
+                .entry(spawn.position.z)
+                .or_default()
+                .extend(spawn_overlays(spawn));
+        }
+        renderer.fill_minimap(project, None);
+
+        Ok(renderer)
+    }
+
+    fn view(&self, transformation: Mat3) -> View {
+        View {
+            transformation,
+            width: self.canvas.width(),
+            height: self.canvas.height(),
+            floors: self.floors,
+        }
+    }
+
+    // every frame of every item pattern on the map, like the WebGL atlas
+    fn fill_pages(&mut self, project: &Project) -> Result<(), Canvas2DSetupError> {
+        let items = self
+            .scene
+            .client_items(&project.data.assets.items)
+            .collect_vec();
+        self.atlas = Atlas::pack(
+            items.iter().copied(),
+            self.scene.creature_textures(),
+            ATLAS_PAGE_SIZE,
+            ATLAS_PADDING,
+        )?;
+
+        let mut pages = Vec::new();
+        for _ in 0..self.atlas.pages {
+            pages.push(
+                create_canvas(self.atlas.page_size, self.atlas.page_size)
+                    .ok_or(Canvas2DSetupError::ContextCreation)?,
+            );
+        }
+
+        let upload = |key: &SpriteKey, frame: usize, texture: &Texture| {
+            let Some(rect) = self.atlas.rect(key, frame) else {
+                return;
+            };
+            let (_, context) = &pages[rect.page as usize];
+            put_rgba(
+                context,
+                &texture.rgba_bytes,
+                rect.x,
+                rect.y,
+                rect.width,
+                rect.height,
+            );
+        };
+        for item in items {
+            let textures = item.textures.composed();
+            for pattern in textures.patterns() {
+                let key = SpriteKey::Item(item.client_id, pattern);
+                for frame in 0..textures.frames_count() {
+                    upload(&key, frame, textures.get(pattern.frame(frame)));
+                }
+            }
+        }
+        for (look, direction, texture) in self.scene.creature_textures() {
+            upload(&SpriteKey::Creature(look, direction), 0, texture);
+        }
+
+        self.pages = pages.into_iter().map(|(page, _)| page).collect();
+        Ok(())
+    }
+
+    fn fill_chunk_overlays(&mut self, project: &Project, key: &ChunkKey) {
+        let tiles = project.data.world.tiles();
+        let mut zones = Vec::new();
+        let mut houses = Vec::new();
+        let positions = self.scene.chunk(key).map(|chunk| chunk.tiles.keys());
+        for position in positions.into_iter().flatten() {
+            let Some(tile) = tiles.get(position) else {
+                continue;
+            };
+            zones.extend(zone_overlays(position, tile));
+            houses.extend(house_overlay(position, tile));
+        }
+        self.chunk_overlays.insert(*key, (zones, houses));
+    }
+
+    // every floor, or only the given one
+    fn fill_minimap(&mut self, project: &Project, z: Option<u8>) {
+        let items = &project.data.assets.items;
+        let tiles = project
+            .data
+            .world
+            .tiles()
+            .iter()
+            .filter(|(position, _)| z.is_none_or(|z| position.z == z));
+        let floors = minimap_floors(tiles, |server_id| {
+            items.get(&server_id).and_then(|item| item.minimap_color)
+        });
+
+        if let Some(z) = z {
+            self.minimap.remove(&z);
+        }
+        for (z, floor) in floors {
+            let Some((canvas, context)) = create_canvas(floor.width, floor.height) else {
+                continue;
+            };
+            put_rgba(&context, &floor.rgba, 0, 0, floor.width, floor.height);
+            self.minimap.insert(z, (floor, canvas));
+        }
+    }
+
+    fn fill_overlay(&self, instance: &OverlayInstance) {
+        let [r, g, b, a] = instance.color;
+        let style = format!("rgba({}, {}, {}, {})", r, g, b, a as f64 / 255.);
+        #[allow(deprecated)]
+        self.context.set_fill_style(&JsValue::from(style));
+        let tile_size = TILE_SIZE as f64;
+        self.context.fill_rect(
+            instance.x as f64 * tile_size,
+            instance.y as f64 * tile_size,
+            instance.width as f64 * tile_size,
+            instance.height as f64 * tile_size,
+        );
+    }
+
+    // overlays belong to the current floor and are drawn over every floor of the map
+    fn render_overlays(&self, scene: &Scene, view: &View) {
+        let current_level = view.floors.current_level;
+        let bounds = view.bounds();
+
+        for (key, _) in scene.visible_chunks(current_level, bounds, 0.) {
+            let Some((zones, houses)) = self.chunk_overlays.get(key) else {
+                continue;
+            };
+            if self.overlays.contains(&Overlay::Zones) {
+                zones
+                    .iter()
+                    .for_each(|instance| self.fill_overlay(instance));
+            }
+            if self.overlays.contains(&Overlay::Houses) {
+                houses
+                    .iter()
+                    .for_each(|instance| self.fill_overlay(instance));
+            }
+        }
+
+        if self.overlays.contains(&Overlay::Spawns) {
+            for instance in self
+                .spawn_overlays
+                .get(&current_level)
+                .into_iter()
+                .flatten()
+            {
+                self.fill_overlay(instance);
+            }
+        }
+
+        if self.overlays.contains(&Overlay::Selection) {
+            for position in self.selection.iter() {
+                if view.floors.alpha(position.z).is_some() {
+                    self.fill_overlay(&selection_overlay(position, view.floors.offset(position.z)));
+                }
+            }
+        }
+
+        if let (true, Some(bounds)) = (self.overlays.contains(&Overlay::Grid), bounds) {
+            for instance in grid_overlays(&bounds) {
+                self.fill_overlay(&instance);
+            }
+        }
+    }
+}
+
+// sprites are drawn in map pixels, the canvas transform maps them to the screen
+impl Render for Canvas2DMapRenderer {
+    type Output = ();
+    type Error = Canvas2DRenderError;
+
+    fn render(&self, scene: &Scene, view: &View) -> Result<(), Canvas2DRenderError> {
+        let context = &self.context;
+        context.set_transform(1., 0., 0., 1., 0., 0.)?;
+        context.set_global_alpha(1.);
+        #[allow(deprecated)]
+        context.set_fill_style(&JsValue::from("black"));
+        context.fill_rect(0., 0., view.width as f64, view.height as f64);
+
+        // clip space to canvas pixels, with y going down
+        let [a, b, _, c, d, _, tx, ty, _] = view.transformation.map(|value| value as f64);
+        let (half_width, half_height) = (view.width as f64 / 2., view.height as f64 / 2.);
+        context.set_transform(
+            a * half_width,
+            -b * half_height,
+            c * half_width,
+            -d * half_height,
+            (tx + 1.) * half_width,
+            (1. - ty) * half_height,
+        )?;
+        context.set_image_smoothing_enabled(false);
+
+        let bounds = view.bounds();
+        let minimap_visible = view.tile_pixels() < MINIMAP_TILE_SIZE;
+        let time = self.time.get() as u64;
+        let tile_size = TILE_SIZE as f64;
+
+        // lower floors first
+        for (z, alpha) in view.floors.visible() {
+            let floor_offset = view.floors.offset(z);
+            let pixel_offset = floor_offset as f64 * tile_size;
+            context.set_global_alpha(alpha as f64);
+
+            let minimap = self.minimap.get(&z).filter(|_| minimap_visible);
+            if let Some((floor, canvas)) = minimap {
+                context.draw_image_with_html_canvas_element_and_dw_and_dh(
+                    canvas,
+                    floor.x as f64 * tile_size + pixel_offset,
+                    floor.y as f64 * tile_size + pixel_offset,
+                    floor.width as f64 * tile_size,
+                    floor.height as f64 * tile_size,
+                )?;
+                continue;
+            }
+
+            for (_, sprites) in scene.visible_tiles(z, bounds, floor_offset) {
+                for sprite in sprites {
+                    let frame = self.atlas.frame(&sprite.key, time);
+                    let Some(rect) = self.atlas.rect(&sprite.key, frame) else {
+                        continue;
+                    };
+                    if rect.width == 0 || rect.height == 0 {
+                        continue;
+                    }
+                    context
+                        .draw_image_with_html_canvas_element_and_sw_and_sh_and_dx_and_dy_and_dw_and_dh(
+                            &self.pages[rect.page as usize],
+                            rect.x as f64,
+                            rect.y as f64,
+                            rect.width as f64,
+                            rect.height as f64,
+                            sprite.x as f64 + pixel_offset,
+                            sprite.y as f64 + pixel_offset,
+                            rect.width as f64,
+                            rect.height as f64,
+                        )?;
+                }
+            }
+        }
+        context.set_global_alpha(1.);
+
+        self.render_overlays(scene, view);
+
+        Ok(())
+    }
+}
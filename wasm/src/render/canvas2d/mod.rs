@@ -0,0 +1,3 @@
+mod map_renderer;
+
+pub use map_renderer::Canvas2DMapRenderer;
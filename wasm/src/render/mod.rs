@@ -1,15 +1,8 @@
-use crate::project::Project;
-use web_sys::HtmlCanvasElement;
-use webgl_matrix::Mat3;
-
-pub trait Render<T, V>
-where
-    Self: Sized,
-{
-    fn new(project: &Project, canvas: HtmlCanvasElement) -> Result<Self, T>;
-    fn render(&self, transformation: &Mat3) -> Result<(), V>;
-}
-
+mod atlas;
+mod canvas2d;
+mod overlay;
+mod picking;
 mod webgl;
 
+pub use canvas2d::Canvas2DMapRenderer;
 pub use webgl::WebGLMapRenderer;
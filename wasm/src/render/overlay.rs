@@ -0,0 +1,155 @@
+use model::{
+    attributes::{House, TileFlags},
+    scene::ViewBounds,
+    Attribute, Position, Spawn, Tile,
+};
+use wasm_bindgen::prelude::wasm_bindgen;
+
+pub const OVERLAY_INSTANCE_SIZE: i32 = 20;
+
+// zoomed out that far the grid would cover the whole map
+const MAX_GRID_LINES: f32 = 512.;
+const GRID_LINE_WIDTH: f32 = 1. / 32.;
+
+const GRID_COLOR: [u8; 4] = [255, 255, 255, 48];
+const SELECTION_COLOR: [u8; 4] = [255, 255, 255, 96];
+const SPAWN_COLOR: [u8; 4] = [255, 0, 255, 32];
+const SPAWN_CENTER_COLOR: [u8; 4] = [255, 0, 255, 128];
+const HOUSE_ALPHA: u8 = 96;
+
+const ZONE_COLORS: [(u32, [u8; 4]); 4] = [
+    (TileFlags::PROTECTION_ZONE, [0, 255, 0, 80]),
+    (TileFlags::NO_PVP, [0, 128, 255, 80]),
+    (TileFlags::PVP_ZONE, [255, 0, 0, 80]),
+    (TileFlags::NO_LOGOUT, [255, 255, 0, 80]),
+];
+
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Overlay {
+    Grid,
+    Selection,
+    Zones,
+    Houses,
+    Spawns,
+}
+
+// a rect in tiles filled with a single color
+#[derive(Debug, Clone, Copy)]
+pub struct OverlayInstance {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+    pub color: [u8; 4],
+}
+
+impl OverlayInstance {
+    pub fn tile(x: f32, y: f32, color: [u8; 4]) -> Self {
+        OverlayInstance {
+            x,
+            y,
+            width: 1.,
+            height: 1.,
+            color,
+        }
+    }
+
+    // layout: rect (4 x f32), color (4 x u8)
+    pub fn write(&self, buffer: &mut Vec<u8>) {
+        for value in [self.x, self.y, self.width, self.height] {
+            buffer.extend_from_slice(&value.to_le_bytes());
+        }
+        buffer.extend_from_slice(&self.color);
+    }
+}
+
+pub fn zone_overlays(position: &Position, tile: &Tile) -> Vec<OverlayInstance> {
+    let Some(Attribute::TileFlags(flags)) = tile.attributes.get("tile_flags") else {
+        return Vec::new();
+    };
+    ZONE_COLORS
+        .iter()
+        .filter(|(flag, _)| flags.contains(*flag))
+        .map(|(_, color)| OverlayInstance::tile(position.x as f32, position.y as f32, *color))
+        .collect()
+}
+
+pub fn house_overlay(position: &Position, tile: &Tile) -> Option<OverlayInstance> {
+    match tile.attributes.get("house") {
+        Some(Attribute::House(House(house_id))) => Some(OverlayInstance::tile(
+            position.x as f32,
+            position.y as f32,
+            house_color(*house_id),
+        )),
+        _ => None,
+    }
+}
+
+pub fn spawn_overlays(spawn: &Spawn) -> [OverlayInstance; 2] {
+    let Position { x, y, .. } = spawn.position;
+    let radius = spawn.radius as f32;
+    [
+        OverlayInstance {
+            x: x as f32 - radius,
+            y: y as f32 - radius,
+            width: radius * 2. + 1.,
+            height: radius * 2. + 1.,
+            color: SPAWN_COLOR,
+        },
+        OverlayInstance::tile(x as f32, y as f32, SPAWN_CENTER_COLOR),
+    ]
+}
+
+// floor offset moves selected tiles of other floors where they are drawn
+pub fn selection_overlay(position: &Position, floor_offset: f32) -> OverlayInstance {
+    OverlayInstance::tile(
+        position.x as f32 + floor_offset,
+        position.y as f32 + floor_offset,
+        SELECTION_COLOR,
+    )
+}
+
+pub fn grid_overlays(bounds: &ViewBounds) -> Vec<OverlayInstance> {
+    let (min_x, min_y) = (bounds.min_x.floor().max(0.), bounds.min_y.floor().max(0.));
+    let (max_x, max_y) = (bounds.max_x.ceil().max(0.), bounds.max_y.ceil().max(0.));
+    if (max_x - min_x) + (max_y - min_y) > MAX_GRID_LINES {
+        return Vec::new();
+    }
+
+    let vertical = (min_x as u32..=max_x as u32).map(|x| OverlayInstance {
+        x: x as f32 - GRID_LINE_WIDTH / 2.,
+        y: min_y,
+        width: GRID_LINE_WIDTH,
+        height: max_y - min_y,
+        color: GRID_COLOR,
+    });
+    let horizontal = (min_y as u32..=max_y as u32).map(|y| OverlayInstance {
+        x: min_x,
+        y: y as f32 - GRID_LINE_WIDTH / 2.,
+        width: max_x - min_x,
+        height: GRID_LINE_WIDTH,
+        color: GRID_COLOR,
+    });
+    vertical.chain(horizontal).collect()
+}
+
+// neighbouring house ids get distant hues
+fn house_color(house_id: u32) -> [u8; 4] {
+    let hue = (house_id as f32 * 0.618_034).fract() * 6.;
+    let x = 1. - (hue % 2. - 1.).abs();
+    let (r, g, b) = match hue as u32 {
+        0 => (1., x, 0.),
+        1 => (x, 1., 0.),
+        2 => (0., 1., x),
+        3 => (0., x, 1.),
+        4 => (x, 0., 1.),
+        _ => (1., 0., x),
+    };
+    [
+        (r * 255.) as u8,
+        (g * 255.) as u8,
+        (b * 255.) as u8,
+        HOUSE_ALPHA,
+    ]
+}
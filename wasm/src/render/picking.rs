@@ -0,0 +1,76 @@
+use crate::project::Project;
+use model::{
+    attributes::{Count, Fluid, Item},
+    Attribute, Position, Tile,
+};
+use serde::Serialize;
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Pick {
+    pub x: u16,
+    pub y: u16,
+    pub z: u8,
+    // index in the tile entities of the top-most entity under the cursor
+    pub entity: Option<usize>,
+    // tile contents for the hover tooltip, in map order
+    pub items: Vec<PickedItem>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PickedItem {
+    pub entity: usize,
+    pub server_id: u16,
+    pub client_id: u16,
+    pub name: Option<String>,
+    pub count: Option<u8>,
+    pub fluid: Option<u8>,
+}
+
+impl Pick {
+    pub fn new(
+        project: &Project,
+        position: &Position,
+        tile: Option<&Tile>,
+        entity: Option<usize>,
+    ) -> Self {
+        let items = tile
+            .map(|tile| {
+                tile.entities
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(index, entity)| {
+                        let Some(Attribute::Item(Item(server_id))) = entity.attributes.get("item")
+                        else {
+                            return None;
+                        };
+                        let item = project.data.assets.items.get(server_id);
+                        Some(PickedItem {
+                            entity: index,
+                            server_id: *server_id,
+                            client_id: item.map_or(0, |item| item.client_id),
+                            name: item.and_then(|item| item.name.clone()),
+                            count: match entity.attributes.get("count") {
+                                Some(Attribute::Count(Count(count))) => Some(*count),
+                                _ => None,
+                            },
+                            fluid: match entity.attributes.get("fluid") {
+                                Some(Attribute::Fluid(Fluid(fluid))) => Some(*fluid),
+                                _ => None,
+                            },
+                        })
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Pick {
+            x: position.x,
+            y: position.y,
+            z: position.z,
+            entity,
+            items,
+        }
+    }
+}
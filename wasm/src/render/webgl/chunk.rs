@@ -0,0 +1,39 @@
+use super::overlay::OverlayLayer;
+use model::scene::ChunkKey;
+use web_sys::{WebGl2RenderingContext, WebGlBuffer, WebGlVertexArrayObject};
+
+// GPU copy of a scene chunk
+pub struct Chunk {
+    pub buffer: Option<WebGlBuffer>,
+    pub instances_count: usize,
+    // first instance of every tile column of the chunk, and the end of the last one
+    pub columns: Vec<usize>,
+    pub zones: OverlayLayer,
+    pub houses: OverlayLayer,
+}
+
+impl Chunk {
+    pub fn delete(&self, gl: &WebGl2RenderingContext) {
+        gl.delete_buffer(self.buffer.as_ref());
+        self.zones.delete(gl);
+        self.houses.delete(gl);
+    }
+}
+
+// visible chunks of a floor copied into one buffer in the order of the floor's tiles
+pub struct FloorInstances {
+    pub vao: Option<WebGlVertexArrayObject>,
+    pub buffer: Option<WebGlBuffer>,
+    // copied chunks, the buffer is filled again when the visible ones change
+    pub keys: Vec<ChunkKey>,
+    pub instances_count: usize,
+    // in bytes, the buffer only grows
+    pub capacity: usize,
+}
+
+impl FloorInstances {
+    pub fn delete(&self, gl: &WebGl2RenderingContext) {
+        gl.delete_vertex_array(self.vao.as_ref());
+        gl.delete_buffer(self.buffer.as_ref());
+    }
+}
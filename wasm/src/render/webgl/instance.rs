@@ -0,0 +1,37 @@
+// one sprite on the map, drawn as the shared unit quad scaled to its atlas rect
+pub const INSTANCE_SIZE: i32 = 28;
+
+pub const UNIT_QUAD: [f32; 12] = [0., 0., 0., 1., 1., 0., 1., 0., 0., 1., 1., 1.];
+
+pub const NO_TINT: [u8; 4] = [255, 255, 255, 255];
+
+#[derive(Debug, Clone, Copy)]
+pub struct Instance {
+    // top left corner in tiles
+    pub x: f32,
+    pub y: f32,
+    // first frame in the atlas page, padding excluded
+    pub rect: [u16; 4],
+    pub page: u8,
+    pub frames: u8,
+    pub columns: u8,
+    // milliseconds
+    pub frame_duration: u16,
+    pub tint: [u8; 4],
+}
+
+impl Instance {
+    // layout: position (2 x f32), rect (4 x u16), page, frames, columns, unused byte, tint (4 x u8),
+    // frame duration (u16), 2 unused bytes
+    pub fn write(&self, buffer: &mut Vec<u8>) {
+        buffer.extend_from_slice(&self.x.to_le_bytes());
+        buffer.extend_from_slice(&self.y.to_le_bytes());
+        for value in self.rect {
+            buffer.extend_from_slice(&value.to_le_bytes());
+        }
+        buffer.extend_from_slice(&[self.page, self.frames, self.columns, 0]);
+        buffer.extend_from_slice(&self.tint);
+        buffer.extend_from_slice(&self.frame_duration.to_le_bytes());
+        buffer.extend_from_slice(&[0, 0]);
+    }
+}
@@ -0,0 +1,173 @@
+use model::{attributes::Item, Attribute, Light, Position, Tile};
+use std::collections::HashMap;
+use web_sys::WebGlTexture;
+
+// brighter lights exist but the client doesn't let them reach further
+pub const MAX_LIGHT_RADIUS: u16 = 12;
+
+// one texel per tile with a dark border, so clamping outside of it gives just the ambient light
+pub struct LightMap {
+    pub x: u16,
+    pub y: u16,
+    pub width: u16,
+    pub height: u16,
+    pub rgba: Vec<u8>,
+    pub texture: Option<WebGlTexture>,
+}
+
+// the brightest light on the tile
+pub fn tile_light(tile: &Tile, light: &impl Fn(u16) -> Option<Light>) -> Option<Light> {
+    tile.entities
+        .iter()
+        .filter_map(|entity| match entity.attributes.get("item") {
+            Some(Attribute::Item(Item(item_id))) => light(*item_id),
+            _ => None,
+        })
+        .filter(|light| light.level > 0)
+        .max_by_key(|light| light.level)
+}
+
+// light fades linearly until it's level tiles away from the source
+fn intensity(light: &Light, dx: i32, dy: i32) -> f32 {
+    let radius = light.level.min(MAX_LIGHT_RADIUS) as f32;
+    let distance = ((dx * dx + dy * dy) as f32).sqrt();
+    (1. - distance / (radius + 1.)).max(0.)
+}
+
+impl LightMap {
+    pub fn build(lights: &[(u16, u16, Light)]) -> Option<Self> {
+        let reach = MAX_LIGHT_RADIUS + 1;
+        let min_x = lights.iter().map(|(x, ..)| *x).min()?.saturating_sub(reach);
+        let min_y = lights
+            .iter()
+            .map(|(_, y, _)| *y)
+            .min()?
+            .saturating_sub(reach);
+        let max_x = lights.iter().map(|(x, ..)| *x).max()?.saturating_add(reach);
+        let max_y = lights
+            .iter()
+            .map(|(_, y, _)| *y)
+            .max()?
+            .saturating_add(reach);
+
+        let mut light_map = LightMap {
+            x: min_x,
+            y: min_y,
+            width: max_x - min_x + 1,
+            height: max_y - min_y + 1,
+            rgba: vec![0; (max_x - min_x + 1) as usize * (max_y - min_y + 1) as usize * 4],
+            texture: None,
+        };
+        for (x, y, light) in lights.iter() {
+            light_map.add(*x, *y, light, None);
+        }
+        Some(light_map)
+    }
+
+    // a light can only grow the map when it's rebuilt
+    pub fn contains_light(&self, x: u16, y: u16) -> bool {
+        let reach = MAX_LIGHT_RADIUS + 1;
+        x >= self.x + reach
+            && y >= self.y + reach
+            && x + reach < self.x + self.width
+            && y + reach < self.y + self.height
+    }
+
+    // area is (min x, min y, max x, max y) of texels the light may change
+    fn add(&mut self, x: u16, y: u16, light: &Light, area: Option<(u16, u16, u16, u16)>) {
+        let [r, g, b] = light.rgb();
+        let radius = light.level.min(MAX_LIGHT_RADIUS);
+        let (min_x, min_y, max_x, max_y) = area.unwrap_or((0, 0, u16::MAX, u16::MAX));
+
+        for texel_y in y.saturating_sub(radius).max(min_y)..=y.saturating_add(radius).min(max_y) {
+            for texel_x in x.saturating_sub(radius).max(min_x)..=x.saturating_add(radius).min(max_x)
+            {
+                if texel_x < self.x
+                    || texel_y < self.y
+                    || texel_x - self.x >= self.width
+                    || texel_y - self.y >= self.height
+                {
+                    continue;
+                }
+                let intensity =
+                    intensity(light, texel_x as i32 - x as i32, texel_y as i32 - y as i32);
+                let index = ((texel_y - self.y) as usize * self.width as usize
+                    + (texel_x - self.x) as usize)
+                    * 4;
+                // overlapping lights keep the brightest channel like the client
+                for (channel, value) in [r, g, b].into_iter().enumerate() {
+                    let value = (value as f32 * intensity) as u8;
+                    self.rgba[index + channel] = self.rgba[index + channel].max(value);
+                }
+                self.rgba[index + 3] = 255;
+            }
+        }
+    }
+
+    // recomputes texels around a changed tile from the lights that can reach them,
+    // returns the changed area as (x, y, width, height) in texels
+    pub fn update(
+        &mut self,
+        tiles: &HashMap<Position, Tile>,
+        position: &Position,
+        light: &impl Fn(u16) -> Option<Light>,
+    ) -> Option<(u16, u16, u16, u16)> {
+        let Position { x, y, z, .. } = *position;
+        let min_x = x.saturating_sub(MAX_LIGHT_RADIUS).max(self.x);
+        let min_y = y.saturating_sub(MAX_LIGHT_RADIUS).max(self.y);
+        let max_x = x
+            .saturating_add(MAX_LIGHT_RADIUS)
+            .min(self.x + self.width - 1);
+        let max_y = y
+            .saturating_add(MAX_LIGHT_RADIUS)
+            .min(self.y + self.height - 1);
+        if min_x > max_x || min_y > max_y {
+            return None;
+        }
+
+        for texel_y in min_y..=max_y {
+            for texel_x in min_x..=max_x {
+                let index = ((texel_y - self.y) as usize * self.width as usize
+                    + (texel_x - self.x) as usize)
+                    * 4;
+                self.rgba[index..index + 4].fill(0);
+            }
+        }
+
+        for source_y in
+            min_y.saturating_sub(MAX_LIGHT_RADIUS)..=max_y.saturating_add(MAX_LIGHT_RADIUS)
+        {
+            for source_x in
+                min_x.saturating_sub(MAX_LIGHT_RADIUS)..=max_x.saturating_add(MAX_LIGHT_RADIUS)
+            {
+                let source = tiles
+                    .get(&Position(source_x, source_y, z))
+                    .and_then(|tile| tile_light(tile, light));
+                if let Some(source) = source {
+                    self.add(
+                        source_x,
+                        source_y,
+                        &source,
+                        Some((min_x, min_y, max_x, max_y)),
+                    );
+                }
+            }
+        }
+
+        Some((
+            min_x - self.x,
+            min_y - self.y,
+            max_x - min_x + 1,
+            max_y - min_y + 1,
+        ))
+    }
+
+    pub fn area_rgba(&self, (x, y, width, height): (u16, u16, u16, u16)) -> Vec<u8> {
+        (y..y + height)
+            .flat_map(|row| {
+                let start = (row as usize * self.width as usize + x as usize) * 4;
+                self.rgba[start..start + width as usize * 4].iter().copied()
+            })
+            .collect()
+    }
+}
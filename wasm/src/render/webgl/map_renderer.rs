@@ -1,34 +1,119 @@
-use super::super::Render;
-use crate::project::Project;
-use itertools::Itertools;
-use js_sys::Float32Array;
-use model::{attributes::Item, Attribute, Position};
+use super::{
+    super::{
+        atlas::{Atlas, AtlasFull, AtlasRect},
+        overlay::{
+            grid_overlays, house_overlay, selection_overlay, spawn_overlays, zone_overlays,
+            Overlay, OverlayInstance, OVERLAY_INSTANCE_SIZE,
+        },
+        picking::Pick,
+    },
+    chunk::{Chunk, FloorInstances},
+    instance::{Instance, INSTANCE_SIZE, NO_TINT, UNIT_QUAD},
+    lighting::{tile_light, LightMap},
+    minimap::MinimapTexture,
+    overlay::OverlayLayer,
+};
+use crate::project::{Project, ProjectData};
+use js_sys::{Float32Array, Uint8Array};
+use model::{
+    layout::TILE_SIZE,
+    minimap::{minimap_floors, minimap_rgba, tile_minimap_color, MinimapFloor},
+    scene::{
+        floor_order, unproject, ChunkKey, Floors, Render, Scene, SceneSprite, SpriteKey, View,
+        ViewMode, CHUNK_SIZE, MAX_LEVEL,
+    },
+    Light, Position,
+};
 use serde::{Deserialize, Serialize};
-use wasm_bindgen::{prelude::wasm_bindgen, JsCast, JsValue};
-use web_sys::{console, HtmlCanvasElement, WebGl2RenderingContext, WebGlProgram, WebGlShader};
+use std::{
+    cell::{Cell, RefCell},
+    collections::{BTreeMap, BTreeSet, HashSet},
+    rc::Rc,
+};
+use wasm_bindgen::{
+    prelude::{wasm_bindgen, Closure},
+    JsCast, JsValue,
+};
+use web_sys::{
+    console, Event, HtmlCanvasElement, WebGl2RenderingContext, WebGlBuffer, WebGlProgram,
+    WebGlShader, WebGlTexture, WebGlVertexArrayObject,
+};
 use webgl_matrix::Mat3;
 
 static VERTEX_SHADER: &str = include_str!("shaders/vertex.glsl");
 static FRAGMENT_SHADER: &str = include_str!("shaders/fragment.glsl");
+static OVERLAY_VERTEX_SHADER: &str = include_str!("shaders/overlay_vertex.glsl");
+static OVERLAY_FRAGMENT_SHADER: &str = include_str!("shaders/overlay_fragment.glsl");
+static MINIMAP_VERTEX_SHADER: &str = include_str!("shaders/minimap_vertex.glsl");
+static MINIMAP_FRAGMENT_SHADER: &str = include_str!("shaders/minimap_fragment.glsl");
+static LIGHTING_VERTEX_SHADER: &str = include_str!("shaders/lighting_vertex.glsl");
+static LIGHTING_FRAGMENT_SHADER: &str = include_str!("shaders/lighting_fragment.glsl");
+
+// canvas events the renderer listens to, kept to remove them on dispose
+type ContextListeners = Vec<(&'static str, Closure<dyn FnMut(Event)>)>;
 
 #[wasm_bindgen]
-#[derive(Clone)]
 pub struct WebGLMapRenderer {
     #[wasm_bindgen(skip)]
     pub program: WebGlProgram,
     #[wasm_bindgen(skip)]
+    pub overlay_program: WebGlProgram,
+    #[wasm_bindgen(skip)]
+    pub minimap_program: WebGlProgram,
+    #[wasm_bindgen(skip)]
+    pub lighting_program: WebGlProgram,
+    #[wasm_bindgen(skip)]
     pub gl: WebGl2RenderingContext,
-    vertices_count: usize,
+    scene: Scene,
+    floors: Floors,
+    atlas: Atlas,
+    atlas_texture: Option<WebGlTexture>,
+    chunks: BTreeMap<ChunkKey, Chunk>,
+    floor_instances: RefCell<BTreeMap<u8, FloorInstances>>,
+    quad_buffer: Option<WebGlBuffer>,
+    overlays: HashSet<Overlay>,
+    selection: BTreeSet<Position>,
+    selection_layer: Option<OverlayLayer>,
+    spawn_layers: BTreeMap<u8, OverlayLayer>,
+    // refilled while rendering, which only borrows the renderer
+    grid_layer: RefCell<Option<OverlayLayer>>,
+    minimap: BTreeMap<u8, MinimapTexture>,
+    minimap_vao: Option<WebGlVertexArrayObject>,
+    lighting: bool,
+    ambient_level: u8,
+    ambient_color: u8,
+    light_maps: BTreeMap<u8, LightMap>,
+    no_light_texture: Option<WebGlTexture>,
+    lighting_vao: Option<WebGlVertexArrayObject>,
+    context_lost: Rc<Cell<bool>>,
+    context_listeners: Rc<ContextListeners>,
+}
+
+#[wasm_bindgen]
+#[derive(Debug, Serialize)]
+pub struct RenderBenchmark {
+    #[wasm_bindgen(readonly)]
+    pub instances: usize,
+    #[wasm_bindgen(readonly, js_name = bufferSize)]
+    pub buffer_size: usize,
+    // the same sprites as six vertices with a position and a texture coordinate, as they were drawn before
+    #[wasm_bindgen(readonly, js_name = vertexBufferSize)]
+    pub vertex_buffer_size: usize,
+    #[wasm_bindgen(readonly, js_name = frameTime)]
+    pub frame_time: f64,
 }
 
 #[wasm_bindgen]
 #[derive(Debug, Serialize, Deserialize)]
 #[non_exhaustive]
 pub enum WebGLSetupError {
+    ContextCreation,
     ShaderCompilation,
     ShaderUnknown,
     ProgramCreation,
     ProgramUnknown,
+    TextureAtlasFull,
+    TextureUpload,
 }
 
 #[wasm_bindgen]
@@ -36,18 +121,33 @@ pub enum WebGLSetupError {
 #[non_exhaustive]
 pub enum WebGLRenderError {
     Something, // FIXME:
+    Transformation,
 }
 
-// FIXME: most of these consts should be dynamically calculated based on webgl limit on that specific machine
-const ATLAS_WIDTH: u16 = 16384;
-const ATLAS_HEIGHT: u16 = 16384;
+impl From<AtlasFull> for WebGLSetupError {
+    fn from(_: AtlasFull) -> Self {
+        WebGLSetupError::TextureAtlasFull
+    }
+}
+
+// pages follow MAX_TEXTURE_SIZE but are capped so a mostly empty last page doesn't waste too much memory
+const MIN_ATLAS_PAGE_SIZE: u32 = 2048;
+const MAX_ATLAS_PAGE_SIZE: u32 = 4096;
+
+// edges of every sprite are repeated around it so filtering doesn't bleed into its neighbours,
+// a mipmap level halves the padding so there are only as many levels as it lasts
+const TEXTURE_PADDING: u16 = 2;
+const ATLAS_MIPMAP_LEVELS: i32 = TEXTURE_PADDING.trailing_zeros() as i32 + 1;
 
-const TILE_SIZE: u16 = 32;
-const TEXTURE_SIZE: u16 = 128;
-const TEXTURE_PADDING: u16 = 32;
-const TEXTURES_IN_ROW: u16 = 128;
+// bytes of a sprite drawn without instancing
+const VERTEX_QUAD_SIZE: usize = 6 * 2 * 2 * 4;
 
-const FLOOR_LEVEL: u8 = 7;
+// zoomed out so far that a tile takes fewer screen pixels, floors are drawn from their minimaps
+const MINIMAP_TILE_SIZE: f32 = 4.;
+
+// a dark night with the white light of the client
+const DEFAULT_AMBIENT_LEVEL: u8 = 64;
+const DEFAULT_AMBIENT_COLOR: u8 = 215;
 
 #[wasm_bindgen]
 impl WebGLMapRenderer {
@@ -104,197 +204,1095 @@ impl WebGLMapRenderer {
         }
     }
 
-    fn fill_buffers(&mut self, project: &Project) -> Result<(), WebGLSetupError> {
-        let mut positions = Vec::new();
-        let mut texcoords = Vec::new();
+    // shaders are only needed until the program is linked
+    fn create_program(
+        gl: &WebGl2RenderingContext,
+        vertex_source: &str,
+        fragment_source: &str,
+    ) -> Result<WebGlProgram, WebGLSetupError> {
+        let vertex_shader =
+            Self::compile_shader(gl, WebGl2RenderingContext::VERTEX_SHADER, vertex_source)?;
+        let fragment_shader =
+            Self::compile_shader(gl, WebGl2RenderingContext::FRAGMENT_SHADER, fragment_source)?;
+        let program = Self::link_program(gl, &vertex_shader, &fragment_shader);
+        gl.delete_shader(Some(&vertex_shader));
+        gl.delete_shader(Some(&fragment_shader));
+        program
+    }
 
-        let current_level = 0;
+    // (sprites, overlays, minimap, lighting)
+    fn create_programs(
+        gl: &WebGl2RenderingContext,
+    ) -> Result<(WebGlProgram, WebGlProgram, WebGlProgram, WebGlProgram), WebGLSetupError> {
+        Ok((
+            Self::create_program(gl, VERTEX_SHADER, FRAGMENT_SHADER)?,
+            Self::create_program(gl, OVERLAY_VERTEX_SHADER, OVERLAY_FRAGMENT_SHADER)?,
+            Self::create_program(gl, MINIMAP_VERTEX_SHADER, MINIMAP_FRAGMENT_SHADER)?,
+            Self::create_program(gl, LIGHTING_VERTEX_SHADER, LIGHTING_FRAGMENT_SHADER)?,
+        ))
+    }
 
-        let tiles = project
-            .data
-            .world
-            .tiles()
-            .iter()
-            .filter(|(pos, _)| {
-                if current_level <= FLOOR_LEVEL {
-                    pos.z >= current_level && pos.z <= FLOOR_LEVEL
-                } else {
-                    pos.z > FLOOR_LEVEL && pos.z <= current_level
-                }
-            })
-            .sorted_by(
-                |(a, _), (b, _)| {
-                    if a.z == b.z {
-                        a.cmp(b)
-                    } else {
-                        b.z.cmp(&a.z)
-                    }
-                },
-            );
+    // everything built from the project, on creation and again after the context is restored
+    fn fill(&mut self, project: &Project) -> Result<(), WebGLSetupError> {
+        self.gl.use_program(Some(&self.program));
+        self.fill_quad();
+        self.fill_textures(project)?;
+        self.fill_chunks(project)?;
+        self.fill_minimap(project)?;
+        self.fill_light_maps(project)?;
+        self.fill_spawn_layers(project);
+        self.fill_selection_layer();
+        *self.grid_layer.get_mut() = Some(self.create_overlay_layer());
+        self.configure_webgl()
+    }
 
-        // TODO: instanced drawing
-        for (Position { x, y, z, .. }, tile) in tiles
-        // TODO: try BTreeMap that is already sorted
-        {
-            for entity in tile.entities.iter() {
-                if let Some(Attribute::Item(Item(item_id))) = entity.attributes.get("item") {
-                    if let Some(item) = project.data.assets.items.get(item_id) {
-                        let texture = item.texture();
-
-                        let offset = z - current_level;
-                        let tile_width = texture.width / TILE_SIZE;
-                        let tile_height = texture.height / TILE_SIZE;
-                        let x_b = (x + 1 + offset as u16) as f32;
-                        let y_b = (y + 1 + offset as u16) as f32;
-                        let x_a = x_b - tile_width as f32;
-                        let y_a = y_b - tile_height as f32;
-
-                        positions.append(&mut vec![
-                            x_a, y_a, x_a, y_b, x_b, y_a, x_b, y_a, x_a, y_b, x_b, y_b,
-                        ]);
-
-                        let tx = (item.id % TEXTURES_IN_ROW) as u16;
-                        let ty = (item.id as f32 / TEXTURES_IN_ROW as f32).floor() as u16;
-
-                        let tx_a = (tx * TEXTURE_SIZE + TEXTURE_PADDING) as f32;
-                        let ty_a = (ty * TEXTURE_SIZE + TEXTURE_PADDING) as f32;
-                        let tx_b = tx_a + texture.width as f32;
-                        let ty_b = ty_a + texture.height as f32;
-
-                        texcoords.append(&mut vec![
-                            tx_a, ty_a, tx_a, ty_b, tx_b, ty_a, tx_b, ty_a, tx_a, ty_b, tx_b, ty_b,
-                        ]);
-                    }
-                }
-            }
+    // handles of a lost context are already dead, so they are only dropped
+    fn forget_resources(&mut self) {
+        self.atlas_texture = None;
+        self.chunks.clear();
+        self.floor_instances.get_mut().clear();
+        self.quad_buffer = None;
+        self.selection_layer = None;
+        self.spawn_layers.clear();
+        *self.grid_layer.get_mut() = None;
+        self.minimap.clear();
+        self.minimap_vao = None;
+        self.light_maps.clear();
+        self.no_light_texture = None;
+        self.lighting_vao = None;
+    }
+
+    // every buffer, texture and vertex array the renderer created, programs are kept
+    fn delete_resources(&mut self) {
+        let gl = &self.gl;
+        gl.delete_texture(self.atlas_texture.as_ref());
+        for chunk in self.chunks.values() {
+            chunk.delete(gl);
+        }
+        for floor in self.floor_instances.get_mut().values() {
+            floor.delete(gl);
+        }
+        gl.delete_buffer(self.quad_buffer.as_ref());
+        let layers = self
+            .selection_layer
+            .iter()
+            .chain(self.spawn_layers.values())
+            .chain(self.grid_layer.get_mut().iter());
+        for layer in layers {
+            layer.delete(gl);
         }
+        for minimap in self.minimap.values() {
+            gl.delete_texture(minimap.texture.as_ref());
+        }
+        gl.delete_vertex_array(self.minimap_vao.as_ref());
+        for light_map in self.light_maps.values() {
+            gl.delete_texture(light_map.texture.as_ref());
+        }
+        gl.delete_texture(self.no_light_texture.as_ref());
+        gl.delete_vertex_array(self.lighting_vao.as_ref());
+        self.forget_resources();
+    }
 
-        self.vertices_count = positions.len() / 2;
+    // the browser only restores a lost context when the loss is prevented, the UI then calls restore
+    fn listen_context_loss(&mut self) {
+        let context_lost = self.context_lost.clone();
+        let on_context_lost = Closure::<dyn FnMut(Event)>::new(move |event: Event| {
+            event.prevent_default();
+            context_lost.set(true);
+        });
 
-        let position_location = self.gl.get_attrib_location(&self.program, "a_position");
-        let position_buffer = self.gl.create_buffer();
+        let event = "webglcontextlost";
+        self.canvas()
+            .add_event_listener_with_callback(event, on_context_lost.as_ref().unchecked_ref())
+            .ok();
+        self.context_listeners = Rc::new(vec![(event, on_context_lost)]);
+    }
+
+    fn configure_viewport(&self) {
+        let canvas = self.canvas();
+        let resolution_location = self.gl.get_uniform_location(&self.program, "u_resolution");
+        self.gl.uniform2f(
+            resolution_location.as_ref(),
+            canvas.width() as f32,
+            canvas.height() as f32,
+        );
+        self.gl
+            .viewport(0, 0, canvas.width() as i32, canvas.height() as i32);
+    }
+
+    fn fill_quad(&mut self) {
+        self.quad_buffer = self.gl.create_buffer();
         self.gl.bind_buffer(
             WebGl2RenderingContext::ARRAY_BUFFER,
-            position_buffer.as_ref(),
+            self.quad_buffer.as_ref(),
         );
         unsafe {
             self.gl.buffer_data_with_array_buffer_view(
                 WebGl2RenderingContext::ARRAY_BUFFER,
-                &Float32Array::view(&positions),
+                &Float32Array::view(&UNIT_QUAD),
                 WebGl2RenderingContext::STATIC_DRAW,
             );
         }
-        self.gl.vertex_attrib_pointer_with_i32(
-            position_location as u32,
+    }
+
+    fn fill_chunks(&mut self, project: &Project) -> Result<(), WebGLSetupError> {
+        let mut chunks = BTreeMap::new();
+        for key in self.scene.chunks().keys() {
+            let mut chunk = self.create_chunk();
+            self.fill_chunk(project, key, &mut chunk);
+            chunks.insert(*key, chunk);
+        }
+        self.chunks = chunks;
+        for floor in std::mem::take(self.floor_instances.get_mut()).values() {
+            floor.delete(&self.gl);
+        }
+
+        Ok(())
+    }
+
+    fn create_chunk(&self) -> Chunk {
+        Chunk {
+            buffer: self.gl.create_buffer(),
+            instances_count: 0,
+            columns: Vec::new(),
+            zones: self.create_overlay_layer(),
+            houses: self.create_overlay_layer(),
+        }
+    }
+
+    fn create_overlay_layer(&self) -> OverlayLayer {
+        OverlayLayer {
+            vao: self.gl.create_vertex_array(),
+            buffer: self.gl.create_buffer(),
+            instances_count: 0,
+        }
+    }
+
+    // floors are shifted in the shader, so chunks don't depend on the current floor
+    fn sprite_instance(&self, sprite: &SceneSprite) -> Option<Instance> {
+        let entry = self.atlas.entry(&sprite.key)?;
+        Some(Instance {
+            x: sprite.x as f32 / TILE_SIZE as f32,
+            y: sprite.y as f32 / TILE_SIZE as f32,
+            rect: [
+                entry.x as u16,
+                entry.y as u16,
+                entry.width as u16,
+                entry.height as u16,
+            ],
+            page: entry.page as u8,
+            frames: entry.frames as u8,
+            columns: entry.columns as u8,
+            frame_duration: entry.frame_duration().min(u16::MAX as u32) as u16,
+            tint: NO_TINT,
+        })
+    }
+
+    fn fill_chunk(&self, project: &Project, key: &ChunkKey, chunk: &mut Chunk) {
+        let tiles = project.data.world.tiles();
+
+        let mut instances = Vec::new();
+        let mut columns = Vec::new();
+        let mut zones = Vec::new();
+        let mut houses = Vec::new();
+
+        let scene_tiles = self.scene.chunk(key).map(|chunk| &chunk.tiles);
+        for (position, sprites) in scene_tiles.into_iter().flatten() {
+            let column = (position.x % CHUNK_SIZE) as usize;
+            columns.resize(
+                columns.len().max(column + 1),
+                instances.len() / INSTANCE_SIZE as usize,
+            );
+            for instance in sprites
+                .iter()
+                .filter_map(|sprite| self.sprite_instance(sprite))
+            {
+                instance.write(&mut instances);
+            }
+            let Some(tile) = tiles.get(position) else {
+                continue;
+            };
+            zones.extend(zone_overlays(position, tile));
+            houses.extend(house_overlay(position, tile));
+        }
+
+        self.fill_overlay_layer(&mut chunk.zones, &zones);
+        self.fill_overlay_layer(&mut chunk.houses, &houses);
+
+        chunk.instances_count = instances.len() / INSTANCE_SIZE as usize;
+        columns.resize(CHUNK_SIZE as usize + 1, chunk.instances_count);
+        chunk.columns = columns;
+
+        // chunks are only drawn through the buffers of their floors, so they don't need a vertex array
+        self.gl
+            .bind_buffer(WebGl2RenderingContext::ARRAY_BUFFER, chunk.buffer.as_ref());
+        unsafe {
+            self.gl.buffer_data_with_array_buffer_view(
+                WebGl2RenderingContext::ARRAY_BUFFER,
+                &Uint8Array::view(&instances),
+                WebGl2RenderingContext::STATIC_DRAW,
+            );
+        }
+        self.gl
+            .bind_buffer(WebGl2RenderingContext::ARRAY_BUFFER, None);
+    }
+
+    // copies the visible chunks of a floor into its buffer when they change
+    fn fill_floor_instances(&self, z: u8, keys: &[ChunkKey]) -> usize {
+        let mut floors = self.floor_instances.borrow_mut();
+        let floor = floors
+            .entry(z)
+            .or_insert_with(|| self.create_floor_instances());
+        if floor.keys == keys {
+            return floor.instances_count;
+        }
+
+        let chunks: Vec<_> = keys.iter().map(|key| self.chunks.get(key)).collect();
+        let instances_count = chunks
+            .iter()
+            .flatten()
+            .map(|chunk| chunk.instances_count)
+            .sum();
+        let size = instances_count * INSTANCE_SIZE as usize;
+        self.gl.bind_buffer(
+            WebGl2RenderingContext::COPY_WRITE_BUFFER,
+            floor.buffer.as_ref(),
+        );
+        if size > floor.capacity {
+            self.gl.buffer_data_with_i32(
+                WebGl2RenderingContext::COPY_WRITE_BUFFER,
+                size as i32,
+                WebGl2RenderingContext::DYNAMIC_DRAW,
+            );
+            floor.capacity = size;
+        }
+
+        let mut offset = 0;
+        for (index, column) in floor_order(keys) {
+            let Some(chunk) = chunks[index] else {
+                continue;
+            };
+            let (start, end) = (
+                chunk.columns[column as usize],
+                chunk.columns[column as usize + 1],
+            );
+            if start == end {
+                continue;
+            }
+            self.gl.bind_buffer(
+                WebGl2RenderingContext::COPY_READ_BUFFER,
+                chunk.buffer.as_ref(),
+            );
+            self.gl.copy_buffer_sub_data_with_i32_and_i32_and_i32(
+                WebGl2RenderingContext::COPY_READ_BUFFER,
+                WebGl2RenderingContext::COPY_WRITE_BUFFER,
+                (start * INSTANCE_SIZE as usize) as i32,
+                (offset * INSTANCE_SIZE as usize) as i32,
+                ((end - start) * INSTANCE_SIZE as usize) as i32,
+            );
+            offset += end - start;
+        }
+        self.gl
+            .bind_buffer(WebGl2RenderingContext::COPY_READ_BUFFER, None);
+        self.gl
+            .bind_buffer(WebGl2RenderingContext::COPY_WRITE_BUFFER, None);
+
+        floor.keys = keys.to_vec();
+        floor.instances_count = instances_count;
+        instances_count
+    }
+
+    fn create_floor_instances(&self) -> FloorInstances {
+        let floor = FloorInstances {
+            vao: self.gl.create_vertex_array(),
+            buffer: self.gl.create_buffer(),
+            keys: Vec::new(),
+            instances_count: 0,
+            capacity: 0,
+        };
+
+        self.gl.bind_vertex_array(floor.vao.as_ref());
+        self.bind_quad(&self.program);
+        self.gl
+            .bind_buffer(WebGl2RenderingContext::ARRAY_BUFFER, floor.buffer.as_ref());
+        let program = &self.program;
+        let stride = INSTANCE_SIZE;
+        self.instance_attribute(
+            program,
+            "a_position",
             2,
             WebGl2RenderingContext::FLOAT,
             false,
-            0,
+            stride,
             0,
         );
-        self.gl.enable_vertex_attrib_array(position_location as u32);
-
-        let texcoord_location = self.gl.get_attrib_location(&self.program, "a_texcoord");
-        let texcoord_buffer = self.gl.create_buffer();
-        self.gl.bind_buffer(
-            WebGl2RenderingContext::ARRAY_BUFFER,
-            texcoord_buffer.as_ref(),
+        self.instance_attribute(
+            program,
+            "a_rect",
+            4,
+            WebGl2RenderingContext::UNSIGNED_SHORT,
+            false,
+            stride,
+            8,
+        );
+        self.instance_attribute(
+            program,
+            "a_animation",
+            3,
+            WebGl2RenderingContext::UNSIGNED_BYTE,
+            false,
+            stride,
+            16,
+        );
+        self.instance_attribute(
+            program,
+            "a_tint",
+            4,
+            WebGl2RenderingContext::UNSIGNED_BYTE,
+            true,
+            stride,
+            20,
+        );
+        self.instance_attribute(
+            program,
+            "a_frame_duration",
+            1,
+            WebGl2RenderingContext::UNSIGNED_SHORT,
+            false,
+            stride,
+            24,
         );
+
+        self.gl.bind_vertex_array(None);
+        floor
+    }
+
+    fn fill_overlay_layer(&self, layer: &mut OverlayLayer, instances: &[OverlayInstance]) {
+        let mut buffer = Vec::new();
+        for instance in instances.iter() {
+            instance.write(&mut buffer);
+        }
+        layer.instances_count = instances.len();
+
+        self.gl.bind_vertex_array(layer.vao.as_ref());
+        self.bind_quad(&self.overlay_program);
+
+        self.gl
+            .bind_buffer(WebGl2RenderingContext::ARRAY_BUFFER, layer.buffer.as_ref());
         unsafe {
             self.gl.buffer_data_with_array_buffer_view(
                 WebGl2RenderingContext::ARRAY_BUFFER,
-                &Float32Array::view(&texcoords),
-                WebGl2RenderingContext::STATIC_DRAW,
+                &Uint8Array::view(&buffer),
+                WebGl2RenderingContext::DYNAMIC_DRAW,
             );
         }
+        let program = &self.overlay_program;
+        let stride = OVERLAY_INSTANCE_SIZE;
+        self.instance_attribute(
+            program,
+            "a_rect",
+            4,
+            WebGl2RenderingContext::FLOAT,
+            false,
+            stride,
+            0,
+        );
+        self.instance_attribute(
+            program,
+            "a_color",
+            4,
+            WebGl2RenderingContext::UNSIGNED_BYTE,
+            true,
+            stride,
+            16,
+        );
+
+        self.gl.bind_vertex_array(None);
+    }
+
+    fn fill_spawn_layers(&mut self, project: &Project) {
+        let mut spawns = BTreeMap::<u8, Vec<OverlayInstance>>::new();
+        for spawn in project.data.world.spawns() {
+            spawns
+                .entry(spawn.position.z)
+                .or_default()
+                .extend(spawn_overlays(spawn));
+        }
+
+        self.spawn_layers = spawns
+            .into_iter()
+            .map(|(z, instances)| {
+                let mut layer = self.create_overlay_layer();
+                self.fill_overlay_layer(&mut layer, &instances);
+                (z, layer)
+            })
+            .collect();
+    }
+
+    // selected tiles of every visible floor, so it's rebuilt when the floor changes
+    fn fill_selection_layer(&mut self) {
+        let instances: Vec<_> = self
+            .selection
+            .iter()
+            .filter(|position| self.floors.alpha(position.z).is_some())
+            .map(|position| selection_overlay(position, self.floors.offset(position.z)))
+            .collect();
+
+        let mut layer = self
+            .selection_layer
+            .take()
+            .unwrap_or_else(|| self.create_overlay_layer());
+        self.fill_overlay_layer(&mut layer, &instances);
+        self.selection_layer = Some(layer);
+    }
+
+    fn bind_quad(&self, program: &WebGlProgram) {
+        self.gl.bind_buffer(
+            WebGl2RenderingContext::ARRAY_BUFFER,
+            self.quad_buffer.as_ref(),
+        );
+        let corner_location = self.gl.get_attrib_location(program, "a_corner");
         self.gl.vertex_attrib_pointer_with_i32(
-            texcoord_location as u32,
+            corner_location as u32,
             2,
             WebGl2RenderingContext::FLOAT,
             false,
             0,
             0,
         );
-        self.gl.enable_vertex_attrib_array(texcoord_location as u32);
+        self.gl.enable_vertex_attrib_array(corner_location as u32);
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn instance_attribute(
+        &self,
+        program: &WebGlProgram,
+        attribute: &str,
+        size: i32,
+        r#type: u32,
+        normalized: bool,
+        stride: i32,
+        offset: i32,
+    ) {
+        let location = self.gl.get_attrib_location(program, attribute) as u32;
+        self.gl
+            .vertex_attrib_pointer_with_i32(location, size, r#type, normalized, stride, offset);
+        self.gl.vertex_attrib_divisor(location, 1);
+        self.gl.enable_vertex_attrib_array(location);
+    }
+
+    fn draw_overlay_layer(&self, layer: &OverlayLayer) {
+        if layer.instances_count == 0 {
+            return;
+        }
+        self.gl.bind_vertex_array(layer.vao.as_ref());
+        self.gl.draw_arrays_instanced(
+            WebGl2RenderingContext::TRIANGLES,
+            0,
+            6,
+            layer.instances_count as i32,
+        );
+    }
+
+    // overlays belong to the current floor and are drawn over every floor of the map
+    fn render_overlays(&self, view: &View) {
+        if self.overlays.is_empty() {
+            return;
+        }
+
+        let current_level = view.floors.current_level;
+        let bounds = view.bounds();
+        self.gl.use_program(Some(&self.overlay_program));
+        let transformation_location = self
+            .gl
+            .get_uniform_location(&self.overlay_program, "u_transformation");
+        self.gl.uniform_matrix3fv_with_f32_array(
+            transformation_location.as_ref(),
+            false,
+            &view.transformation,
+        );
+
+        let current_chunks = self
+            .scene
+            .visible_chunks(current_level, bounds, 0.)
+            .filter_map(|(key, _)| self.chunks.get(key));
+        for chunk in current_chunks {
+            if self.overlays.contains(&Overlay::Zones) {
+                self.draw_overlay_layer(&chunk.zones);
+            }
+            if self.overlays.contains(&Overlay::Houses) {
+                self.draw_overlay_layer(&chunk.houses);
+            }
+        }
+
+        if self.overlays.contains(&Overlay::Spawns) {
+            if let Some(layer) = self.spawn_layers.get(&current_level) {
+                self.draw_overlay_layer(layer);
+            }
+        }
+
+        if self.overlays.contains(&Overlay::Selection) {
+            if let Some(layer) = self.selection_layer.as_ref() {
+                self.draw_overlay_layer(layer);
+            }
+        }
+
+        // the grid follows the view, so it's the only layer built every frame
+        let mut grid_layer = self.grid_layer.borrow_mut();
+        if let (true, Some(bounds), Some(layer)) = (
+            self.overlays.contains(&Overlay::Grid),
+            bounds,
+            grid_layer.as_mut(),
+        ) {
+            self.fill_overlay_layer(layer, &grid_overlays(&bounds));
+            self.draw_overlay_layer(layer);
+        }
+
+        self.gl.bind_vertex_array(None);
+        self.gl.use_program(Some(&self.program));
+    }
+
+    fn max_texture_size(&self) -> u32 {
+        self.gl
+            .get_parameter(WebGl2RenderingContext::MAX_TEXTURE_SIZE)
+            .ok()
+            .and_then(|size| size.as_f64())
+            .unwrap_or(MIN_ATLAS_PAGE_SIZE as f64) as u32
+    }
+
+    fn minimap_floors(&self, project: &Project, z: Option<u8>) -> BTreeMap<u8, MinimapFloor> {
+        let items = &project.data.assets.items;
+        let tiles = project
+            .data
+            .world
+            .tiles()
+            .iter()
+            .filter(|(position, _)| z.is_none_or(|z| position.z == z));
+        minimap_floors(tiles, |server_id| {
+            items.get(&server_id).and_then(|item| item.minimap_color)
+        })
+    }
 
+    fn fill_minimap(&mut self, project: &Project) -> Result<(), WebGLSetupError> {
+        self.minimap_vao = self.gl.create_vertex_array();
+        self.gl.bind_vertex_array(self.minimap_vao.as_ref());
+        self.bind_quad(&self.minimap_program);
+        self.gl.bind_vertex_array(None);
+
+        for (z, floor) in self.minimap_floors(project, None) {
+            let minimap = self.create_minimap_texture(floor)?;
+            self.minimap.insert(z, minimap);
+        }
         Ok(())
     }
 
-    fn fill_textures(&self, project: &Project) -> Result<(), WebGLSetupError> {
+    // floor textures use the second texture unit, the first one keeps the atlas
+    fn create_floor_texture(
+        &self,
+        width: u32,
+        height: u32,
+        rgba: &[u8],
+        filter: u32,
+    ) -> Result<Option<WebGlTexture>, WebGLSetupError> {
+        let max_texture_size = self.max_texture_size();
+        if width > max_texture_size || height > max_texture_size {
+            return Ok(None);
+        }
+
         let texture = self.gl.create_texture();
-        self.gl.active_texture(WebGl2RenderingContext::TEXTURE0);
+        self.gl.active_texture(WebGl2RenderingContext::TEXTURE1);
         self.gl
             .bind_texture(WebGl2RenderingContext::TEXTURE_2D, texture.as_ref());
-        self.gl.tex_storage_2d(
+        self.gl
+            .tex_image_2d_with_i32_and_i32_and_i32_and_format_and_type_and_opt_u8_array(
+                WebGl2RenderingContext::TEXTURE_2D,
+                0,
+                WebGl2RenderingContext::RGBA as i32,
+                width as i32,
+                height as i32,
+                0,
+                WebGl2RenderingContext::RGBA,
+                WebGl2RenderingContext::UNSIGNED_BYTE,
+                Some(rgba),
+            )
+            .map_err(|_| WebGLSetupError::TextureUpload)?;
+        for (parameter, value) in [
+            (WebGl2RenderingContext::TEXTURE_MIN_FILTER, filter),
+            (WebGl2RenderingContext::TEXTURE_MAG_FILTER, filter),
+            (
+                WebGl2RenderingContext::TEXTURE_WRAP_S,
+                WebGl2RenderingContext::CLAMP_TO_EDGE,
+            ),
+            (
+                WebGl2RenderingContext::TEXTURE_WRAP_T,
+                WebGl2RenderingContext::CLAMP_TO_EDGE,
+            ),
+        ] {
+            self.gl
+                .tex_parameteri(WebGl2RenderingContext::TEXTURE_2D, parameter, value as i32);
+        }
+        self.gl.active_texture(WebGl2RenderingContext::TEXTURE0);
+
+        Ok(texture)
+    }
+
+    // (x, y, width, height) in texels
+    fn update_floor_texture(
+        gl: &WebGl2RenderingContext,
+        texture: &WebGlTexture,
+        (x, y, width, height): (u16, u16, u16, u16),
+        rgba: &[u8],
+    ) -> Result<(), WebGLSetupError> {
+        gl.active_texture(WebGl2RenderingContext::TEXTURE1);
+        gl.bind_texture(WebGl2RenderingContext::TEXTURE_2D, Some(texture));
+        gl.tex_sub_image_2d_with_i32_and_i32_and_u32_and_type_and_opt_u8_array(
             WebGl2RenderingContext::TEXTURE_2D,
-            5, // how many mipmaps, it's 5 because padding is 32 which is 2^5
-            WebGl2RenderingContext::RGBA8,
-            ATLAS_WIDTH as i32,
-            ATLAS_HEIGHT as i32,
+            0,
+            x as i32,
+            y as i32,
+            width as i32,
+            height as i32,
+            WebGl2RenderingContext::RGBA,
+            WebGl2RenderingContext::UNSIGNED_BYTE,
+            Some(rgba),
+        )
+        .map_err(|_| WebGLSetupError::TextureUpload)?;
+        gl.active_texture(WebGl2RenderingContext::TEXTURE0);
+        Ok(())
+    }
+
+    fn create_minimap_texture(
+        &self,
+        floor: MinimapFloor,
+    ) -> Result<MinimapTexture, WebGLSetupError> {
+        let texture = self.create_floor_texture(
+            floor.width,
+            floor.height,
+            &floor.rgba,
+            WebGl2RenderingContext::NEAREST,
+        )?;
+        Ok(MinimapTexture { floor, texture })
+    }
+
+    // a single pixel is uploaded, unless the tile is outside of the floor image and it has to grow
+    fn update_minimap(
+        &mut self,
+        project: &Project,
+        position: &Position,
+    ) -> Result<(), WebGLSetupError> {
+        let items = &project.data.assets.items;
+        let color = project.data.world.tiles().get(position).and_then(|tile| {
+            tile_minimap_color(tile, &|server_id| {
+                items.get(&server_id).and_then(|item| item.minimap_color)
+            })
+        });
+
+        let Position { x, y, z, .. } = *position;
+        if let Some(minimap) = self.minimap.get_mut(&z) {
+            if minimap.floor.set(x, y, color) {
+                let Some(texture) = minimap.texture.as_ref() else {
+                    return Ok(());
+                };
+                let (texel_x, texel_y) = (x - minimap.floor.x, y - minimap.floor.y);
+                let index =
+                    (texel_y as usize * minimap.floor.width as usize + texel_x as usize) * 4;
+                return Self::update_floor_texture(
+                    &self.gl,
+                    texture,
+                    (texel_x, texel_y, 1, 1),
+                    &minimap.floor.rgba[index..index + 4],
+                );
+            }
+        }
+        if color.is_none() {
+            return Ok(());
+        }
+
+        if let Some(minimap) = self.minimap.remove(&z) {
+            self.gl.delete_texture(minimap.texture.as_ref());
+        }
+        if let Some(floor) = self.minimap_floors(project, Some(z)).remove(&z) {
+            let minimap = self.create_minimap_texture(floor)?;
+            self.minimap.insert(z, minimap);
+        }
+        Ok(())
+    }
+
+    fn draw_minimap(
+        &self,
+        minimap: &MinimapTexture,
+        transformation: &Mat3,
+        floor_offset: f32,
+        alpha: f32,
+    ) {
+        let program = &self.minimap_program;
+        self.gl.use_program(Some(program));
+
+        let transformation_location = self.gl.get_uniform_location(program, "u_transformation");
+        self.gl.uniform_matrix3fv_with_f32_array(
+            transformation_location.as_ref(),
+            false,
+            transformation,
+        );
+        let floor_offset_location = self.gl.get_uniform_location(program, "u_floor_offset");
+        self.gl
+            .uniform1f(floor_offset_location.as_ref(), floor_offset);
+        let alpha_location = self.gl.get_uniform_location(program, "u_alpha");
+        self.gl.uniform1f(alpha_location.as_ref(), alpha);
+        let rect_location = self.gl.get_uniform_location(program, "u_rect");
+        let MinimapFloor {
+            x,
+            y,
+            width,
+            height,
+            ..
+        } = minimap.floor;
+        self.gl.uniform4f(
+            rect_location.as_ref(),
+            x as f32,
+            y as f32,
+            width as f32,
+            height as f32,
         );
 
-        for item in project.data.assets.items.values() {
-            let texture = item.texture();
+        self.gl.active_texture(WebGl2RenderingContext::TEXTURE1);
+        self.gl
+            .bind_texture(WebGl2RenderingContext::TEXTURE_2D, minimap.texture.as_ref());
+        self.gl.bind_vertex_array(self.minimap_vao.as_ref());
+        self.gl.draw_arrays(WebGl2RenderingContext::TRIANGLES, 0, 6);
+        self.gl.active_texture(WebGl2RenderingContext::TEXTURE0);
 
-            let tx = ((item.id % TEXTURES_IN_ROW) * TEXTURE_SIZE) as i32;
-            let ty =
-                ((item.id as f32 / TEXTURES_IN_ROW as f32).floor() as u16 * TEXTURE_SIZE) as i32;
+        self.gl.use_program(Some(&self.program));
+    }
 
-            self.gl
-                .tex_sub_image_2d_with_i32_and_i32_and_u32_and_type_and_opt_u8_array(
-                    WebGl2RenderingContext::TEXTURE_2D,
-                    0,
-                    tx,
-                    ty,
-                    (texture.width + 2 * TEXTURE_PADDING) as i32,
-                    (texture.height + 2 * TEXTURE_PADDING) as i32,
-                    WebGl2RenderingContext::RGBA,
-                    WebGl2RenderingContext::UNSIGNED_BYTE,
-                    Some(&texture.rgba_bytes(TEXTURE_PADDING as usize)),
-                )
-                .unwrap(); // FIXME: unwrap
+    fn light_sources(
+        &self,
+        project: &Project,
+        z: Option<u8>,
+    ) -> BTreeMap<u8, Vec<(u16, u16, Light)>> {
+        let items = &project.data.assets.items;
+        let mut lights = BTreeMap::<u8, Vec<_>>::new();
+        for (position, tile) in project.data.world.tiles().iter() {
+            if z.is_some_and(|z| position.z != z) {
+                continue;
+            }
+            let light = tile_light(tile, &|server_id| {
+                items.get(&server_id).and_then(|item| item.light)
+            });
+            if let Some(light) = light {
+                lights
+                    .entry(position.z)
+                    .or_default()
+                    .push((position.x, position.y, light));
+            }
+        }
+        lights
+    }
+
+    fn create_light_map(
+        &self,
+        lights: &[(u16, u16, Light)],
+    ) -> Result<Option<LightMap>, WebGLSetupError> {
+        let Some(mut light_map) = LightMap::build(lights) else {
+            return Ok(None);
+        };
+        light_map.texture = self.create_floor_texture(
+            light_map.width.into(),
+            light_map.height.into(),
+            &light_map.rgba,
+            WebGl2RenderingContext::LINEAR,
+        )?;
+        Ok(Some(light_map))
+    }
+
+    fn fill_light_maps(&mut self, project: &Project) -> Result<(), WebGLSetupError> {
+        self.lighting_vao = self.gl.create_vertex_array();
+        self.gl.bind_vertex_array(self.lighting_vao.as_ref());
+        self.bind_quad(&self.lighting_program);
+        self.gl.bind_vertex_array(None);
+
+        // floors without lights only get the ambient light
+        self.no_light_texture =
+            self.create_floor_texture(1, 1, &[0, 0, 0, 255], WebGl2RenderingContext::NEAREST)?;
+
+        for (z, lights) in self.light_sources(project, None) {
+            if let Some(light_map) = self.create_light_map(&lights)? {
+                self.light_maps.insert(z, light_map);
+            }
+        }
+        Ok(())
+    }
+
+    // lights reach a few tiles, only that area is recomputed unless the map has to grow
+    fn update_light_map(
+        &mut self,
+        project: &Project,
+        position: &Position,
+    ) -> Result<(), WebGLSetupError> {
+        let items = &project.data.assets.items;
+        let tiles = project.data.world.tiles();
+        let light_of = |server_id| items.get(&server_id).and_then(|item| item.light);
+        let light = tiles
+            .get(position)
+            .and_then(|tile| tile_light(tile, &light_of));
+
+        let Position { x, y, z, .. } = *position;
+        if let Some(light_map) = self.light_maps.get_mut(&z) {
+            if light.is_none() || light_map.contains_light(x, y) {
+                let Some(area) = light_map.update(tiles, position, &light_of) else {
+                    return Ok(());
+                };
+                if let Some(texture) = light_map.texture.as_ref() {
+                    Self::update_floor_texture(
+                        &self.gl,
+                        texture,
+                        area,
+                        &light_map.area_rgba(area),
+                    )?;
+                }
+                return Ok(());
+            }
+        } else if light.is_none() {
+            return Ok(());
+        }
+
+        if let Some(light_map) = self.light_maps.remove(&z) {
+            self.gl.delete_texture(light_map.texture.as_ref());
+        }
+        let lights = self.light_sources(project, Some(z)).remove(&z);
+        if let Some(light_map) = self.create_light_map(&lights.unwrap_or_default())? {
+            self.light_maps.insert(z, light_map);
+        }
+        Ok(())
+    }
+
+    // multiplies the drawn floors with the light of the current one
+    fn render_lighting(&self, view: &View) {
+        let Some(bounds) = view.bounds().filter(|_| self.lighting) else {
+            return;
+        };
+
+        let program = &self.lighting_program;
+        self.gl.use_program(Some(program));
+
+        let transformation_location = self.gl.get_uniform_location(program, "u_transformation");
+        self.gl.uniform_matrix3fv_with_f32_array(
+            transformation_location.as_ref(),
+            false,
+            &view.transformation,
+        );
+        let view_location = self.gl.get_uniform_location(program, "u_view");
+        self.gl.uniform4f(
+            view_location.as_ref(),
+            bounds.min_x,
+            bounds.min_y,
+            bounds.max_x - bounds.min_x,
+            bounds.max_y - bounds.min_y,
+        );
+
+        let ambient_location = self.gl.get_uniform_location(program, "u_ambient");
+        let [r, g, b, _] = minimap_rgba(self.ambient_color);
+        let level = self.ambient_level as f32 / 255.;
+        self.gl.uniform3f(
+            ambient_location.as_ref(),
+            r as f32 / 255. * level,
+            g as f32 / 255. * level,
+            b as f32 / 255. * level,
+        );
+
+        let light_map = self
+            .light_maps
+            .get(&view.floors.current_level)
+            .filter(|light_map| light_map.texture.is_some());
+        let (rect, texture) = match light_map {
+            Some(light_map) => (
+                [
+                    light_map.x as f32,
+                    light_map.y as f32,
+                    light_map.width as f32,
+                    light_map.height as f32,
+                ],
+                light_map.texture.as_ref(),
+            ),
+            None => ([0., 0., 1., 1.], self.no_light_texture.as_ref()),
+        };
+        let rect_location = self.gl.get_uniform_location(program, "u_rect");
+        self.gl
+            .uniform4f(rect_location.as_ref(), rect[0], rect[1], rect[2], rect[3]);
+
+        self.gl.active_texture(WebGl2RenderingContext::TEXTURE1);
+        self.gl
+            .bind_texture(WebGl2RenderingContext::TEXTURE_2D, texture);
+        self.gl.blend_func(
+            WebGl2RenderingContext::DST_COLOR,
+            WebGl2RenderingContext::ZERO,
+        );
+        self.gl.bind_vertex_array(self.lighting_vao.as_ref());
+        self.gl.draw_arrays(WebGl2RenderingContext::TRIANGLES, 0, 6);
+        self.gl.bind_vertex_array(None);
+        self.gl.blend_func(
+            WebGl2RenderingContext::ONE,
+            WebGl2RenderingContext::ONE_MINUS_SRC_ALPHA,
+        );
+        self.gl.active_texture(WebGl2RenderingContext::TEXTURE0);
+
+        self.gl.use_program(Some(&self.program));
+    }
+
+    fn fill_textures(&mut self, project: &Project) -> Result<(), WebGLSetupError> {
+        let max_texture_size = self.max_texture_size();
+        let max_pages = self
+            .gl
+            .get_parameter(WebGl2RenderingContext::MAX_ARRAY_TEXTURE_LAYERS)
+            .ok()
+            .and_then(|layers| layers.as_f64())
+            .unwrap_or(1.) as u32;
+
+        let items: Vec<_> = self
+            .scene
+            .client_items(&project.data.assets.items)
+            .collect();
+
+        self.atlas = Atlas::pack(
+            items.iter().copied(),
+            self.scene.creature_textures(),
+            max_texture_size.min(MAX_ATLAS_PAGE_SIZE),
+            TEXTURE_PADDING as u32,
+        )?;
+        if self.atlas.pages > max_pages {
+            return Err(WebGLSetupError::TextureAtlasFull);
+        }
+
+        self.atlas_texture = self.gl.create_texture();
+        let texture = self.atlas_texture.clone();
+        self.gl.active_texture(WebGl2RenderingContext::TEXTURE0);
+        self.gl
+            .bind_texture(WebGl2RenderingContext::TEXTURE_2D_ARRAY, texture.as_ref());
+        self.gl.tex_storage_3d(
+            WebGl2RenderingContext::TEXTURE_2D_ARRAY,
+            ATLAS_MIPMAP_LEVELS,
+            WebGl2RenderingContext::RGBA8,
+            self.atlas.page_size as i32,
+            self.atlas.page_size as i32,
+            self.atlas.pages as i32,
+        );
+
+        for item in items.into_iter() {
+            let textures = item.textures.composed();
+            for pattern in textures.patterns() {
+                let key = SpriteKey::Item(item.client_id, pattern);
+                for frame in 0..textures.frames_count() {
+                    let Some(rect) = self.atlas.rect(&key, frame) else {
+                        continue;
+                    };
+                    let texture = textures.get(pattern.frame(frame));
+                    self.upload_atlas_texture(rect, texture)?;
+                }
+            }
+        }
+
+        for (look, direction, texture) in self.scene.creature_textures() {
+            let Some(rect) = self.atlas.rect(&SpriteKey::Creature(look, direction), 0) else {
+                continue;
+            };
+            self.upload_atlas_texture(rect, texture)?;
         }
 
         let texture_location = self.gl.get_uniform_location(&self.program, "u_texture");
         self.gl.uniform1i(texture_location.as_ref(), 0);
-        self.gl.generate_mipmap(WebGl2RenderingContext::TEXTURE_2D);
         self.gl
-            .bind_texture(WebGl2RenderingContext::TEXTURE_2D, texture.as_ref());
+            .generate_mipmap(WebGl2RenderingContext::TEXTURE_2D_ARRAY);
+        self.gl
+            .bind_texture(WebGl2RenderingContext::TEXTURE_2D_ARRAY, texture.as_ref());
 
         self.gl.tex_parameteri(
-            WebGl2RenderingContext::TEXTURE_2D,
+            WebGl2RenderingContext::TEXTURE_2D_ARRAY,
             WebGl2RenderingContext::TEXTURE_MIN_FILTER,
             WebGl2RenderingContext::LINEAR_MIPMAP_LINEAR as i32,
         );
         self.gl.tex_parameteri(
-            WebGl2RenderingContext::TEXTURE_2D,
+            WebGl2RenderingContext::TEXTURE_2D_ARRAY,
             WebGl2RenderingContext::TEXTURE_MAG_FILTER,
-            WebGl2RenderingContext::LINEAR_MIPMAP_LINEAR as i32,
+            WebGl2RenderingContext::NEAREST as i32,
         );
 
         Ok(())
     }
 
+    // the rect excludes padding, which is drawn around it
+    fn upload_atlas_texture(
+        &self,
+        rect: AtlasRect,
+        texture: &model::Texture,
+    ) -> Result<(), WebGLSetupError> {
+        if rect.width == 0 || rect.height == 0 {
+            return Ok(());
+        }
+        self.gl
+            .tex_sub_image_3d_with_opt_u8_array(
+                WebGl2RenderingContext::TEXTURE_2D_ARRAY,
+                0,
+                (rect.x - self.atlas.padding) as i32,
+                (rect.y - self.atlas.padding) as i32,
+                rect.page as i32,
+                (rect.width + 2 * self.atlas.padding) as i32,
+                (rect.height + 2 * self.atlas.padding) as i32,
+                1,
+                WebGl2RenderingContext::RGBA,
+                WebGl2RenderingContext::UNSIGNED_BYTE,
+                Some(&texture.rgba_bytes(TEXTURE_PADDING as usize)),
+            )
+            .map_err(|_| WebGLSetupError::TextureUpload)
+    }
+
     fn configure_webgl(&self) -> Result<(), WebGLSetupError> {
         let tile_size_location = self.gl.get_uniform_location(&self.program, "u_tile_size");
         self.gl
             .uniform1f(tile_size_location.as_ref(), TILE_SIZE as f32);
 
-        let resolution_location = self.gl.get_uniform_location(&self.program, "u_resolution");
-        self.gl.uniform2f(
-            resolution_location.as_ref(),
-            self.canvas().width() as f32,
-            self.canvas().height() as f32,
+        let atlas_page_size_location = self
+            .gl
+            .get_uniform_location(&self.program, "u_atlas_page_size");
+        self.gl.uniform1f(
+            atlas_page_size_location.as_ref(),
+            self.atlas.page_size as f32,
         );
 
-        let texture_atlas_size_location = self
+        let atlas_padding_location = self
             .gl
-            .get_uniform_location(&self.program, "u_texture_atlas_size");
-        self.gl.uniform2f(
-            texture_atlas_size_location.as_ref(),
-            ATLAS_WIDTH as f32,
-            ATLAS_HEIGHT as f32,
-        );
+            .get_uniform_location(&self.program, "u_atlas_padding");
+        self.gl
+            .uniform1f(atlas_padding_location.as_ref(), self.atlas.padding as f32);
+
+        self.gl.use_program(Some(&self.overlay_program));
+        let overlay_tile_size_location = self
+            .gl
+            .get_uniform_location(&self.overlay_program, "u_tile_size");
+        self.gl
+            .uniform1f(overlay_tile_size_location.as_ref(), TILE_SIZE as f32);
+
+        self.gl.use_program(Some(&self.minimap_program));
+        let minimap_tile_size_location = self
+            .gl
+            .get_uniform_location(&self.minimap_program, "u_tile_size");
+        self.gl
+            .uniform1f(minimap_tile_size_location.as_ref(), TILE_SIZE as f32);
+        let minimap_texture_location = self
+            .gl
+            .get_uniform_location(&self.minimap_program, "u_texture");
+        self.gl.uniform1i(minimap_texture_location.as_ref(), 1);
+
+        self.gl.use_program(Some(&self.lighting_program));
+        let lighting_tile_size_location = self
+            .gl
+            .get_uniform_location(&self.lighting_program, "u_tile_size");
+        self.gl
+            .uniform1f(lighting_tile_size_location.as_ref(), TILE_SIZE as f32);
+        let lighting_texture_location = self
+            .gl
+            .get_uniform_location(&self.lighting_program, "u_texture");
+        self.gl.uniform1i(lighting_texture_location.as_ref(), 1);
+        self.gl.use_program(Some(&self.program));
 
         self.gl.enable(WebGl2RenderingContext::BLEND);
         self.gl.blend_func(
@@ -302,12 +1300,7 @@ impl WebGLMapRenderer {
             WebGl2RenderingContext::ONE_MINUS_SRC_ALPHA,
         );
 
-        self.gl.viewport(
-            0,
-            0,
-            self.canvas().width() as i32,
-            self.canvas().height() as i32,
-        );
+        self.configure_viewport();
 
         Ok(())
     }
@@ -324,13 +1317,273 @@ impl WebGLMapRenderer {
     // workaround because it's not possible to use wasm_bindgen macro directly on trait implementation methods
     #[wasm_bindgen(js_name = render)]
     pub fn render_wasm(&self, transformation: &Float32Array) -> Result<(), JsValue> {
-        let mat3 = Mat3::try_from(transformation.to_vec()).unwrap();
-        self.render(&mat3).map_err(|error| error.into())
+        let mat3 = Mat3::try_from(transformation.to_vec())
+            .map_err(|_| WebGLRenderError::Transformation)?;
+        self.render(&self.scene, &self.view(mat3))
+            .map_err(|error| error.into())
+    }
+
+    // time in milliseconds, animated items show the frame for it on the next render,
+    // the shader wraps it with integers so frames stay exact however long the page is open
+    #[wasm_bindgen(js_name = setTime)]
+    pub fn set_time(&self, time: f64) {
+        let time_location = self.gl.get_uniform_location(&self.program, "u_time");
+        self.gl
+            .uniform1ui(time_location.as_ref(), time as u64 as u32);
+    }
+
+    // renders the same view a number of times waiting for the GPU after each frame,
+    // buffer size counts instances of the whole map and the copies of the visible floors
+    pub fn benchmark(
+        &self,
+        transformation: &Float32Array,
+        frames: u32,
+    ) -> Result<RenderBenchmark, JsValue> {
+        let mat3 = Mat3::try_from(transformation.to_vec())
+            .map_err(|_| WebGLRenderError::Transformation)?;
+        let view = self.view(mat3);
+        let instances = self
+            .chunks
+            .values()
+            .map(|chunk| chunk.instances_count)
+            .sum::<usize>();
+
+        let start = js_sys::Date::now();
+        for _ in 0..frames {
+            self.render(&self.scene, &view).map_err(JsValue::from)?;
+            self.gl.finish();
+        }
+        let frame_time = (js_sys::Date::now() - start) / frames.max(1) as f64;
+        let floors_size = self
+            .floor_instances
+            .borrow()
+            .values()
+            .map(|floor| floor.capacity)
+            .sum::<usize>();
+
+        Ok(RenderBenchmark {
+            instances,
+            buffer_size: instances * INSTANCE_SIZE as usize + floors_size + UNIT_QUAD.len() * 4,
+            vertex_buffer_size: instances * VERTEX_QUAD_SIZE,
+            frame_time,
+        })
+    }
+
+    // x and y are canvas coordinates in CSS pixels, like offsetX and offsetY of mouse events
+    pub fn pick(
+        &self,
+        project: &Project,
+        x: f32,
+        y: f32,
+        transformation: &Float32Array,
+    ) -> Result<JsValue, JsValue> {
+        let mat3 = Mat3::try_from(transformation.to_vec())
+            .map_err(|_| WebGLRenderError::Transformation)?;
+        let canvas = self.canvas();
+        let clip_x = x / canvas.client_width() as f32 * 2. - 1.;
+        let clip_y = 1. - y / canvas.client_height() as f32 * 2.;
+
+        let Some((pixel_x, pixel_y)) = unproject(&mat3, clip_x, clip_y) else {
+            return Ok(JsValue::null());
+        };
+        let (position, entity) = self.scene.pick(
+            &project.data.assets.items,
+            &self.floors,
+            pixel_x / TILE_SIZE as f32,
+            pixel_y / TILE_SIZE as f32,
+        );
+        let tile = project.data.world.tiles().get(&position);
+        let pick = Pick::new(project, &position, tile, entity);
+
+        serde_wasm_bindgen::to_value(&pick).map_err(JsValue::from)
+    }
+
+    #[wasm_bindgen(getter, js_name = currentLevel)]
+    pub fn current_level(&self) -> u8 {
+        self.floors.current_level
+    }
+
+    // floors are picked when drawing, only the selection has to be rebuilt
+    #[wasm_bindgen(js_name = setCurrentLevel)]
+    pub fn set_current_level(&mut self, level: u8) {
+        self.floors.current_level = level.min(MAX_LEVEL);
+        self.fill_selection_layer();
+    }
+
+    #[wasm_bindgen(getter, js_name = viewMode)]
+    pub fn view_mode(&self) -> ViewMode {
+        self.floors.view_mode
+    }
+
+    #[wasm_bindgen(js_name = setViewMode)]
+    pub fn set_view_mode(&mut self, view_mode: ViewMode) {
+        self.floors.view_mode = view_mode;
+        self.fill_selection_layer();
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn lighting(&self) -> bool {
+        self.lighting
+    }
+
+    #[wasm_bindgen(js_name = setLighting)]
+    pub fn set_lighting(&mut self, lighting: bool) {
+        self.lighting = lighting;
+    }
+
+    // level goes from 0 (dark) to 255 (daylight), color is an index in the minimap palette
+    #[wasm_bindgen(js_name = setAmbientLight)]
+    pub fn set_ambient_light(&mut self, level: u8, color: u8) {
+        self.ambient_level = level;
+        self.ambient_color = color;
+    }
+
+    #[wasm_bindgen(js_name = isOverlayVisible)]
+    pub fn is_overlay_visible(&self, overlay: Overlay) -> bool {
+        self.overlays.contains(&overlay)
+    }
+
+    #[wasm_bindgen(js_name = setOverlayVisible)]
+    pub fn set_overlay_visible(&mut self, overlay: Overlay, visible: bool) {
+        if visible {
+            self.overlays.insert(overlay);
+        } else {
+            self.overlays.remove(&overlay);
+        }
+    }
+
+    #[wasm_bindgen(js_name = isTileSelected)]
+    pub fn is_tile_selected(&self, x: u16, y: u16, z: u8) -> bool {
+        self.selection.contains(&Position(x, y, z))
+    }
+
+    #[wasm_bindgen(js_name = selectTile)]
+    pub fn select_tile(&mut self, x: u16, y: u16, z: u8) {
+        if self.selection.insert(Position(x, y, z)) {
+            self.fill_selection_layer();
+        }
+    }
+
+    #[wasm_bindgen(js_name = deselectTile)]
+    pub fn deselect_tile(&mut self, x: u16, y: u16, z: u8) {
+        if self.selection.remove(&Position(x, y, z)) {
+            self.fill_selection_layer();
+        }
+    }
+
+    #[wasm_bindgen(js_name = clearSelection)]
+    pub fn clear_selection(&mut self) {
+        self.selection.clear();
+        self.fill_selection_layer();
+    }
+
+    // the drawing buffer follows the size the canvas is displayed at
+    pub fn resize(&self, width: u32, height: u32) {
+        let canvas = self.canvas();
+        canvas.set_width(width);
+        canvas.set_height(height);
+        self.configure_viewport();
+    }
+
+    #[wasm_bindgen(getter, js_name = contextLost)]
+    pub fn context_lost(&self) -> bool {
+        self.context_lost.get()
+    }
+
+    // rebuilds everything on webglcontextrestored, view state like the selection is kept
+    pub fn restore(&mut self, project: &Project) -> Result<(), JsValue> {
+        self.forget_resources();
+        let (program, overlay_program, minimap_program, lighting_program) =
+            Self::create_programs(&self.gl)?;
+        self.program = program;
+        self.overlay_program = overlay_program;
+        self.minimap_program = minimap_program;
+        self.lighting_program = lighting_program;
+        self.fill(project)?;
+        self.context_lost.set(false);
+        Ok(())
+    }
+
+    // frees GPU memory right away instead of waiting for the context to be collected,
+    // the renderer can't be used afterwards
+    pub fn dispose(&mut self) {
+        let canvas = self.canvas();
+        for (event, listener) in self.context_listeners.iter() {
+            canvas
+                .remove_event_listener_with_callback(event, listener.as_ref().unchecked_ref())
+                .ok();
+        }
+        self.context_listeners = Rc::new(Vec::new());
+
+        self.delete_resources();
+        for program in [
+            &self.program,
+            &self.overlay_program,
+            &self.minimap_program,
+            &self.lighting_program,
+        ] {
+            self.gl.delete_program(Some(program));
+        }
+    }
+
+    // only the chunk with the tile, its minimap pixel and the light around it are rebuilt
+    #[wasm_bindgen(js_name = updateTile)]
+    pub fn update_tile(&mut self, project: &Project, x: u16, y: u16, z: u8) -> Result<(), JsValue> {
+        let position = Position(x, y, z);
+        self.update_minimap(project, &position)?;
+        self.update_light_map(project, &position)?;
+
+        let ProjectData { assets, world } = &project.data;
+        let key = self
+            .scene
+            .update_tile(world, &assets.items, &assets.outfits, &position);
+
+        // packing new items moves every sprite, so every chunk is filled again
+        if self
+            .scene
+            .chunk(&key)
+            .is_some_and(|chunk| self.atlas.misses(chunk))
+        {
+            // the old atlas is only deleted once the new one is uploaded, so a failed packing
+            // keeps drawing the map as it was
+            let atlas = std::mem::take(&mut self.atlas);
+            let atlas_texture = self.atlas_texture.take();
+            if let Err(error) = self.fill_textures(project) {
+                self.gl.delete_texture(self.atlas_texture.as_ref());
+                self.atlas = atlas;
+                self.atlas_texture = atlas_texture;
+                self.gl.bind_texture(
+                    WebGl2RenderingContext::TEXTURE_2D_ARRAY,
+                    self.atlas_texture.as_ref(),
+                );
+                return Err(error.into());
+            }
+            self.gl.delete_texture(atlas_texture.as_ref());
+
+            let chunks = std::mem::take(&mut self.chunks);
+            self.fill_chunks(project)?;
+            for chunk in chunks.values() {
+                chunk.delete(&self.gl);
+            }
+            return Ok(());
+        }
+
+        let mut chunk = self
+            .chunks
+            .remove(&key)
+            .unwrap_or_else(|| self.create_chunk());
+        self.fill_chunk(project, &key, &mut chunk);
+        self.chunks.insert(key, chunk);
+        // copied again on the next render
+        if let Some(floor) = self.floor_instances.get_mut().get_mut(&key.z) {
+            floor.keys.clear();
+        }
+        Ok(())
     }
 }
 
-impl Render<WebGLSetupError, WebGLRenderError> for WebGLMapRenderer {
-    fn new(project: &Project, canvas: HtmlCanvasElement) -> Result<Self, WebGLSetupError> {
+impl WebGLMapRenderer {
+    pub fn new(project: &Project, canvas: HtmlCanvasElement) -> Result<Self, WebGLSetupError> {
         let opts = js_sys::Object::new();
         js_sys::Reflect::set(
             &opts,
@@ -339,46 +1592,85 @@ impl Render<WebGLSetupError, WebGLRenderError> for WebGLMapRenderer {
         )
         .unwrap();
 
-        // FIXME: all unwraps
         let gl = canvas
             .get_context_with_context_options("webgl2", &opts)
-            .unwrap()
-            .unwrap()
-            .dyn_into::<web_sys::WebGl2RenderingContext>()
-            .unwrap();
-
-        let vertex_shader =
-            Self::compile_shader(&gl, WebGl2RenderingContext::VERTEX_SHADER, VERTEX_SHADER)?;
-        let fragment_shader = Self::compile_shader(
-            &gl,
-            WebGl2RenderingContext::FRAGMENT_SHADER,
-            FRAGMENT_SHADER,
-        )?;
+            .ok()
+            .flatten()
+            .and_then(|context| context.dyn_into::<WebGl2RenderingContext>().ok())
+            .ok_or(WebGLSetupError::ContextCreation)?;
 
-        let program = Self::link_program(&gl, &vertex_shader, &fragment_shader)?;
-        gl.use_program(Some(&program));
+        let (program, overlay_program, minimap_program, lighting_program) =
+            Self::create_programs(&gl)?;
 
         let mut renderer = WebGLMapRenderer {
             program,
+            overlay_program,
+            minimap_program,
+            lighting_program,
             gl,
-            vertices_count: 0,
+            scene: Scene::new(
+                &project.data.world,
+                &project.data.assets.items,
+                &project.data.assets.outfits,
+            ),
+            floors: Floors::default(),
+            atlas: Atlas::default(),
+            atlas_texture: None,
+            chunks: BTreeMap::new(),
+            floor_instances: RefCell::new(BTreeMap::new()),
+            quad_buffer: None,
+            overlays: HashSet::from([Overlay::Selection]),
+            selection: BTreeSet::new(),
+            selection_layer: None,
+            spawn_layers: BTreeMap::new(),
+            grid_layer: RefCell::new(None),
+            minimap: BTreeMap::new(),
+            minimap_vao: None,
+            lighting: false,
+            ambient_level: DEFAULT_AMBIENT_LEVEL,
+            ambient_color: DEFAULT_AMBIENT_COLOR,
+            light_maps: BTreeMap::new(),
+            no_light_texture: None,
+            lighting_vao: None,
+            context_lost: Rc::new(Cell::new(false)),
+            context_listeners: Rc::new(Vec::new()),
         };
 
-        renderer.fill_buffers(project)?;
-        renderer.fill_textures(project)?;
-        renderer.configure_webgl()?;
+        renderer.listen_context_loss();
+        renderer.fill(project)?;
 
         Ok(renderer)
     }
 
-    fn render(&self, transformation: &Mat3) -> Result<(), WebGLRenderError> {
+    fn view(&self, transformation: Mat3) -> View {
+        let canvas = self.canvas();
+        View {
+            transformation,
+            width: canvas.width(),
+            height: canvas.height(),
+            floors: self.floors,
+        }
+    }
+}
+
+// the scene is uploaded in chunks when the renderer is created, only the view changes between frames
+impl Render for WebGLMapRenderer {
+    type Output = ();
+    type Error = WebGLRenderError;
+
+    fn render(&self, scene: &Scene, view: &View) -> Result<(), WebGLRenderError> {
+        // nothing can be drawn until the context is restored
+        if self.context_lost.get() {
+            return Ok(());
+        }
+
         let transformation_location = self
             .gl
             .get_uniform_location(&self.program, "u_transformation");
         self.gl.uniform_matrix3fv_with_f32_array(
             transformation_location.as_ref(),
             false,
-            transformation,
+            &view.transformation,
         );
 
         self.gl.clear(
@@ -386,11 +1678,55 @@ impl Render<WebGLSetupError, WebGLRenderError> for WebGLMapRenderer {
         );
         self.gl.clear_color(0., 0., 0., 1.);
 
-        self.gl.draw_arrays(
-            WebGl2RenderingContext::TRIANGLES,
-            0,
-            self.vertices_count as i32,
-        );
+        let bounds = view.bounds();
+        let floor_offset_location = self
+            .gl
+            .get_uniform_location(&self.program, "u_floor_offset");
+        let alpha_location = self.gl.get_uniform_location(&self.program, "u_alpha");
+
+        let minimap_visible = view.tile_pixels() < MINIMAP_TILE_SIZE;
+
+        for (z, alpha) in view.floors.visible() {
+            let floor_offset = view.floors.offset(z);
+
+            let minimap = self
+                .minimap
+                .get(&z)
+                .filter(|minimap| minimap_visible && minimap.texture.is_some());
+            if let Some(minimap) = minimap {
+                self.draw_minimap(minimap, &view.transformation, floor_offset, alpha);
+                continue;
+            }
+
+            self.gl
+                .uniform1f(floor_offset_location.as_ref(), floor_offset);
+            self.gl.uniform1f(alpha_location.as_ref(), alpha);
+
+            let keys: Vec<_> = scene
+                .visible_chunks(z, bounds, floor_offset)
+                .map(|(key, _)| *key)
+                .collect();
+            let instances_count = self.fill_floor_instances(z, &keys);
+            if instances_count == 0 {
+                continue;
+            }
+
+            let floors = self.floor_instances.borrow();
+            let Some(floor) = floors.get(&z) else {
+                continue;
+            };
+            self.gl.bind_vertex_array(floor.vao.as_ref());
+            self.gl.draw_arrays_instanced(
+                WebGl2RenderingContext::TRIANGLES,
+                0,
+                6,
+                instances_count as i32,
+            );
+        }
+        self.gl.bind_vertex_array(None);
+
+        self.render_lighting(view);
+        self.render_overlays(view);
 
         Ok(())
     }
@@ -0,0 +1,8 @@
+use model::minimap::MinimapFloor;
+use web_sys::WebGlTexture;
+
+// floors bigger than MAX_TEXTURE_SIZE have no texture and keep drawing sprites
+pub struct MinimapTexture {
+    pub floor: MinimapFloor,
+    pub texture: Option<WebGlTexture>,
+}
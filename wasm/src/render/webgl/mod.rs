@@ -1,3 +1,8 @@
+mod chunk;
+mod instance;
+mod lighting;
 mod map_renderer;
+mod minimap;
+mod overlay;
 
 pub use map_renderer::WebGLMapRenderer;
@@ -0,0 +1,14 @@
+use web_sys::{WebGl2RenderingContext, WebGlBuffer, WebGlVertexArrayObject};
+
+pub struct OverlayLayer {
+    pub vao: Option<WebGlVertexArrayObject>,
+    pub buffer: Option<WebGlBuffer>,
+    pub instances_count: usize,
+}
+
+impl OverlayLayer {
+    pub fn delete(&self, gl: &WebGl2RenderingContext) {
+        gl.delete_vertex_array(self.vao.as_ref());
+        gl.delete_buffer(self.buffer.as_ref());
+    }
+}
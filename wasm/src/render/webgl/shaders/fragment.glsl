@@ -1,15 +1,21 @@
 #version 300 es
 
-precision mediump float;
+precision highp float;
+precision highp sampler2DArray;
 
-uniform vec2 u_texture_atlas_size;
-uniform sampler2D u_texture;
+uniform float u_atlas_page_size;
+uniform sampler2DArray u_texture;
+uniform float u_alpha;
 
 in vec2 v_texcoord;
+flat in float v_page;
+in vec4 v_tint;
 
 out vec4 color;
 
 void main() {
-    vec2 clip_space = v_texcoord / u_texture_atlas_size;
-    color = texture(u_texture, clip_space);
+    vec2 clip_space = v_texcoord / u_atlas_page_size;
+    // premultiplied alpha, so the whole color fades
+    vec4 tint = vec4(v_tint.rgb * v_tint.a, v_tint.a);
+    color = texture(u_texture, vec3(clip_space, v_page)) * tint * u_alpha;
 }
@@ -0,0 +1,15 @@
+#version 300 es
+
+precision highp float;
+
+uniform sampler2D u_texture;
+uniform vec3 u_ambient;
+
+in vec2 v_texcoord;
+
+out vec4 color;
+
+void main() {
+    // multiplied with what is already drawn, full light leaves it unchanged
+    color = vec4(max(u_ambient, texture(u_texture, v_texcoord).rgb), 1);
+}
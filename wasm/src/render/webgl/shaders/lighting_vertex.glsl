@@ -0,0 +1,21 @@
+#version 300 es
+
+precision highp float;
+
+uniform float u_tile_size;
+uniform mat3 u_transformation;
+// (x, y, width, height) in tiles of the visible part of the map
+uniform vec4 u_view;
+// (x, y, width, height) in tiles of the light map
+uniform vec4 u_rect;
+
+// corner of the shared unit quad
+in vec2 a_corner;
+
+out vec2 v_texcoord;
+
+void main() {
+    vec2 tile_position = u_view.xy + a_corner * u_view.zw;
+    gl_Position = vec4((u_transformation * vec3(tile_position * vec2(u_tile_size), 1)).xy, 0, 1);
+    v_texcoord = (tile_position - u_rect.xy) / u_rect.zw;
+}
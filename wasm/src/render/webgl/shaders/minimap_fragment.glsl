@@ -0,0 +1,15 @@
+#version 300 es
+
+precision highp float;
+
+uniform sampler2D u_texture;
+uniform float u_alpha;
+
+in vec2 v_texcoord;
+
+out vec4 color;
+
+void main() {
+    // minimap pixels are either opaque or empty, so they are premultiplied already
+    color = texture(u_texture, v_texcoord) * u_alpha;
+}
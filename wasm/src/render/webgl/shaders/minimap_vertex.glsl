@@ -0,0 +1,20 @@
+#version 300 es
+
+precision highp float;
+
+uniform float u_tile_size;
+uniform mat3 u_transformation;
+uniform float u_floor_offset;
+// (x, y, width, height) of the floor image in tiles
+uniform vec4 u_rect;
+
+// corner of the shared unit quad
+in vec2 a_corner;
+
+out vec2 v_texcoord;
+
+void main() {
+    vec2 pixel_position = (u_rect.xy + vec2(u_floor_offset) + a_corner * u_rect.zw) * vec2(u_tile_size);
+    gl_Position = vec4((u_transformation * vec3(pixel_position, 1)).xy, 0, 1);
+    v_texcoord = a_corner;
+}
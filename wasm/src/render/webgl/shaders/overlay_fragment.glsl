@@ -0,0 +1,12 @@
+#version 300 es
+
+precision highp float;
+
+in vec4 v_color;
+
+out vec4 color;
+
+void main() {
+    // premultiplied alpha like the map sprites
+    color = vec4(v_color.rgb * v_color.a, v_color.a);
+}
@@ -0,0 +1,20 @@
+#version 300 es
+
+precision highp float;
+
+uniform float u_tile_size;
+uniform mat3 u_transformation;
+
+// corner of the shared unit quad
+in vec2 a_corner;
+// per instance: (x, y, width, height) in tiles
+in vec4 a_rect;
+in vec4 a_color;
+
+out vec4 v_color;
+
+void main() {
+    vec2 pixel_position = (a_rect.xy + a_corner * a_rect.zw) * vec2(u_tile_size);
+    gl_Position = vec4((u_transformation * vec3(pixel_position, 1)).xy, 0, 1);
+    v_color = a_color;
+}
@@ -1,18 +1,42 @@
 #version 300 es
 
-precision mediump float;
+precision highp float;
 
 uniform float u_tile_size;
 uniform vec2 u_resolution;
 uniform mat3 u_transformation;
+uniform float u_floor_offset;
+uniform float u_atlas_padding;
+// milliseconds, wrapped around by the integer division
+uniform uint u_time;
 
+// corner of the shared unit quad
+in vec2 a_corner;
+// per instance: top left corner in tiles
 in vec2 a_position;
-in vec2 a_texcoord;
+// per instance: (x, y, width, height) of the first frame in the atlas page
+in vec4 a_rect;
+// per instance: (page, frames count, columns of the frames grid)
+in vec3 a_animation;
+in vec4 a_tint;
+// per instance: milliseconds of every frame
+in float a_frame_duration;
 
 out vec2 v_texcoord;
+flat out float v_page;
+out vec4 v_tint;
 
 void main() {
-    vec2 pixel_position = a_position * vec2(u_tile_size);
+    vec2 size = a_rect.zw;
+    vec2 pixel_position = (a_position + vec2(u_floor_offset)) * vec2(u_tile_size) + a_corner * size;
     gl_Position = vec4((u_transformation * vec3(pixel_position, 1)).xy, 0, 1);
-    v_texcoord = a_texcoord;
+
+    uint frame_index = u_time / uint(max(a_frame_duration, 1.0)) % uint(a_animation.y);
+    float frame = float(frame_index);
+    float row = floor((frame + 0.5) / a_animation.z);
+    float column = frame - row * a_animation.z;
+    vec2 cell = size + vec2(2.0 * u_atlas_padding);
+    v_texcoord = a_rect.xy + vec2(column, row) * cell + a_corner * size;
+    v_page = a_animation.x;
+    v_tint = a_tint;
 }
@@ -1,40 +1,65 @@
-use super::Message;
+use super::{serialize, Error, Message};
 use futures::Stream;
 use js_sys::{ArrayBuffer, Uint8Array};
+use rkyv::AlignedVec;
 use tokio::sync::mpsc::{channel, Receiver};
 use wasm_bindgen::{prelude::Closure, JsCast};
 use wasm_bindgen_futures::spawn_local;
-use web_sys::{BinaryType, MessageEvent};
+use web_sys::{BinaryType, CloseEvent, MessageEvent};
 
 pub struct WebSocket {
     client: web_sys::WebSocket,
-    receiver: Receiver<Message>,
+    receiver: Receiver<Result<Message, Error>>,
 }
 
 impl WebSocket {
     pub fn new(ws_url: &str) -> Self {
         let client = web_sys::WebSocket::new(&format!("ws://{}", ws_url)).unwrap();
         client.set_binary_type(BinaryType::Arraybuffer); // TODO: check which one is faster, arraybuffer or blob
-        let (sender, receiver) = channel::<Message>(1024);
+        let (sender, receiver) = channel::<Result<Message, Error>>(1024);
+        let onclose_sender = sender.clone();
         let onmessage_callback = Closure::wrap(Box::new(move |event: MessageEvent| {
             let sender = sender.clone();
             spawn_local(async move {
                 if let Ok(array_buffer) = event.data().dyn_into::<ArrayBuffer>() {
-                    let array_buffer = Uint8Array::new(&array_buffer).to_vec();
-                    let msg =
-                        unsafe { rkyv::from_bytes_unchecked::<Message>(&array_buffer).unwrap() };
-                    sender.send(msg).await.unwrap(); // FIXME: unwraps
+                    // the receiver is only gone once loading stopped
+                    let _ = sender.send(decode(&array_buffer)).await;
                 }
             });
         }) as Box<dyn FnMut(MessageEvent)>);
         client.set_onmessage(Some(onmessage_callback.as_ref().unchecked_ref()));
         onmessage_callback.forget();
+        // whatever waits for a reply fails instead of waiting forever
+        let onclose_callback = Closure::wrap(Box::new(move |_: CloseEvent| {
+            let sender = onclose_sender.clone();
+            spawn_local(async move {
+                let _ = sender.send(Err(Error::WebSocket)).await;
+            });
+        }) as Box<dyn FnMut(CloseEvent)>);
+        client.set_onclose(Some(onclose_callback.as_ref().unchecked_ref()));
+        onclose_callback.forget();
         Self { client, receiver }
     }
+
+    pub fn send(&self, message: &Message) -> Result<(), Error> {
+        let bytes = serialize(message, 0)?;
+        self.client
+            .send_with_u8_array(&bytes)
+            .map_err(|_| Error::WebSocket)
+    }
+}
+
+// copied into aligned memory, archived values can't be read from wherever the buffer starts
+fn decode(array_buffer: &ArrayBuffer) -> Result<Message, Error> {
+    let array = Uint8Array::new(array_buffer);
+    let mut bytes = AlignedVec::with_capacity(array.length() as usize);
+    bytes.resize(array.length() as usize, 0);
+    array.copy_to(&mut bytes);
+    rkyv::from_bytes::<Message>(&bytes).map_err(|_| Error::Decode)
 }
 
 impl Stream for WebSocket {
-    type Item = Message;
+    type Item = Result<Message, Error>;
 
     fn poll_next(
         mut self: std::pin::Pin<&mut Self>,